@@ -1,28 +1,23 @@
 //! This module contains the build related functions
 
-use crate::hasher::Hasher;
+use crate::error::{Error, Result};
+use crate::hasher::{Hasher, Manifest};
 use crate::parser::{BuildConfig, OSConfig, TargetConfig};
+use crate::report::{Artifact, BuildReport, Diagnostic, Severity};
 use crate::utils::features::cfg_feat;
 use crate::utils::log::{log, LogLevel};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs;
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
 use walkdir::WalkDir;
 
-static BUILD_DIR: &str = "ruxgo_bld";
-static BIN_DIR: &str = "ruxgo_bld/bin";
-#[cfg(target_os = "windows")]
-static OBJ_DIR: &str = "ruxgo_bld/obj_win32";
-#[cfg(target_os = "linux")]
-static OBJ_DIR: &str = "ruxgo_bld/obj_linux";
-
 // ruxlibc info and ld script
 lazy_static! {
     static ref RUXLIBC_INC: String = {
@@ -44,12 +39,9 @@ lazy_static! {
         }
     };
 }
-static RUXLIBC_BIN: &str = "ruxgo_bld/bin/libc.a";
 static RUXLIBC_RUST_LIB: &str = "libruxlibc.a";
 
 // ruxmusl info
-static RUXMUSL_INC: &str = "ruxgo_bld/ruxmusl/install/include";
-static RUXMUSL_BIN: &str = "ruxgo_bld/ruxmusl/install/lib/libc.a";
 static RUXMUSL_RUST_LIB: &str = "libruxmusl.a";
 
 /// Represents a target
@@ -61,11 +53,22 @@ pub struct Target<'a> {
     dependant_includes: HashMap<String, Vec<String>>,
     pub bin_path: String,
     pub elf_path: String,
-    hash_file_path: String,
-    path_hash: HashMap<String, String>,
+    manifest_path: String,
+    manifest: Manifest,
     dependant_libs: Vec<Target<'a>>,
 }
 
+/// Describes how a source file of a target is compiled
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompileUnit {
+    pub file: String,
+    pub object: String,
+    /// The shell command compiling the file
+    pub command: String,
+    /// The headers the file includes, directly or through other headers
+    pub includes: Vec<String>,
+}
+
 /// Represents a source file (A single C or Cpp file)
 #[derive(Debug)]
 struct Src {
@@ -74,6 +77,7 @@ struct Src {
     obj_name: String,
     bin_path: String, // consider change to obj_path
     dependant_includes: Vec<String>,
+    command: String,
 }
 
 impl<'a> Target<'a> {
@@ -87,41 +91,20 @@ impl<'a> Target<'a> {
         os_config: &'a OSConfig,
         target_config: &'a TargetConfig,
         targets: &'a Vec<TargetConfig>,
-    ) -> Self {
+    ) -> Result<Self> {
         let srcs = Vec::new();
         let dependant_includes: HashMap<String, Vec<String>> = HashMap::new();
-        let mut bin_path = format!("{}/{}", BIN_DIR, target_config.name);
-        let mut elf_path = String::new();
-        #[cfg(target_os = "windows")]
-        match target_config.typ.as_str() {
-            "exe" => bin_path.push_str(".exe"),
-            "dll" => bin_path.push_str(".dll"),
-            "static" => bin_path.push_str(".lib"),
-            _ => (),
-        }
-        #[cfg(target_os = "linux")]
-        match target_config.typ.as_str() {
-            "exe" => {
-                elf_path = format!("{}.elf", bin_path);
-                bin_path.push_str(".bin");
-            }
-            "dll" => bin_path.push_str(".so"),
-            "static" => bin_path.push_str(".a"),
-            "object" => bin_path.push_str(".o"),
-            _ => (),
-        }
-        #[cfg(target_os = "windows")]
-        let hash_file_path = format!("ruxgo_bld/{}.win32.hash", &target_config.name);
-        #[cfg(target_os = "linux")]
-        let hash_file_path = format!("ruxgo_bld/{}.linux.hash", &target_config.name);
-        let path_hash = Hasher::load_hashes_from_file(&hash_file_path);
+        let paths = &build_config.paths;
+        let (bin_path, elf_path) = paths.target_bin(&target_config.name, &target_config.typ);
+        let manifest_path = paths.manifest_file(&target_config.name);
+        let manifest = Manifest::load(&manifest_path);
         let mut dependant_libs = Vec::new();
 
         // add dependant libs
         for dependant_lib in &target_config.deps {
             for target in targets {
                 if target.name == *dependant_lib {
-                    dependant_libs.push(Target::new(build_config, os_config, target, targets));
+                    dependant_libs.push(Target::new(build_config, os_config, target, targets)?);
                 }
             }
         }
@@ -132,25 +115,14 @@ impl<'a> Target<'a> {
                 && dep_lib.target_config.typ != "static"
                 && dep_lib.target_config.typ != "object"
             {
-                log(
-                    LogLevel::Error,
-                    "Can add only dll, static or object libs as dependant libs",
-                );
-                log(
-                    LogLevel::Error,
-                    &format!(
-                        "Target: {} is not a dll, static or object library",
-                        dep_lib.target_config.name
-                    ),
-                );
-                log(
-                    LogLevel::Error,
-                    &format!(
-                        "Target: {} is a {}",
-                        dep_lib.target_config.name, dep_lib.target_config.typ
-                    ),
-                );
-                std::process::exit(1);
+                return Err(Error::Config(format!(
+                    "Can add only dll, static or object libs as dependant libs\n\
+                     Target: {} is not a dll, static or object library\n\
+                     Target: {} is a {}",
+                    dep_lib.target_config.name,
+                    dep_lib.target_config.name,
+                    dep_lib.target_config.typ
+                )));
             }
             log(
                 LogLevel::Info,
@@ -158,46 +130,31 @@ impl<'a> Target<'a> {
             );
             if dep_lib.target_config.typ == "dll" && !dep_lib.target_config.name.starts_with("lib")
             {
-                log(
-                    LogLevel::Error,
-                    "Dependant dll lib name must start with lib",
-                );
-                log(
-                    LogLevel::Error,
-                    &format!(
-                        "Target: {} does not start with lib",
-                        dep_lib.target_config.name
-                    ),
-                );
-                std::process::exit(1);
+                return Err(Error::Config(format!(
+                    "Dependant dll lib name must start with lib\n\
+                     Target: {} does not start with lib",
+                    dep_lib.target_config.name
+                )));
             }
         }
         if target_config.deps.len() > dependant_libs.len() {
-            log(LogLevel::Error, "Dependant libs not found!");
-            log(
-                LogLevel::Error,
-                &format!("Dependant libs: {:?}", target_config.deps),
-            );
-            log(
-                LogLevel::Error,
-                &format!(
-                    "Found libs: {:?}",
-                    targets
-                        .iter()
-                        .map(|x| {
-                            if x.typ == "dll" || x.typ == "static" || x.typ == "object" {
-                                x.name.clone()
-                            } else {
-                                "".to_string()
-                            }
-                        })
-                        .collect::<Vec<String>>()
-                        .into_iter()
-                        .filter(|x| !x.is_empty())
-                        .collect::<Vec<String>>()
-                ),
-            );
-            std::process::exit(1);
+            return Err(Error::Config(format!(
+                "Dependant libs not found!\nDependant libs: {:?}\nFound libs: {:?}",
+                target_config.deps,
+                targets
+                    .iter()
+                    .map(|x| {
+                        if x.typ == "dll" || x.typ == "static" || x.typ == "object" {
+                            x.name.clone()
+                        } else {
+                            "".to_string()
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .into_iter()
+                    .filter(|x| !x.is_empty())
+                    .collect::<Vec<String>>()
+            )));
         }
         let mut target = Target::<'a> {
             srcs,
@@ -207,36 +164,52 @@ impl<'a> Target<'a> {
             dependant_includes,
             bin_path,
             elf_path,
-            path_hash,
-            hash_file_path,
+            manifest,
+            manifest_path,
             dependant_libs,
         };
-        target.get_srcs(&target_config.src);
-        target
+        target.get_srcs()?;
+        Ok(target)
+    }
+
+    /// Invalidates all objects of the target if the toolchain changed since the last build
+    /// # Arguments
+    /// * `fingerprint` - The fingerprint of the current toolchain
+    pub fn check_toolchain(&mut self, fingerprint: &str) {
+        if self.manifest.check_toolchain(fingerprint) {
+            log(
+                LogLevel::Info,
+                &format!(
+                    "Toolchain changed, rebuilding target: {}",
+                    self.target_config.name
+                ),
+            );
+        }
     }
 
     /// Builds the target
+    /// Returns the artifact of the target and the compiler warnings.
     /// # Arguments
-    /// * `gen_cc` - Generate compile_commands.json
     /// * `relink` - Determine whether to re-link
-    pub fn build(&mut self, gen_cc: bool, relink: bool) {
+    #[allow(clippy::len_zero)]
+    pub fn build(&mut self, relink: bool) -> Result<BuildReport> {
         let mut to_link: bool = false;
 
         // if the source file needs to be build, then to link
         let mut link_causer: Vec<&str> = Vec::new();
         let mut srcs_needed = 0;
         let total_srcs = self.srcs.len();
-        let mut src_ccs = Vec::new();
-        for src in &self.srcs {
-            let (to_build, _) = src.to_build(&self.path_hash);
+        let to_builds: Vec<bool> = self
+            .srcs
+            .par_iter()
+            .map(|src| src.to_build(&self.manifest).0)
+            .collect();
+        for (src, &to_build) in self.srcs.iter().zip(&to_builds) {
             if to_build {
                 to_link = true;
                 link_causer.push(&src.path);
                 srcs_needed += 1;
             }
-            if gen_cc {
-                src_ccs.push(self.gen_cc(src));
-            }
         }
 
         // if the source file is empty and dependant_libs is not empty, then to link
@@ -249,18 +222,6 @@ impl<'a> Target<'a> {
             to_link = true
         }
 
-        if gen_cc {
-            let mut file = std::fs::OpenOptions::new()
-                .append(true)
-                .open("./compile_commands.json")
-                .unwrap();
-            for src_cc in src_ccs {
-                if let Err(e) = writeln!(file, "{},", src_cc) {
-                    eprintln!("Couldn't write to file: {}", e);
-                }
-            }
-        }
-
         // log output when to link
         if to_link {
             log(
@@ -284,21 +245,20 @@ impl<'a> Target<'a> {
                     );
                 }
             }
-            if !Path::new(OBJ_DIR).exists() {
-                fs::create_dir(OBJ_DIR).unwrap_or_else(|why| {
-                    log(
-                        LogLevel::Error,
-                        &format!("Couldn't create obj dir: {}", why),
-                    );
-                    std::process::exit(1);
-                });
+            let obj_dir = self.build_config.paths.obj_dir();
+            if !Path::new(&obj_dir).exists() {
+                fs::create_dir_all(&obj_dir)
+                    .map_err(|why| Error::io("Couldn't create obj dir", why))?;
             }
         } else {
             log(
                 LogLevel::Log,
                 &format!("Target: {} is up to date", &self.target_config.name),
             );
-            return;
+            return Ok(BuildReport {
+                artifacts: vec![self.artifact(false)],
+                diagnostics: Vec::new(),
+            });
         }
 
         // parallel built
@@ -306,50 +266,61 @@ impl<'a> Target<'a> {
         let num_complete = Arc::new(Mutex::new(0));
         let src_hash_to_update = Arc::new(Mutex::new(Vec::new()));
         let warns = Arc::new(Mutex::new(Vec::new()));
-        self.srcs.par_iter().for_each(|src| {
-            let (to_build, _message) = src.to_build(&self.path_hash);
-            //log(LogLevel::Debug, &format!("{} => {}", src.path, to_build));
-            if to_build {
-                let warn = src.build(
-                    self.build_config,
-                    self.os_config,
-                    self.target_config,
-                    &self.dependant_libs,
-                );
-                if let Some(warn) = warn {
-                    warns.lock().unwrap().push(warn);
-                }
-                src_hash_to_update.lock().unwrap().push(src);
-                log(LogLevel::Info, &format!("Compiled: {}", src.path));
-                // If the RUXGO_LOG_LEVEL is not "Info" or "Debug", update the compilation progress bar
-                let log_level = std::env::var("RUXGO_LOG_LEVEL").unwrap_or("".to_string());
-                if !(log_level == "Info" || log_level == "Debug") {
-                    let mut num_complete = num_complete.lock().unwrap();
-                    *num_complete += 1;
-                    let progress_bar = progress_bar.lock().unwrap();
-                    let template = format!(
-                        "    {}{}",
-                        "Compiling :".cyan(),
-                        "[{bar:40.}] {pos}/{len} ({percent}%) {msg}[{elapsed_precise}] "
-                    );
-                    progress_bar.set_style(
-                        ProgressStyle::with_template(&template)
-                            .unwrap()
-                            .progress_chars("=>-"),
-                    );
-                    progress_bar.inc(1);
+        self.srcs
+            .par_iter()
+            .zip(to_builds.par_iter())
+            .try_for_each(|(src, &to_build)| {
+                if to_build {
+                    let warn = src.build()?;
+                    if let Some(warn) = warn {
+                        warns.lock().unwrap().push((src.path.clone(), warn));
+                    }
+                    src_hash_to_update.lock().unwrap().push(src);
+                    log(LogLevel::Info, &format!("Compiled: {}", src.path));
+                    // If the RUXGO_LOG_LEVEL is not "Info" or "Debug", update the compilation progress bar
+                    let log_level = std::env::var("RUXGO_LOG_LEVEL").unwrap_or("".to_string());
+                    if !(log_level == "Info" || log_level == "Debug") {
+                        let mut num_complete = num_complete.lock().unwrap();
+                        *num_complete += 1;
+                        let progress_bar = progress_bar.lock().unwrap();
+                        let template = format!(
+                            "    {}{}",
+                            "Compiling :".cyan(),
+                            "[{bar:40.}] {pos}/{len} ({percent}%) {msg}[{elapsed_precise}] "
+                        );
+                        progress_bar.set_style(
+                            ProgressStyle::with_template(&template)
+                                .unwrap()
+                                .progress_chars("=>-"),
+                        );
+                        progress_bar.inc(1);
+                    }
                 }
-            }
-        });
+                Ok(())
+            })?;
         let warns = warns.lock().unwrap();
         if warns.len() > 0 {
             log(LogLevel::Warn, "Warnings emitted during build:");
-            for warn in warns.iter() {
+            for (_, warn) in warns.iter() {
                 log(LogLevel::Warn, &format!("\t{}", warn));
             }
         }
+        let diagnostics = warns
+            .iter()
+            .map(|(path, warn)| Diagnostic {
+                severity: Severity::Warning,
+                target: self.target_config.name.clone(),
+                file: path.clone(),
+                message: warn.clone(),
+            })
+            .collect();
         for src in src_hash_to_update.lock().unwrap().iter() {
-            Hasher::save_hash(&src.path, &mut self.path_hash);
+            Hasher::save_hash(
+                &src.path,
+                &mut self.manifest,
+                &self.target_config.name,
+                Some(&src.command),
+            );
         }
 
         // links the target
@@ -357,44 +328,46 @@ impl<'a> Target<'a> {
             for src in link_causer {
                 log(LogLevel::Info, &format!("\tLinking file: {}", &src));
             }
-            for src in &self.srcs {
-                for include in &src.dependant_includes {
-                    Hasher::save_hash(include, &mut self.path_hash);
-                }
-            }
-            Hasher::save_hashes_to_file(&self.hash_file_path, &self.path_hash);
-            self.link(&self.dependant_libs);
+            let mut includes: Vec<&str> = self
+                .srcs
+                .iter()
+                .flat_map(|src| src.dependant_includes.iter().map(String::as_str))
+                .collect();
+            includes.sort_unstable();
+            includes.dedup();
+            Hasher::save_hashes(&includes, &mut self.manifest, &self.target_config.name);
+            self.manifest.save(&self.manifest_path)?;
+            self.link(&self.dependant_libs)?;
+        }
+        Ok(BuildReport {
+            artifacts: vec![self.artifact(to_link)],
+            diagnostics,
+        })
+    }
+
+    /// Returns the artifact of the target
+    /// # Arguments
+    /// * `linked` - Whether the target was linked by the current build
+    fn artifact(&self, linked: bool) -> Artifact {
+        Artifact {
+            target: self.target_config.name.clone(),
+            typ: self.target_config.typ.clone(),
+            path: self.bin_path.clone(),
+            elf_path: self.elf_path.clone(),
+            linked,
         }
     }
 
     /// Links the dependant libs(or targets)
     /// # Arguments
     /// * `dep_targets` - The targets that this target depends on
-    pub fn link(&self, dep_targets: &Vec<Target>) {
-        let mut objs = Vec::new();
-        if !Path::new(BIN_DIR).exists() {
-            fs::create_dir_all(BIN_DIR).unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Couldn't create build dir: {}", why),
-                );
-                std::process::exit(1);
-            })
-        }
-        for src in &self.srcs {
-            objs.push(&src.obj_name);
-        }
-        let mut cmd = String::new();
-        let mut cmd_bin = String::new();
-        if self.target_config.typ == "dll" {
-            cmd = self.link_dll(objs, dep_targets);
-        } else if self.target_config.typ == "static" {
-            cmd = self.link_static(objs);
-        } else if self.target_config.typ == "object" {
-            cmd = self.link_object(objs, dep_targets);
-        } else if self.target_config.typ == "exe" {
-            (cmd, cmd_bin) = self.link_exe(objs, dep_targets);
+    pub fn link(&self, dep_targets: &Vec<Target>) -> Result<()> {
+        let bin_dir = self.build_config.paths.bin_dir();
+        if !Path::new(&bin_dir).exists() {
+            fs::create_dir_all(&bin_dir)
+                .map_err(|why| Error::io("Couldn't create build dir", why))?;
         }
+        let (cmd, cmd_bin) = self.link_cmds(dep_targets);
 
         log(
             LogLevel::Log,
@@ -405,38 +378,77 @@ impl<'a> Target<'a> {
             .arg("-c")
             .arg(&cmd)
             .output()
-            .expect("failed to execute process");
+            .map_err(|why| Error::io("Failed to execute process", why))?;
         if output.status.success() {
             log(LogLevel::Log, "Linking successful");
-            Hasher::save_hashes_to_file(&self.hash_file_path, &self.path_hash); // ? check if repeated
+            self.manifest.save(&self.manifest_path)?; // ? check if repeated
         } else {
-            log(LogLevel::Error, "Linking failed");
-            log(LogLevel::Error, &format!(" Command: {}", &cmd));
-            log(
-                LogLevel::Error,
-                &format!("  Error: {}", String::from_utf8_lossy(&output.stderr)),
-            );
-            std::process::exit(1);
+            return Err(Error::Link(format!(
+                "Linking failed\n Command: {}\n  Error: {}",
+                &cmd,
+                String::from_utf8_lossy(&output.stderr)
+            )));
         }
         if !cmd_bin.is_empty() {
             let output_bin = Command::new("sh")
                 .arg("-c")
                 .arg(&cmd_bin)
                 .output()
-                .expect("failed to execute process");
+                .map_err(|why| Error::io("Failed to execute process", why))?;
             if output_bin.status.success() {
                 log(LogLevel::Info, &format!(" Bin_path: {}", &self.bin_path));
                 log(LogLevel::Info, &format!(" Elf_path: {}", &self.elf_path));
             } else {
-                log(LogLevel::Error, "  Rust-objcopy failed");
-                log(LogLevel::Error, &format!(" Command: {}", &cmd_bin));
-                log(
-                    LogLevel::Error,
-                    &format!("  Error: {}", String::from_utf8_lossy(&output_bin.stderr)),
-                );
-                std::process::exit(1);
+                return Err(Error::Link(format!(
+                    "Objcopy failed\n Command: {}\n  Error: {}",
+                    &cmd_bin,
+                    String::from_utf8_lossy(&output_bin.stderr)
+                )));
             }
         }
+        Ok(())
+    }
+
+    /// Returns the commands linking the target against its dependant libs
+    /// # Notes
+    /// The second command turns the ELF of an executable built for an OS into the bin,
+    /// it is empty otherwise.
+    pub fn link_commands(&self) -> (String, String) {
+        self.link_cmds(&self.dependant_libs)
+    }
+
+    /// Returns the commands linking the target, see `link_commands`
+    fn link_cmds(&self, dep_targets: &Vec<Target>) -> (String, String) {
+        let objs: Vec<&String> = self.srcs.iter().map(|src| &src.obj_name).collect();
+        match self.target_config.typ.as_str() {
+            "dll" => (self.link_dll(objs, dep_targets), String::new()),
+            "static" => (self.link_static(objs, dep_targets), String::new()),
+            "object" => (self.link_object(objs, dep_targets), String::new()),
+            "exe" => self.link_exe(objs, dep_targets),
+            _ => (String::new(), String::new()),
+        }
+    }
+
+    /// Returns how the source files of the target are compiled
+    pub fn compile_units(&self) -> Vec<CompileUnit> {
+        self.srcs
+            .iter()
+            .map(|src| {
+                let mut includes: Vec<String> = src
+                    .dependant_includes
+                    .iter()
+                    .filter(|include| **include != src.path)
+                    .cloned()
+                    .collect();
+                includes.sort_unstable();
+                CompileUnit {
+                    file: src.path.clone(),
+                    object: src.obj_name.clone(),
+                    command: src.command.clone(),
+                    includes,
+                }
+            })
+            .collect()
     }
 
     /// Links the dll targets
@@ -445,7 +457,7 @@ impl<'a> Target<'a> {
         if !self.target_config.linker.is_empty() {
             cmd.push_str(&self.target_config.linker);
         } else {
-            cmd.push_str(&self.build_config.compiler.read().unwrap());
+            cmd.push_str(&self.build_config.toolchain.cc);
         }
         cmd.push_str(" -shared");
         cmd.push_str(" -o ");
@@ -456,8 +468,13 @@ impl<'a> Target<'a> {
         }
         cmd.push(' ');
 
-        // link other dependant libraries
+        // link other dependant libraries, object ones become part of the dll
         for dep_target in dep_targets {
+            if dep_target.target_config.typ == "object" {
+                cmd.push_str(&dep_target.bin_path);
+                cmd.push(' ');
+                continue;
+            }
             dep_target
                 .target_config
                 .include_dir
@@ -476,7 +493,7 @@ impl<'a> Target<'a> {
         // add -L library search path
         if !self.dependant_libs.is_empty() {
             cmd.push_str(" -L");
-            cmd.push_str(BIN_DIR);
+            cmd.push_str(&self.build_config.paths.bin_dir());
             cmd.push_str(" -Wl,-rpath,\'$ORIGIN\' "); // '$ORIGIN' represents the directory path where the executable is located
             cmd.push(' ');
         }
@@ -488,9 +505,15 @@ impl<'a> Target<'a> {
     }
 
     /// Links the static targets
-    fn link_static(&self, objs: Vec<&String>) -> String {
+    /// # Notes
+    /// Dependant object libs are archived with the objects of the target.
+    fn link_static(&self, objs: Vec<&String>, dep_targets: &[Target]) -> String {
         let mut cmd = String::new();
-        cmd.push_str(&self.target_config.archive);
+        if !self.target_config.archive.is_empty() {
+            cmd.push_str(&self.target_config.archive);
+        } else {
+            cmd.push_str(&self.build_config.toolchain.ar);
+        }
         cmd.push(' ');
         cmd.push_str(&self.target_config.ldflags);
         cmd.push(' ');
@@ -499,6 +522,12 @@ impl<'a> Target<'a> {
             cmd.push(' ');
             cmd.push_str(obj);
         }
+        for dep_target in dep_targets {
+            if dep_target.target_config.typ == "object" {
+                cmd.push(' ');
+                cmd.push_str(&dep_target.bin_path);
+            }
+        }
 
         cmd
     }
@@ -509,7 +538,7 @@ impl<'a> Target<'a> {
         if !self.target_config.linker.is_empty() {
             cmd.push_str(&self.target_config.linker);
         } else {
-            cmd.push_str(&self.build_config.compiler.read().unwrap());
+            cmd.push_str(&self.build_config.toolchain.cc);
         }
         cmd.push(' ');
         cmd.push_str(&self.target_config.ldflags);
@@ -535,7 +564,7 @@ impl<'a> Target<'a> {
         if !self.target_config.linker.is_empty() {
             cmd.push_str(&self.target_config.linker);
         } else {
-            cmd.push_str(&self.build_config.compiler.read().unwrap());
+            cmd.push_str(&self.build_config.toolchain.ld);
         }
         cmd.push(' ');
 
@@ -562,7 +591,7 @@ impl<'a> Target<'a> {
             // link ulib and os
             if self.os_config.ulib == "ruxlibc" {
                 cmd.push(' ');
-                cmd.push_str(RUXLIBC_BIN);
+                cmd.push_str(&self.build_config.paths.ruxlibc_bin());
                 cmd.push(' ');
                 let mode = if !self.os_config.platform.mode.is_empty() {
                     &self.os_config.platform.mode
@@ -570,12 +599,15 @@ impl<'a> Target<'a> {
                     "debug"
                 };
                 cmd.push_str(&format!(
-                    "{}/target/{}/{}/{}",
-                    BUILD_DIR, &self.os_config.platform.target, mode, RUXLIBC_RUST_LIB
+                    "{}/{}/{}/{}",
+                    self.build_config.paths.target_dir(),
+                    &self.os_config.platform.target,
+                    mode,
+                    RUXLIBC_RUST_LIB
                 ));
             } else if self.os_config.ulib == "ruxmusl" {
                 cmd.push(' ');
-                cmd.push_str(RUXMUSL_BIN);
+                cmd.push_str(&self.build_config.paths.ruxmusl_bin());
                 cmd.push(' ');
                 let mode = if !self.os_config.platform.mode.is_empty() {
                     &self.os_config.platform.mode
@@ -583,8 +615,11 @@ impl<'a> Target<'a> {
                     "debug"
                 };
                 cmd.push_str(&format!(
-                    "{}/target/{}/{}/{}",
-                    BUILD_DIR, &self.os_config.platform.target, mode, RUXMUSL_RUST_LIB
+                    "{}/{}/{}/{}",
+                    self.build_config.paths.target_dir(),
+                    &self.os_config.platform.target,
+                    mode,
+                    RUXMUSL_RUST_LIB
                 ));
             }
 
@@ -604,8 +639,8 @@ impl<'a> Target<'a> {
 
             // generate a bin file
             cmd_bin.push_str(&format!(
-                "rust-objcopy --binary-architecture={}",
-                &self.os_config.platform.arch
+                "{} --binary-architecture={}",
+                &self.build_config.toolchain.objcopy, &self.os_config.platform.arch
             ));
             cmd_bin.push(' ');
             cmd_bin.push_str(&self.elf_path);
@@ -642,7 +677,7 @@ impl<'a> Target<'a> {
                     cmd.push(' ');
                     // added -L library search path
                     cmd.push_str(" -L");
-                    cmd.push_str(BIN_DIR);
+                    cmd.push_str(&self.build_config.paths.bin_dir());
                     cmd.push_str(" -Wl,-rpath,\'$ORIGIN\' "); // '$ORIGIN' represents the directory path where the executable is located
                     cmd.push(' ');
                 }
@@ -653,192 +688,56 @@ impl<'a> Target<'a> {
         (cmd, cmd_bin)
     }
 
-    /// Generates the compile_commands.json file for a src
-    fn gen_cc(&self, src: &Src) -> String {
-        let mut cc = String::new();
-        cc.push_str("{\n"); // Json start
-        if *self.build_config.compiler.read().unwrap() == "clang++"
-            || *self.build_config.compiler.read().unwrap() == "g++"
-        {
-            cc.push_str("\t\"command\": \"c++");
-        } else if *self.build_config.compiler.read().unwrap() == "clang"
-            || *self.build_config.compiler.read().unwrap() == "gcc"
-        {
-            cc.push_str("\t\"command\": \"cc");
-        } else {
-            log(
-                LogLevel::Error,
-                &format!(
-                    "Compiler: {} is not supported",
-                    &self.build_config.compiler.read().unwrap()
-                ),
-            );
-            log(
-                LogLevel::Error,
-                "Supported compilers: clang++, g++, clang, gcc",
-            );
-            std::process::exit(1);
-        }
-        cc.push_str(" -c -o ");
-        cc.push_str(&src.obj_name);
-        self.target_config.include_dir.iter().for_each(|include| {
-            cc.push_str(" -I");
-            cc.push_str(include);
-        });
-
-        for lib in &self.dependant_libs {
-            lib.target_config.include_dir.iter().for_each(|include| {
-                cc.push_str(" -I");
-                cc.push_str(include);
-            });
-        }
-
-        cc.push(' ');
-        let cflags = &self.target_config.cflags;
-
-        let subcmds = cflags.split('`').collect::<Vec<&str>>();
-        // Take even entries are non-subcmds and odd entries are subcmds
-        let (subcmds, non_subcmds): (Vec<String>, String) = subcmds.iter().enumerate().fold(
-            (Vec::new(), String::new()),
-            |(mut subcmds, mut non_subcmds), (i, subcmd)| {
-                if i % 2 != 0 {
-                    subcmds.push(subcmd.to_string());
-                } else {
-                    non_subcmds.push_str(subcmd);
-                    non_subcmds.push(' ');
-                }
-                (subcmds, non_subcmds)
-            },
-        );
-
-        cc.push_str(&non_subcmds);
-
-        for subcmd in subcmds {
-            let cmd_output = Command::new("sh")
-                .arg("-c")
-                .arg(&subcmd)
-                .output()
-                .expect("failed to execute process");
-            if cmd_output.status.success() {
-                let stdout = String::from_utf8_lossy(&cmd_output.stdout);
-                let stdout = stdout.replace('\n', " ");
-                cc.push_str(&stdout);
-            } else {
-                let stderr = String::from_utf8_lossy(&cmd_output.stderr);
-                log(
-                    LogLevel::Error,
-                    &format!("Failed to execute subcmd: {}", &subcmd),
-                );
-                log(LogLevel::Error, &format!("  Stderr: {}", stderr));
-                std::process::exit(1);
-            }
-        }
-
-        #[cfg(target_os = "linux")]
-        if self.target_config.typ == "dll" {
-            cc.push_str("-fPIC ");
-        }
-
-        cc.push_str(&src.path);
-        cc.push_str("\",\n"); // Json end
-                              // other info: "directory","file"
-        let mut dirent = String::new();
-        dirent.push_str("\t\"directory\": \"");
-        dirent.push_str(
-            &std::env::current_dir()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .replace('\\', "/"),
-        );
-        dirent.push_str("\",\n");
-        let dirent = dirent.replace('/', "\\\\").replace("\\\\.\\\\", "\\\\"); // aim to Windows
-        cc.push_str(&dirent);
-        let mut fileent = String::new();
-        fileent.push_str("\t\"file\": \"");
-        fileent.push_str(
-            &std::env::current_dir()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .replace('\\', "/"),
-        );
-        fileent.push('/');
-        fileent.push_str(&src.path);
-        fileent.push('\"');
-        let fileent = fileent.replace('/', "\\\\").replace("\\\\.\\\\", "\\\\");
-        cc.push_str(&fileent);
-
-        cc.push_str("\n}");
-        #[cfg(target_os = "linux")]
-        return cc.replace("\\\\", "/");
-        #[cfg(target_os = "windows")]
-        return cc;
-    }
-
     /// Recursively gets all the source files in the given root path
     /// # Notes
-    /// The source is first filtered through the `src_only` and `src_exclude` fields
-    fn get_srcs(&mut self, root_path: &str) {
-        for entry in WalkDir::new(root_path).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            let path_str = path.to_str().unwrap_or_default();
-            #[cfg(target_os = "windows")]
-            let path_str = path_str.replace('\\', "/");
-            if self.should_exclude(path_str) {
-                continue;
-            }
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if (ext == "cpp" || ext == "c") && self.should_include(path_str) {
-                        self.add_src(path_str.to_owned());
-                    }
-                }
-            }
-        }
-    }
-
-    /// Exclusion logic: Check if the path is in src_exclude
-    fn should_exclude(&self, path: &str) -> bool {
-        self.target_config
-            .src_exclude
-            .iter()
-            .any(|excluded| path.contains(excluded))
-    }
-
-    /// Inclusion logic: Apply src_only logic only to files
-    fn should_include(&self, path: &str) -> bool {
-        if self.target_config.src_only.is_empty() {
-            return true;
+    /// The source is first filtered through the `src_only` and `src_exclude` fields.
+    fn get_srcs(&mut self) -> Result<()> {
+        let paths = self.target_config.get_src_names()?;
+        let obj_names = get_obj_names(&paths, &self.target_config.src);
+        for (path, obj_name) in paths.into_iter().zip(obj_names) {
+            self.add_src(path, &obj_name)?;
         }
-        self.target_config
-            .src_only
-            .iter()
-            .any(|included| path.contains(included))
+        Ok(())
     }
 
     /// Adds a source file to the target's srcs field
-    fn add_src(&mut self, path: String) {
+    fn add_src(&mut self, path: String, obj_name: &str) -> Result<()> {
         let name = Target::get_src_name(&path);
-        let obj_name = self.get_src_obj_name(&name);
-        let dependant_includes = self.get_dependant_includes(&path);
+        let obj_name = self.get_src_obj_name(obj_name);
+        let dependant_includes = self.get_dependant_includes(&path)?;
         let bin_path = self.bin_path.clone();
-        self.srcs
-            .push(Src::new(path, name, obj_name, bin_path, dependant_includes));
+        let command = Src::compile_cmd(
+            &path,
+            &obj_name,
+            self.build_config,
+            self.os_config,
+            self.target_config,
+            &self.dependant_libs,
+        )?;
+        self.srcs.push(Src::new(
+            path,
+            name,
+            obj_name,
+            bin_path,
+            dependant_includes,
+            command,
+        ));
+        Ok(())
     }
 
     /// Returns the file name without the extension from the path
     fn get_src_name(path: &str) -> String {
-        let path_buf = PathBuf::from(path);
-        let file_name = path_buf.file_name().unwrap().to_str().unwrap();
-        let name = file_name.split('.').next().unwrap();
-        name.to_string()
+        let file_name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        file_name.split('.').next().unwrap_or_default().to_string()
     }
 
-    /// Returns the object file name corresponding to the source file
+    /// Returns the object file path for the given object name
     fn get_src_obj_name(&self, src_name: &str) -> String {
         let mut obj_name = String::new();
-        obj_name.push_str(OBJ_DIR);
+        obj_name.push_str(&self.build_config.paths.obj_dir());
         obj_name.push('/');
         obj_name.push_str(&self.target_config.name);
         obj_name.push('-');
@@ -848,27 +747,28 @@ impl<'a> Target<'a> {
     }
 
     /// Returns a vector of .h or .hpp files the given C/C++ depends on
-    fn get_dependant_includes(&mut self, path: &str) -> Vec<String> {
+    fn get_dependant_includes(&mut self, path: &str) -> Result<Vec<String>> {
+        // The output of a step that has not run yet, the target is resolved before building
+        if !Path::new(path).exists() {
+            return Ok(Vec::new());
+        }
         let mut result = HashSet::new();
         // Use the stack to handle recursive paths
         let mut to_process = vec![path.to_string()];
-        let include_substrings: HashSet<String> = self
-            .get_include_substrings(path)
-            .unwrap_or_default()
-            .into_iter()
-            .collect();
+        let include_substrings: HashSet<String> =
+            self.get_include_substrings(path)?.into_iter().collect();
         if include_substrings.is_empty() {
-            return Vec::new();
+            return Ok(Vec::new());
         }
         while let Some(current_path) = to_process.pop() {
             if !result.insert(current_path.clone()) {
                 // If this path has already been processed, skip it
                 continue;
             }
-            if !self.dependant_includes.contains_key(&current_path) {
-                self.dependant_includes
-                    .insert(current_path.clone(), Vec::new());
-            }
+            let dependant_includes = self
+                .dependant_includes
+                .entry(current_path.clone())
+                .or_default();
 
             for include_dir in &self.target_config.include_dir {
                 for entry in WalkDir::new(include_dir).into_iter().filter_map(|e| e.ok()) {
@@ -880,10 +780,7 @@ impl<'a> Target<'a> {
                     {
                         let path_str = path.to_string_lossy().to_string();
                         if result.insert(path_str.clone()) {
-                            self.dependant_includes
-                                .get_mut(&current_path)
-                                .unwrap()
-                                .push(path_str.clone());
+                            dependant_includes.push(path_str.clone());
                             to_process.push(path_str);
                         }
                     }
@@ -891,30 +788,26 @@ impl<'a> Target<'a> {
             }
         }
 
-        result.into_iter().collect()
+        Ok(result.into_iter().collect())
     }
 
     /// Returns a list of substrings that contain "#include \"" in the source file
-    fn get_include_substrings(&self, path: &str) -> Option<Vec<String>> {
-        let file = std::fs::File::open(path);
-        if file.is_err() {
-            // If the software is self-developed, enable this debug option
-            //log(LogLevel::Debug, &format!("Failed to get include substrings for file: {}", path));
-            return None;
-        }
-        let mut file = file.unwrap();
-        let mut buf = String::new();
-        file.read_to_string(&mut buf).unwrap();
+    fn get_include_substrings(&self, path: &str) -> Result<Vec<String>> {
+        let contents = fs::read(path)
+            .map_err(|why| Error::io(format!("Could not read source '{}'", path), why))?;
+        // Sources are not always valid UTF-8, e.g. in comments
+        let contents = String::from_utf8_lossy(&contents);
 
-        let lines = buf.lines();
         let mut include_substrings = Vec::new();
-        for line in lines {
-            if line.starts_with("#include \"") {
-                let include_path = line.split('\"').nth(1).unwrap().to_owned();
-                include_substrings.push(include_path);
+        for line in contents.lines() {
+            let include_path = line
+                .strip_prefix("#include \"")
+                .and_then(|rest| rest.split('"').next());
+            if let Some(include_path) = include_path {
+                include_substrings.push(include_path.to_owned());
             }
         }
-        Some(include_substrings)
+        Ok(include_substrings)
     }
 }
 
@@ -926,6 +819,7 @@ impl Src {
         obj_name: String,
         bin_path: String,
         dependant_includes: Vec<String>,
+        command: String,
     ) -> Self {
         Self {
             path,
@@ -933,22 +827,27 @@ impl Src {
             obj_name,
             bin_path,
             dependant_includes,
+            command,
         }
     }
 
     /// Determines whether the object file needs to be rebuilt
-    fn to_build(&self, path_hash: &HashMap<String, String>) -> (bool, String) {
+    fn to_build(&self, manifest: &Manifest) -> (bool, String) {
         if !Path::new(&self.bin_path).exists() {
             let result = (true, format!("\tBinary does not exist: {}", &self.bin_path));
             return result;
         }
 
-        if Hasher::is_file_changed(&self.path, path_hash) {
+        if Hasher::is_file_changed(&self.path, manifest) {
             let result = (true, format!("\tSource file has changed: {}", &self.path));
             return result;
         }
+        if Hasher::is_command_changed(&self.path, &self.command, manifest) {
+            let result = (true, format!("\tCommand has changed: {}", &self.path));
+            return result;
+        }
         for dependant_include in &self.dependant_includes {
-            if Hasher::is_file_changed(&dependant_include.clone(), path_hash) {
+            if Hasher::is_file_changed(&dependant_include.clone(), manifest) {
                 let result = (
                     true,
                     format!(
@@ -966,16 +865,19 @@ impl Src {
         )
     }
 
-    /// Builds the source files
-    fn build(
-        &self,
+    /// Returns the command that compiles the source file
+    fn compile_cmd(
+        path: &str,
+        obj_name: &str,
         build_config: &BuildConfig,
         os_config: &OSConfig,
         target_config: &TargetConfig,
         dependant_libs: &Vec<Target>,
-    ) -> Option<String> {
+    ) -> Result<String> {
         let mut cmd = String::new();
-        cmd.push_str(&build_config.compiler.read().unwrap());
+        let compiler = build_config.toolchain.compiler_for(path);
+        cmd.push_str(compiler);
+        cmd.push_str(&build_config.toolchain.compiler_flags(compiler));
         // If os exist
         let mut os_cflags = String::new();
         if !os_config.name.is_empty() {
@@ -983,7 +885,7 @@ impl Src {
             if os_config.ulib == "ruxlibc" {
                 os_cflags.push_str(" -isystem");
                 os_cflags.push_str(RUXLIBC_INC.as_str());
-                let (_, lib_feats) = cfg_feat(os_config);
+                let (_, lib_feats) = cfg_feat(os_config)?;
                 // generate the preprocessing macro definition
                 for lib_feat in lib_feats {
                     let processed_lib_feat = lib_feat.to_uppercase().replace('-', "_");
@@ -995,7 +897,7 @@ impl Src {
                 ));
             } else if os_config.ulib == "ruxmusl" {
                 os_cflags.push_str(" -isystem");
-                os_cflags.push_str(RUXMUSL_INC);
+                os_cflags.push_str(&build_config.paths.ruxmusl_inc());
             }
             if os_config.platform.mode == "release" {
                 os_cflags.push_str(" -O3");
@@ -1026,7 +928,7 @@ impl Src {
             cmd.push_str(include);
         });
         cmd.push_str(" -o ");
-        cmd.push_str(&self.obj_name);
+        cmd.push_str(obj_name);
 
         // consider some includes in other depandant_libs
         for dependant_lib in dependant_libs {
@@ -1041,19 +943,26 @@ impl Src {
         }
 
         cmd.push_str(" -c ");
-        cmd.push_str(&self.path);
+        cmd.push_str(path);
 
         if target_config.typ == "dll" {
             cmd.push_str(" -fPIC");
         }
 
+        Ok(cmd)
+    }
+
+    /// Builds the source files
+    #[allow(clippy::len_zero)]
+    fn build(&self) -> Result<Option<String>> {
+        let cmd = &self.command;
         log(LogLevel::Info, &format!("Building: {}", &self.name));
         log(LogLevel::Info, &format!("  Command: {}", &cmd));
         let output = Command::new("sh")
             .arg("-c")
-            .arg(&cmd)
+            .arg(cmd)
             .output()
-            .expect("failed to execute process");
+            .map_err(|why| Error::io("Failed to execute process", why))?;
         if output.status.success() {
             log(LogLevel::Info, &format!("  Success: {}", &self.name));
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -1062,20 +971,101 @@ impl Src {
             }
             let stderr = String::from_utf8_lossy(&output.stderr);
             if stderr.len() > 0 {
-                return Some(stderr.to_string());
+                return Ok(Some(stderr.to_string()));
             }
-            None
+            Ok(None)
         } else {
-            log(LogLevel::Error, &format!("  Command: {}", &cmd));
-            log(
-                LogLevel::Error,
-                &format!("  Stdout: {}", String::from_utf8_lossy(&output.stdout)),
-            );
-            log(
-                LogLevel::Error,
-                &format!("  Stderr: {}", String::from_utf8_lossy(&output.stderr)),
-            );
-            std::process::exit(1);
+            Err(Error::Compile(format!(
+                "Failed to compile: {}\n  Command: {}\n  Stdout: {}\n  Stderr: {}",
+                &self.path,
+                &cmd,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            )))
         }
     }
 }
+
+/// Returns the object names of the sources, without the target name and extension
+/// # Notes
+/// An object is named after the file name of its source without extension. Sources that
+/// share this name, e.g. `main.c` in two directories, are named after their path relative
+/// to the src root instead, e.g. `core-main` and `os-unix-main`; if that is shared as
+/// well, a hash of the source path is appended.
+/// # Arguments
+/// * `paths` - The source files of the target
+/// * `roots` - The src roots of the target
+fn get_obj_names(paths: &[String], roots: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = paths
+        .iter()
+        .map(|path| Target::get_src_name(path))
+        .collect();
+    let renames: [&dyn Fn(&str) -> String; 2] = [&|path| get_src_rel_name(path, roots), &|path| {
+        let hash = Hasher::hash_string(path);
+        format!("{}-{}", get_src_rel_name(path, roots), &hash[..8])
+    }];
+    for rename in renames {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for name in &names {
+            *counts.entry(name).or_default() += 1;
+        }
+        let shared: Vec<usize> = (0..names.len())
+            .filter(|&i| counts[names[i].as_str()] > 1)
+            .collect();
+        for i in shared {
+            names[i] = rename(&paths[i]);
+        }
+    }
+    names
+}
+
+/// Returns the path of a source relative to its src root, joined by `-` and without extension
+fn get_src_rel_name(path: &str, roots: &[String]) -> String {
+    let path = Path::new(path);
+    let rel_path = roots
+        .iter()
+        .filter_map(|root| path.strip_prefix(root).ok())
+        .find(|rel_path| !rel_path.as_os_str().is_empty())
+        .unwrap_or(path);
+    let mut parts: Vec<String> = rel_path
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.components())
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    parts.push(Target::get_src_name(&rel_path.to_string_lossy()));
+    parts.join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn object_names_are_unique() {
+        let roots = strings(&["./src/core", "./src/os", "./other"]);
+        let paths = strings(&[
+            "./src/core/main.c",
+            "./src/core/util.c",
+            "./src/os/unix/main.c",
+            "./other/unix/main.c",
+            "./src/os/event/util.c",
+            "./src/os/ssl.c",
+        ]);
+        let names = get_obj_names(&paths, &roots);
+        // Unique file names are kept, shared ones are named after the relative path
+        assert_eq!(names[..2], strings(&["main", "util"]));
+        assert_eq!(names[4..], strings(&["event-util", "ssl"]));
+        // Shared relative paths get a hash of the source path
+        assert!(names[2].starts_with("unix-main-"));
+        assert!(names[3].starts_with("unix-main-"));
+        assert_ne!(names[2], names[3]);
+    }
+}
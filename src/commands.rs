@@ -1,31 +1,25 @@
 //! This module contains code that handles various CLI flags
 
 use crate::builder::Target;
+use crate::compdb;
+use crate::error::{Error, Result};
 use crate::global_cfg::GlobalConfig;
 use crate::hasher::Hasher;
-use crate::parser::{self, BuildConfig, OSConfig, QemuConfig, TargetConfig};
+use crate::parser::{self, BuildConfig, OSConfig, QemuConfig, SourceConfig, TargetConfig};
+use crate::patches;
+use crate::report::{BuildReport, CleanReport, Diagnostic, PatchReport, RunReport, Severity};
+use crate::sources;
+use crate::steps;
 use crate::utils::env;
 use crate::utils::features;
 use crate::utils::log::{log, LogLevel};
+use crate::utils::paths;
 use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
-static BUILD_DIR: &str = "ruxgo_bld";
-static BIN_DIR: &str = "ruxgo_bld/bin";
-#[cfg(target_os = "windows")]
-static OBJ_DIR: &str = "ruxgo_bld/obj_win32";
-#[cfg(target_os = "linux")]
-static OBJ_DIR: &str = "ruxgo_bld/obj_linux";
-static TARGET_DIR: &str = "ruxgo_bld/target";
-
-// OSConfig hash file
-static OSCONFIG_HASH_FILE: &str = "ruxgo_bld/os_config.hash";
-
 // ruxlibc info
-static RUXLIBC_BIN: &str = "ruxgo_bld/bin/libc.a";
-static RUXLIBC_HASH_PATH: &str = "ruxgo_bld/libc.linux.hash";
 lazy_static! {
     static ref RUXLIBC_SRC: String = {
         let path1 = "../ruxos/ulib/ruxlibc/c";
@@ -39,7 +33,6 @@ lazy_static! {
 }
 
 // ruxmusl info
-static RUXMUSL_DIR: &str = "ruxgo_bld/ruxmusl";
 lazy_static! {
     static ref ULIB_RUXMUSL: String = {
         let path1 = "../ruxos/ulib/ruxmusl";
@@ -52,55 +45,43 @@ lazy_static! {
     };
     static ref ULIB_RUXMUSL_SRC: String = format!("{}/musl-1.2.3", *ULIB_RUXMUSL);
 }
+/// The ruxos directory the os is built in, if it exists
+pub static RUXOS_DIR: &str = "../ruxos";
+
+static MUSL_URL: &str = "https://musl.libc.org/releases/musl-1.2.3.tar.gz";
+static MUSL_SHA256: &str = "7d5b0b6062521e4627e099e4c9dc8248d32a30285e959b7eecaa780cf8cfd4a4";
 
 /// Cleans the local targets
+/// Returns the removed files and the ones that could not be removed.
 /// # Arguments
+/// * `build_config` - The local build configuration
 /// * `targets` - A vector of targets to clean
 /// * `os_config` - The local os configuration
 /// * `choices` - A vector of choices to select which components to delete
-pub fn clean(targets: &Vec<TargetConfig>, os_config: &OSConfig, choices: Vec<String>) {
-    // Helper function to remove a directory or a file and log the result
-    let remove_dir = |dir_path: &str| {
-        if Path::new(dir_path).exists() {
-            if let Err(error) = fs::remove_dir_all(dir_path) {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not remove directory '{}': {}", dir_path, error),
-                );
-            } else {
-                log(LogLevel::Log, &format!("Cleaning: {}", dir_path));
-            }
-        }
-    };
-    let remove_file = |file_path: &str| {
-        if Path::new(file_path).exists() {
-            if let Err(error) = fs::remove_file(file_path) {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not remove file '{}': {}", file_path, error),
-                );
-            } else if file_path.ends_with(".hash") {
-                log(LogLevel::Info, &format!("Cleaning: {}", file_path));
-            } else {
-                log(LogLevel::Log, &format!("Cleaning: {}", file_path));
-            }
-        }
-    };
+pub fn clean(
+    build_config: &BuildConfig,
+    targets: &Vec<TargetConfig>,
+    os_config: &OSConfig,
+    choices: Vec<String>,
+) -> CleanReport {
+    let paths = &build_config.paths;
+    let mut report = CleanReport::default();
 
     // Removes os if choices includes "OS" or choices includes "All"
     if choices.contains(&String::from("OS")) || choices.contains(&String::from("All")) {
-        remove_dir(TARGET_DIR);
-        remove_file(OSCONFIG_HASH_FILE);
+        remove_dir(&paths.target_dir(), &mut report);
+        remove_file(&paths.os_config_hash(), &mut report);
     }
 
     // Removes ulib if choices includes "Ulib" or choices includes "All"
     if choices.contains(&String::from("Ulib")) || choices.contains(&String::from("All")) {
-        remove_file(OSCONFIG_HASH_FILE);
+        remove_file(&paths.os_config_hash(), &mut report);
         if os_config.ulib == "ruxlibc" {
-            remove_file(RUXLIBC_HASH_PATH);
-            remove_file(RUXLIBC_BIN);
+            remove_file(&paths.manifest_file("libc"), &mut report);
+            remove_file(&paths.legacy_hash_file("libc"), &mut report);
+            remove_file(&paths.ruxlibc_bin(), &mut report);
         } else if os_config.ulib == "ruxmusl" {
-            remove_dir(RUXMUSL_DIR);
+            remove_dir(&paths.ruxmusl_dir(), &mut report);
         }
     }
 
@@ -108,13 +89,16 @@ pub fn clean(targets: &Vec<TargetConfig>, os_config: &OSConfig, choices: Vec<Str
     if choices.contains(&String::from("App_bins")) || choices.contains(&String::from("All")) {
         // removes local bins of targets
         for target in targets {
-            #[cfg(target_os = "windows")]
-            let hash_path = format!("ruxgo_bld/{}.win32.hash", &target.name);
-            #[cfg(target_os = "linux")]
-            let hash_path = format!("ruxgo_bld/{}.linux.hash", &target.name);
-            remove_file(&hash_path);
-            if Path::new(BIN_DIR).exists() {
-                let mut bin_name = format!("{}/{}", BIN_DIR, target.name);
+            remove_file(&paths.manifest_file(&target.name), &mut report);
+            remove_file(&paths.legacy_hash_file(&target.name), &mut report);
+            for step in &target.steps {
+                remove_file(
+                    &paths.step_manifest_file(&target.name, &step.name),
+                    &mut report,
+                );
+            }
+            if Path::new(&paths.bin_dir()).exists() {
+                let mut bin_name = format!("{}/{}", paths.bin_dir(), target.name);
                 let mut elf_name = String::new();
                 #[cfg(target_os = "windows")]
                 match target.typ.as_str() {
@@ -133,189 +117,126 @@ pub fn clean(targets: &Vec<TargetConfig>, os_config: &OSConfig, choices: Vec<Str
                     "object" => bin_name.push_str(".o"),
                     _ => (),
                 }
-                remove_file(&bin_name);
-                remove_file(&elf_name);
+                remove_file(&bin_name, &mut report);
+                remove_file(&elf_name, &mut report);
             }
         }
     }
 
     // Removes obj if choices includes "Obj" or choices includes "All"
     if choices.contains(&String::from("Obj")) || choices.contains(&String::from("All")) {
-        remove_dir(OBJ_DIR);
+        remove_dir(&paths.obj_dir(), &mut report);
+        remove_dir(&paths.legacy_obj_dir(), &mut report);
     }
 
     // Removes all if choices includes "All"
     if choices.contains(&String::from("All")) {
-        remove_dir(BUILD_DIR);
+        // The patch states are removed with the build directory, restore what they patched
+        let mut reverted = true;
+        for (dir, _) in patched_dirs(build_config, targets) {
+            if let Err(error) = patches::revert_patches(paths, dir) {
+                reverted = false;
+                let message = format!(
+                    "Could not revert the patches of '{}', keeping {}: {}",
+                    dir, paths.root, error
+                );
+                log(LogLevel::Error, &message);
+                report.diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    target: String::new(),
+                    file: dir.to_string(),
+                    message,
+                });
+            }
+        }
+        if reverted {
+            remove_dir(&paths.root, &mut report);
+        }
+    }
+    report
+}
+
+/// Removes a directory, logs the result and records it in the clean report
+fn remove_dir(dir_path: &str, report: &mut CleanReport) {
+    if Path::new(dir_path).exists() {
+        if let Err(error) = fs::remove_dir_all(dir_path) {
+            let message = format!("Could not remove directory '{}': {}", dir_path, error);
+            log(LogLevel::Error, &message);
+            report.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                target: String::new(),
+                file: dir_path.to_string(),
+                message,
+            });
+        } else {
+            log(LogLevel::Log, &format!("Cleaning: {}", dir_path));
+            report.removed.push(dir_path.to_string());
+        }
+    }
+}
+
+/// Removes a file, logs the result and records it in the clean report
+fn remove_file(file_path: &str, report: &mut CleanReport) {
+    if Path::new(file_path).exists() {
+        if let Err(error) = fs::remove_file(file_path) {
+            let message = format!("Could not remove file '{}': {}", file_path, error);
+            log(LogLevel::Error, &message);
+            report.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                target: String::new(),
+                file: file_path.to_string(),
+                message,
+            });
+            return;
+        }
+        if file_path.ends_with(".hash") || file_path.ends_with(".manifest.json") {
+            log(LogLevel::Info, &format!("Cleaning: {}", file_path));
+        } else {
+            log(LogLevel::Log, &format!("Cleaning: {}", file_path));
+        }
+        report.removed.push(file_path.to_string());
     }
 }
 
 /// Builds all targets
+/// Returns the artifacts of the targets and the compiler warnings.
 /// # Arguments
 /// * `build_config` - The local build configuration
 /// * `targets` - A vector of targets to build
 /// * `os_config` - The local os configuration
 /// * `gen_cc` - Whether to generate a compile_commands.json file
-/// * `gen_vsc` - Whether to generate a .vscode/c_cpp_properties.json file
 pub fn build(
     build_config: &BuildConfig,
     targets: &Vec<TargetConfig>,
     os_config: &OSConfig,
     gen_cc: bool,
-    gen_vsc: bool,
-) {
-    if !Path::new(BUILD_DIR).exists() {
-        fs::create_dir(BUILD_DIR).unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not create ruxgo_bld directory: {}", why),
-            );
-            std::process::exit(1);
-        });
+) -> Result<BuildReport> {
+    let paths = &build_config.paths;
+    let mut report = BuildReport::default();
+    if !Path::new(&paths.profile_dir()).exists() {
+        fs::create_dir_all(paths.profile_dir()).map_err(|why| {
+            Error::io(
+                format!("Could not create {} directory", paths.profile_dir()),
+                why,
+            )
+        })?;
     }
+    remove_legacy_outputs(paths, os_config, targets);
+    let toolchain_fingerprint = build_config.toolchain.fingerprint();
+    sources::fetch_sources(build_config)?;
+    // Written before compiling, so that editors also work on a tree that does not build
     if gen_cc {
-        let mut cc_file = fs::OpenOptions::new()
-            .append(true)
-            .open("compile_commands.json")
-            .unwrap_or_else(|why| {
-                log(LogLevel::Error, &format!("Could not open cc file: {}", why));
-                std::process::exit(1);
-            });
-        cc_file.write_all(b"[").unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not write to cc file: {}", why),
-            );
-            std::process::exit(1);
-        });
-    }
-
-    if gen_vsc {
-        let mut vsc_file = fs::OpenOptions::new()
-            .append(true)
-            .open(".vscode/c_cpp_properties.json")
-            .unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not open vsc file: {}", why),
-                );
-                std::process::exit(1);
-            });
-
-        let inc_dirs: Vec<String> = targets.iter().flat_map(|t| t.include_dir.clone()).collect();
-        let compiler_path: String = build_config.compiler.read().unwrap().clone();
-        let mut intellimode: String = String::new();
-        if compiler_path == "gcc" || compiler_path == "g++" {
-            intellimode = "gcc-x64".to_string();
-        } else if compiler_path == "clang" || compiler_path == "clang++" {
-            intellimode = "clang-x64".to_string();
-        } else {
-            log(
-                LogLevel::Error,
-                &format!("Unsupported compiler: {}", compiler_path),
-            );
-        }
-
-        #[cfg(target_os = "windows")]
-        let compiler_path = Command::new("sh")
-            .arg("-c")
-            .arg(&format!("where {}", &compiler_path))
-            .output()
-            .expect("failed to execute process")
-            .stdout;
-
-        #[cfg(target_os = "windows")]
-        //Pick the first compiler path
-        let compiler_path = String::from_utf8(compiler_path)
-            .unwrap()
-            .split("\n")
-            .collect::<Vec<&str>>()[0]
-            .to_string()
-            .replace("\r", "")
-            .replace("\\", "/");
-        #[cfg(target_os = "windows")]
-        let vsc_json = format!(
-            r#"{{
-    "configurations": [
-        {{
-            "name": "Win32",
-            "includePath": [
-                "{}"
-            ],
-            "defines": [
-                "_DEBUG",
-                "UNICODE",
-                "_UNICODE"
-            ],
-            "compilerPath": "{}",
-            "cStandard": "c11",
-            "cppStandard": "c++17",
-            "intelliSenseMode": "windows-{}"
-        }}
-    ],
-    "version": 4
-}}"#,
-            inc_dirs.join("\",\n\t\t\t\t\""),
-            compiler_path,
-            intellimode
-        );
-        #[cfg(target_os = "linux")]
-        let compiler_path = Command::new("sh")
-            .arg("-c")
-            .arg(&format!("which {}", &compiler_path))
-            .output()
-            .expect("failed to execute process")
-            .stdout;
-
-        #[cfg(target_os = "linux")]
-        let compiler_path = String::from_utf8(compiler_path).unwrap().replace('\n', "");
-
-        #[cfg(target_os = "linux")]
-        let vsc_json = format!(
-            r#"{{
-    "configurations": [
-        {{
-            "name": "Linux",
-            "includePath": [
-                "{}"
-            ],
-            "defines": [
-                "_DEBUG",
-                "UNICODE",
-                "_UNICODE"
-            ],
-            "compilerPath": "{}",
-            "cStandard": "c11",
-            "cppStandard": "c++17",
-            "intelliSenseMode": "linux-{}"
-        }}
-    ],
-    "version": 4
-}}"#,
-            inc_dirs.join("\",\n\t\t\t\t\""),
-            compiler_path,
-            intellimode
-        );
-
-        //Write to file
-        vsc_file
-            .write_all(vsc_json.as_bytes())
-            .unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not write to vsc file: {}", why),
-                );
-                std::process::exit(1);
-            });
+        compdb::write(build_config, os_config, targets)?;
     }
 
     let mut config_changed = false;
 
     // Checks and constructs os and ulib based on the os_config changes.
-    if os_config != &OSConfig::default() {
+    if !os_config.name.is_empty() {
+        // The toolchain is part of the hash so that the ulib is rebuilt when it changes
         let os_config_str = serde_json::to_string(os_config).unwrap_or_else(|_| "".to_string());
-        let current_hash = Hasher::hash_string(&os_config_str);
-        let old_hash = Hasher::read_hash_from_file(OSCONFIG_HASH_FILE);
+        let current_hash = Hasher::hash_string(&(os_config_str + &toolchain_fingerprint));
+        let old_hash = Hasher::read_hash_from_file(&paths.os_config_hash())?;
         if old_hash != current_hash || os_config.develop == "y" {
             log(
                 LogLevel::Debug,
@@ -329,19 +250,24 @@ pub fn build(
                 ),
             );
             config_changed = true;
-            let (rux_feats_final, lib_feats_final) = features::cfg_feat_addprefix(os_config);
+            let (rux_feats_final, lib_feats_final) = features::cfg_feat_addprefix(os_config)?;
             build_os(
+                build_config,
                 os_config,
                 &os_config.ulib,
                 &rux_feats_final,
                 &lib_feats_final,
-            );
+            )?;
             if os_config.ulib == "ruxlibc" {
-                build_ruxlibc(build_config, os_config, gen_cc);
+                report.merge(build_ruxlibc(
+                    build_config,
+                    os_config,
+                    &toolchain_fingerprint,
+                )?);
             } else if os_config.ulib == "ruxmusl" {
-                build_ruxmusl(build_config, os_config);
+                build_ruxmusl(build_config, os_config)?;
             }
-            Hasher::save_hash_to_file(OSCONFIG_HASH_FILE, &current_hash);
+            Hasher::save_hash_to_file(&paths.os_config_hash(), &current_hash)?;
         } else {
             log(LogLevel::Log, "OS config is up to date");
         }
@@ -349,50 +275,190 @@ pub fn build(
 
     // Constructs each target separately based on the os_config changes.
     for target in targets {
-        let mut tgt = Target::new(build_config, os_config, target, targets);
+        patches::sync_patches(paths, target.src_root(), &target.patches)?;
+        steps::run_steps(build_config, target)?;
+        let mut tgt = Target::new(build_config, os_config, target, targets)?;
+        tgt.check_toolchain(&toolchain_fingerprint);
 
         let needs_relink = config_changed && target.typ == "exe";
-        tgt.build(gen_cc, needs_relink);
+        report.merge(tgt.build(needs_relink)?);
     }
 
-    if gen_cc {
-        let mut cc_file = fs::OpenOptions::new()
-            .read(true)
-            .append(true)
-            .open("compile_commands.json")
-            .unwrap_or_else(|why| {
-                log(LogLevel::Error, &format!("Could not open cc file: {}", why));
-                std::process::exit(1);
-            });
-        cc_file.write_all(b"]").unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not write to cc file: {}", why),
-            );
-            std::process::exit(1);
-        });
-    }
     log(LogLevel::Log, "Build complete!");
+    Ok(report)
+}
+
+/// Removes the hash files and outputs of the layout used before profiles
+/// # Notes
+/// The old hash files record neither the toolchain nor the commands, so the outputs
+/// they describe may belong to any profile. Their targets are rebuilt instead.
+/// # Arguments
+/// * `paths` - The paths of the build directory
+/// * `os_config` - The local os configuration
+/// * `targets` - The targets to build
+fn remove_legacy_outputs(
+    paths: &paths::BuildPaths,
+    os_config: &OSConfig,
+    targets: &[TargetConfig],
+) {
+    let mut outputs: Vec<(&str, &str)> = targets
+        .iter()
+        .map(|target| (target.name.as_str(), target.typ.as_str()))
+        .collect();
+    if os_config.ulib == "ruxlibc" {
+        outputs.push(("libc", "static"));
+    }
+    for (name, typ) in outputs {
+        let hash_file = paths.legacy_hash_file(name);
+        if !Path::new(&hash_file).exists() {
+            continue;
+        }
+        log(
+            LogLevel::Info,
+            &format!("Removing outputs of '{}' built before profiles", name),
+        );
+        let mut files = vec![PathBuf::from(hash_file)];
+        let prefix = format!("{}-", name);
+        if let Ok(entries) = fs::read_dir(paths.legacy_obj_dir()) {
+            files.extend(entries.flatten().map(|entry| entry.path()).filter(|path| {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                file_name.starts_with(&prefix) && file_name.ends_with(".o")
+            }));
+        }
+        let (bin_path, elf_path) = paths.target_bin(name, typ);
+        for path in [bin_path, elf_path] {
+            if let Some(file_name) = Path::new(&path).file_name() {
+                files.push(Path::new(&paths.legacy_bin_dir()).join(file_name));
+            }
+        }
+        for file in files.iter().filter(|file| file.exists()) {
+            if let Err(why) = fs::remove_file(file) {
+                log(
+                    LogLevel::Warn,
+                    &format!("Failed to remove '{}': {}", file.display(), why),
+                );
+            }
+        }
+    }
+    // Fails while other targets still have outputs in them
+    let _ = fs::remove_dir(paths.legacy_obj_dir());
+    let _ = fs::remove_dir(paths.legacy_bin_dir());
+}
+
+/// Returns the status of the patches of all sources and targets
+/// # Notes
+/// Directories without configured or applied patches are left out.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `targets` - A vector of targets
+pub fn patch_status(
+    build_config: &BuildConfig,
+    targets: &[TargetConfig],
+) -> Result<Vec<PatchReport>> {
+    let mut reports = Vec::new();
+    for (dir, dir_patches) in patched_dirs(build_config, targets) {
+        let report = patches::status(&build_config.paths, dir, dir_patches)?;
+        if !report.patches.is_empty() {
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
+
+/// Reverts the patches of all sources and targets
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `targets` - A vector of targets
+pub fn revert_patches(build_config: &BuildConfig, targets: &[TargetConfig]) -> Result<()> {
+    for (dir, _) in patched_dirs(build_config, targets) {
+        patches::revert_patches(&build_config.paths, dir)?;
+    }
+    Ok(())
+}
+
+/// Returns the directories patched by the sources and targets, with their patches
+fn patched_dirs<'a>(
+    build_config: &'a BuildConfig,
+    targets: &'a [TargetConfig],
+) -> impl Iterator<Item = (&'a str, &'a [String])> {
+    let sources = build_config
+        .sources
+        .iter()
+        .map(|source| (source.dest.as_str(), source.patches.as_slice()));
+    let targets = targets
+        .iter()
+        .map(|target| (target.src_root(), target.patches.as_slice()));
+    sources.chain(targets)
 }
 
 /// Builds the specified os
 /// # Arguments
+/// * `build_config` - The local build configuration
 /// * `os_config` - The os configuration
 /// * `ulib` - The user library, `ruxlibc` or `ruxmusl`
 /// * `rux_feats` - Features to be enabled for Ruxos modules (crate `ruxfeat`)
 /// * `lib_feats` - Features to be enabled for the user library (crate `ruxlibc`, `ruxmusl`)
-fn build_os(os_config: &OSConfig, ulib: &str, rux_feats: &[String], lib_feats: &[String]) {
-    let current_dir = std::env::current_dir().unwrap();
-    let target_dir_path = current_dir.join(TARGET_DIR);
-    let target_dir = format!("--target-dir {}", target_dir_path.to_str().unwrap());
-
-    // Checks if the ruxos directory exists and change to it if it does
-    let ruxos_dir = Path::new("../ruxos");
+fn build_os(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    ulib: &str,
+    rux_feats: &[String],
+    lib_feats: &[String],
+) -> Result<()> {
+    let current_dir =
+        std::env::current_dir().map_err(|why| Error::io("Failed to get current directory", why))?;
+    let target_dir_path = current_dir.join(build_config.paths.target_dir());
+
+    let cmd = os_build_command(
+        os_config,
+        ulib,
+        &target_dir_path.to_string_lossy(),
+        rux_feats,
+        lib_feats,
+    );
+    log(LogLevel::Info, &format!("Command: {}", cmd));
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    // Runs in the ruxos directory if it exists
+    let ruxos_dir = Path::new(RUXOS_DIR);
     if ruxos_dir.exists() {
-        std::env::set_current_dir(ruxos_dir).unwrap();
+        command.current_dir(ruxos_dir);
     }
+    let output = command
+        .output()
+        .map_err(|why| Error::io("Failed to execute command", why))?;
+    if !output.status.success() {
+        return Err(Error::OsBuild(format!(
+            "Command execution failed: {}",
+            output.status
+        )));
+    }
+    Ok(())
+}
 
+/// Returns the cargo command building the specified os
+/// # Notes
+/// The command is run in the ruxos directory if it exists, see `RUXOS_DIR`.
+/// # Arguments
+/// * `os_config` - The os configuration
+/// * `ulib` - The user library, `ruxlibc` or `ruxmusl`
+/// * `target_dir` - The cargo target directory, as seen from where the command runs
+/// * `rux_feats` - Features to be enabled for Ruxos modules (crate `ruxfeat`)
+/// * `lib_feats` - Features to be enabled for the user library (crate `ruxlibc`, `ruxmusl`)
+pub fn os_build_command(
+    os_config: &OSConfig,
+    ulib: &str,
+    target_dir: &str,
+    rux_feats: &[String],
+    lib_feats: &[String],
+) -> String {
     let target = format!("--target {}", os_config.platform.target);
+    let target_dir = format!("--target-dir {}", target_dir);
     let mut mode = String::new();
     if !os_config.platform.mode.is_empty() {
         mode = format!("--{}", os_config.platform.mode);
@@ -404,187 +470,170 @@ fn build_os(os_config: &OSConfig, ulib: &str, rux_feats: &[String], lib_feats: &
         _ => "",
     };
     let features = [rux_feats, lib_feats].concat().join(" ");
-
-    // cmd
-    let cmd = format!(
+    format!(
         "cargo build {} {} {} {} {} --features \"{}\"",
         target, target_dir, mode, os_ulib, verbose, features
-    );
-    log(LogLevel::Info, &format!("Command: {}", cmd));
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(cmd)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .output()
-        .expect("Failed to execute command");
-    if !output.status.success() {
-        log(
-            LogLevel::Error,
-            &format!("Command execution failed: {:?}", output.stderr),
-        );
-        std::process::exit(1);
-    }
-
-    // Changes the current directory back to the original directory
-    std::env::set_current_dir(current_dir).unwrap();
+    )
 }
 
 /// Builds the ruxlibc
 /// # Arguments
 /// * `os_config` - The os configuration
 /// * `build_config` - The local build configuration
-/// * `gen_cc` - Whether to generate a compile_commands.json file
-fn build_ruxlibc(build_config: &BuildConfig, os_config: &OSConfig, gen_cc: bool) {
-    if !Path::new(BIN_DIR).exists() {
-        fs::create_dir_all(BIN_DIR).unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Couldn't create build dir: {}", why),
-            );
-            std::process::exit(1);
-        })
+/// * `toolchain_fingerprint` - The fingerprint of the current toolchain
+fn build_ruxlibc(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    toolchain_fingerprint: &str,
+) -> Result<BuildReport> {
+    let bin_dir = build_config.paths.bin_dir();
+    if !Path::new(&bin_dir).exists() {
+        fs::create_dir_all(&bin_dir).map_err(|why| Error::io("Couldn't create build dir", why))?;
     }
-    let ulib_tgt = TargetConfig {
+    let ulib_tgt = ruxlibc_target(build_config);
+    let ulib_targets = Vec::new();
+    let mut tgt = Target::new(build_config, os_config, &ulib_tgt, &ulib_targets)?;
+    tgt.check_toolchain(toolchain_fingerprint);
+    tgt.build(false)
+}
+
+/// Returns the target compiling the C sources of ruxlibc into a static library
+/// # Arguments
+/// * `build_config` - The local build configuration
+pub fn ruxlibc_target(build_config: &BuildConfig) -> TargetConfig {
+    TargetConfig {
         name: "libc".to_string(),
-        src: RUXLIBC_SRC.to_string(),
+        member: String::new(),
+        src: vec![RUXLIBC_SRC.to_string()],
+        files: Vec::new(),
         src_only: Vec::new(),
         src_exclude: Vec::new(),
+        src_match: "glob".to_string(),
         include_dir: Vec::new(), // this is empty to avoid repetition at src build
         typ: "static".to_string(),
         cflags: String::from(""),
-        archive: format!("{}-linux-musl-ar", os_config.platform.arch),
+        archive: build_config.toolchain.ar.clone(),
         linker: String::from(""),
         ldflags: String::from("rcs"),
         deps: Vec::new(),
-    };
-    let ulib_targets = Vec::new();
-    let mut tgt = Target::new(build_config, os_config, &ulib_tgt, &ulib_targets);
-    tgt.build(gen_cc, false);
+        steps: Vec::new(),
+        patches: Vec::new(),
+    }
 }
 
 /// Builds the ruxmusl
 /// # Arguments
 /// * `os_config` - The os configuration
 /// * `build_config` - The local build configuration
-fn build_ruxmusl(build_config: &BuildConfig, os_config: &OSConfig) {
-    if !Path::new(RUXMUSL_DIR).exists() {
+fn build_ruxmusl(build_config: &BuildConfig, os_config: &OSConfig) -> Result<()> {
+    let ruxmusl_dir = build_config.paths.ruxmusl_dir();
+    if !Path::new(&ruxmusl_dir).exists() {
         // download ruxmusl
-        if !Path::new(&*ULIB_RUXMUSL_SRC).exists() {
-            log(LogLevel::Info, "Downloading musl-1.2.3 source code");
-            Command::new("wget")
-                .args([
-                    "https://musl.libc.org/releases/musl-1.2.3.tar.gz",
-                    "-P",
-                    ULIB_RUXMUSL.as_str(),
-                ])
-                .spawn()
-                .expect("Failed to execute command")
-                .wait()
-                .expect("Failed to wait for command");
-            Command::new("tar")
-                .args([
-                    "-zxf",
-                    &format!("{}/musl-1.2.3.tar.gz", *ULIB_RUXMUSL),
-                    "-C",
-                    ULIB_RUXMUSL.as_str(),
-                ])
-                .spawn()
-                .expect("Failed to execute command")
-                .wait()
-                .expect("Failed to wait for command");
-            Command::new("rm")
-                .args(["-f", &format!("{}/musl-1.2.3.tar.gz", *ULIB_RUXMUSL)])
-                .spawn()
-                .expect("Failed to execute command")
-                .wait()
-                .expect("Failed to wait for command");
-        }
+        sources::fetch_source(&musl_source(), build_config.offline)?;
 
         // create ruxgo_bld/ruxmusl
-        fs::create_dir_all(RUXMUSL_DIR).unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Couldn't create build dir: {}", why),
-            );
-            std::process::exit(1);
-        });
+        fs::create_dir_all(&ruxmusl_dir)
+            .map_err(|why| Error::io("Couldn't create build dir", why))?;
 
         // config ruxmusl to generate makefile
-        let current_dir = std::env::current_dir().expect("Failed to get current directory");
+        let current_dir = std::env::current_dir()
+            .map_err(|why| Error::io("Failed to get current directory", why))?;
         let ruxmusl_abs_path = current_dir.join(ULIB_RUXMUSL_SRC.as_str());
-        let ruxmusl_abs_path_str = ruxmusl_abs_path
-            .to_str()
-            .expect("Failed to convert path to string");
-
-        let compiler = build_config.compiler.read().unwrap();
-        let mut cmd = format!(
-            "{}/configure --prefix=./install --exec-prefix=./ --syslibdir=./install/lib --disable-warnings",
-            ruxmusl_abs_path_str
-        );
-        if build_config.app.is_empty() {
-            cmd += " --disable-shared";
-        }
-        cmd += &format!(" ARCH={} CC={}", os_config.platform.arch, compiler);
+
+        let cmd =
+            ruxmusl_configure_command(build_config, os_config, &ruxmusl_abs_path.to_string_lossy());
 
         log(LogLevel::Info, &format!("Command: {}", cmd));
         let configure_output = Command::new("sh")
             .arg("-c")
             .arg(cmd)
-            .current_dir(RUXMUSL_DIR)
+            .current_dir(&ruxmusl_dir)
             .stderr(Stdio::inherit())
             .output()
-            .expect("Failed to execute configure command");
+            .map_err(|why| Error::io("Failed to execute configure command", why))?;
         if !configure_output.status.success() {
-            log(
-                LogLevel::Error,
-                &format!(
-                    "configure command execution failed: {:?}",
-                    configure_output.stderr
-                ),
-            );
-            std::process::exit(1);
+            return Err(Error::OsBuild(format!(
+                "configure command execution failed: {}",
+                configure_output.status
+            )));
         }
 
         // compile and install ruxmusl
         log(LogLevel::Log, "Compiling and installing Musl...");
         let make_output = Command::new("make")
             .args(["-j"])
-            .current_dir(RUXMUSL_DIR)
+            .current_dir(&ruxmusl_dir)
             .output()
-            .expect("Failed to run make command");
+            .map_err(|why| Error::io("Failed to run make command", why))?;
         if !make_output.status.success() {
-            log(
-                LogLevel::Error,
-                &format!(
-                    "\"make -j\" command execution failed: {:?}",
-                    make_output.status.code()
-                ),
-            );
-            std::process::exit(1);
+            return Err(Error::OsBuild(format!(
+                "\"make -j\" command execution failed: {:?}",
+                make_output.status.code()
+            )));
         }
         let make_install_output = Command::new("make")
             .args(["install"])
-            .current_dir(RUXMUSL_DIR)
+            .current_dir(&ruxmusl_dir)
             .stderr(Stdio::inherit())
             .output()
-            .expect("Failed to run make install command");
+            .map_err(|why| Error::io("Failed to run make install command", why))?;
         if !make_install_output.status.success() {
-            log(
-                LogLevel::Error,
-                &format!(
-                    "\"make install\" command execution failed: {:?}",
-                    make_install_output.status.code()
-                ),
-            );
-            std::process::exit(1);
+            return Err(Error::OsBuild(format!(
+                "\"make install\" command execution failed: {:?}",
+                make_install_output.status.code()
+            )));
         }
     }
+    Ok(())
+}
+
+/// Returns the source of the musl libc built into ruxmusl
+pub fn musl_source() -> SourceConfig {
+    SourceConfig {
+        name: "musl-1.2.3".to_string(),
+        url: MUSL_URL.to_string(),
+        sha256: MUSL_SHA256.to_string(),
+        archive: "tar.gz".to_string(),
+        strip_components: 1,
+        dest: ULIB_RUXMUSL_SRC.to_string(),
+        patches: Vec::new(),
+    }
+}
+
+/// Returns the command configuring musl, run in `BuildPaths::ruxmusl_dir`
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The os configuration
+/// * `src_dir` - The musl source directory, as seen from the ruxmusl directory
+pub fn ruxmusl_configure_command(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    src_dir: &str,
+) -> String {
+    let toolchain = &build_config.toolchain;
+    let compiler = format!(
+        "{}{}",
+        toolchain.cc,
+        toolchain.compiler_flags(&toolchain.cc)
+    );
+    let mut cmd = format!(
+        "{}/configure --prefix=./install --exec-prefix=./ --syslibdir=./install/lib --disable-warnings",
+        src_dir
+    );
+    if build_config.app.is_empty() {
+        cmd += " --disable-shared";
+    }
+    cmd += &format!(
+        " ARCH={} CC=\"{}\" AR={}",
+        os_config.platform.arch, compiler, toolchain.ar
+    );
+    cmd
 }
 
 /// Runs the exe target
+/// Returns the command that was run and its exit code.
 /// # Arguments
+/// * `bin_args` - Arguments passed to the executable
 /// * `os_config` - The os configuration
 /// * `build_config` - The local build configuration
 /// * `exe_target` - The exe target to run
@@ -595,18 +644,18 @@ pub fn run(
     os_config: &OSConfig,
     exe_target: &TargetConfig,
     targets: &Vec<TargetConfig>,
-) {
-    let trgt = Target::new(build_config, os_config, exe_target, targets);
+) -> Result<RunReport> {
+    sources::fetch_sources(build_config)?;
+    let trgt = Target::new(build_config, os_config, exe_target, targets)?;
     if !Path::new(&trgt.bin_path).exists() {
-        log(
-            LogLevel::Error,
-            &format!("Could not find binary: {}", &trgt.bin_path),
-        );
-        std::process::exit(1);
+        return Err(Error::Run(format!(
+            "Could not find binary: {}",
+            &trgt.bin_path
+        )));
     }
     if os_config.platform.qemu != QemuConfig::default() {
         let (qemu_args, qemu_args_debug) =
-            QemuConfig::config_qemu(&os_config.platform.qemu, &os_config.platform, &trgt);
+            QemuConfig::config_qemu(&os_config.platform.qemu, &os_config.platform, &trgt)?;
         // enable virtual disk image if need
         if os_config.platform.qemu.blk == "y" {
             let path = Path::new(&os_config.platform.qemu.disk_img);
@@ -619,21 +668,22 @@ pub fn run(
                     ),
                 );
             } else {
-                make_disk_image_fat32(&os_config.platform.qemu.disk_img);
+                make_disk_image_fat32(&os_config.platform.qemu.disk_img)?;
             }
         }
         // create loaded app file systems if needed
         if !build_config.app.is_empty() && &os_config.platform.qemu.v9p == "y" {
-            create_app_fs(build_config, os_config);
+            create_app_fs(build_config, os_config)?;
         }
         // enable qemu gdb guest if needed
         if &os_config.platform.qemu.debug == "y" {
-            run_qemu_debug(qemu_args_debug, bin_args);
+            run_qemu_debug(qemu_args_debug, bin_args)
         } else if &os_config.platform.qemu.debug == "n" {
-            run_qemu(qemu_args, bin_args);
+            run_qemu(qemu_args, bin_args)
         } else {
-            log(LogLevel::Error, "Debug field must be one of 'y' or 'n'");
-            std::process::exit(1);
+            Err(Error::Config(
+                "Debug field must be one of 'y' or 'n'".to_string(),
+            ))
         }
     } else {
         log(LogLevel::Log, &format!("Running: {}", &trgt.bin_path));
@@ -647,21 +697,25 @@ pub fn run(
         cmd.stdin(Stdio::inherit())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit());
-        let output = cmd.output();
-        if output.is_ok() {
-            log(LogLevel::Info, &format!("  Success: {}", &trgt.bin_path));
-        } else {
-            log(
-                LogLevel::Error,
-                &format!("  Error: {}", output.err().unwrap()),
-            );
-            std::process::exit(1);
+        let output = cmd
+            .output()
+            .map_err(|why| Error::Run(format!("Failed to run '{}': {}", &trgt.bin_path, why)))?;
+        log(LogLevel::Info, &format!("  Success: {}", &trgt.bin_path));
+        let mut command = trgt.bin_path.clone();
+        for arg in cmd.get_args() {
+            command.push(' ');
+            command.push_str(&arg.to_string_lossy());
         }
+        Ok(RunReport {
+            command,
+            exit_code: output.status.code(),
+        })
     }
 }
 
 /// Makes the disk_img of fat32
-fn make_disk_image_fat32(file_name: &str) {
+#[allow(clippy::needless_borrows_for_generic_args)]
+fn make_disk_image_fat32(file_name: &str) -> Result<()> {
     log(
         LogLevel::Log,
         &format!("Creating FAT32 disk image \"{}\" ...", file_name),
@@ -672,37 +726,30 @@ fn make_disk_image_fat32(file_name: &str) {
         .arg("bs=1M")
         .arg("count=64")
         .output()
-        .expect("failed to execute dd command");
+        .map_err(|why| Error::io("Failed to execute dd command", why))?;
     if !output.status.success() {
-        log(
-            LogLevel::Error,
-            &format!(
-                "dd command failed with exit code {:?}",
-                output.status.code()
-            ),
-        );
-        std::process::exit(1);
+        return Err(Error::Run(format!(
+            "dd command failed with exit code {:?}",
+            output.status.code()
+        )));
     }
     let mkfs_output = Command::new("mkfs.fat")
         .arg("-F")
         .arg("32")
         .arg(file_name)
         .output()
-        .expect("failed to execute mkfs.fat command");
+        .map_err(|why| Error::io("Failed to execute mkfs.fat command", why))?;
     if !mkfs_output.status.success() {
-        log(
-            LogLevel::Error,
-            &format!(
-                "mkfs.fat command failed with exit code {:?}",
-                mkfs_output.status.code()
-            ),
-        );
-        std::process::exit(1);
+        return Err(Error::Run(format!(
+            "mkfs.fat command failed with exit code {:?}",
+            mkfs_output.status.code()
+        )));
     }
+    Ok(())
 }
 
 /// Runs the bin by qemu
-fn run_qemu(qemu_args: Vec<String>, bin_args: Option<Vec<&str>>) {
+fn run_qemu(qemu_args: Vec<String>, bin_args: Option<Vec<&str>>) -> Result<RunReport> {
     log(LogLevel::Log, "Running on qemu...");
     let mut cmd = String::new();
     for qemu_arg in qemu_args {
@@ -718,23 +765,26 @@ fn run_qemu(qemu_args: Vec<String>, bin_args: Option<Vec<&str>>) {
     log(LogLevel::Info, &format!("Command: {}", cmd));
     let output = Command::new("sh")
         .arg("-c")
-        .arg(cmd)
+        .arg(&cmd)
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .output()
-        .expect("Failed to start qemu");
+        .map_err(|why| Error::io("Failed to start qemu", why))?;
     if !output.status.success() {
-        log(
-            LogLevel::Error,
-            &format!("Command execution failed: {:?}", output.stderr),
-        );
-        std::process::exit(1);
+        return Err(Error::Run(format!(
+            "Command execution failed: {}",
+            output.status
+        )));
     }
+    Ok(RunReport {
+        command: cmd.trim_end().to_string(),
+        exit_code: output.status.code(),
+    })
 }
 
 /// Runs the bin by qemu and enable gdb guest
-fn run_qemu_debug(qemu_debug_args: Vec<String>, bin_args: Option<Vec<&str>>) {
+fn run_qemu_debug(qemu_debug_args: Vec<String>, bin_args: Option<Vec<&str>>) -> Result<RunReport> {
     log(LogLevel::Log, "Debugging on qemu...");
     let mut cmd = String::new();
     for qemu_debug_arg in qemu_debug_args {
@@ -754,40 +804,40 @@ fn run_qemu_debug(qemu_debug_args: Vec<String>, bin_args: Option<Vec<&str>>) {
     );
     let output = Command::new("sh")
         .arg("-c")
-        .arg(cmd)
+        .arg(&cmd)
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .output()
-        .expect("Failed to start qemu");
+        .map_err(|why| Error::io("Failed to start qemu", why))?;
     if !output.status.success() {
-        log(
-            LogLevel::Error,
-            &format!("Command execution failed: {:?}", output.stderr),
-        );
-        std::process::exit(1);
+        return Err(Error::Run(format!(
+            "Command execution failed: {}",
+            output.status
+        )));
     }
+    Ok(RunReport {
+        command: cmd.trim_end().to_string(),
+        exit_code: output.status.code(),
+    })
 }
 
 /// Initialises a new project in the current directory
-pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfig) {
+pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfig) -> Result<()> {
     log(LogLevel::Log, "Initializing project...");
 
     if Path::new(project_name).exists() {
-        log(LogLevel::Error, &format!("{} already exists", project_name));
-        log(LogLevel::Error, "Cannot initialise project");
-        std::process::exit(1);
+        return Err(Error::Config(format!(
+            "{} already exists\nCannot initialise project",
+            project_name
+        )));
     }
 
     //Initialise git repo in project directory
     let mut cmd = Command::new("git");
     cmd.arg("init").arg(project_name);
-    let output = cmd.output();
-    if output.is_err() {
-        log(LogLevel::Error, "Could not initialise git repo");
-        log(LogLevel::Error, &format!("{}", output.err().unwrap()));
-        std::process::exit(1);
-    }
+    cmd.output()
+        .map_err(|why| Error::io("Could not initialise git repo", why))?;
 
     //Initialise config_linux.toml
     #[cfg(target_os = "windows")]
@@ -795,37 +845,30 @@ pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfi
     #[cfg(target_os = "linux")]
     let config_file = project_name.to_owned() + "/config_linux.toml";
     if Path::new(&config_file).exists() {
-        log(LogLevel::Error, &format!("{} already exists", config_file));
-        log(LogLevel::Error, "Cannot initialise project");
-        std::process::exit(1);
+        return Err(Error::Config(format!(
+            "{} already exists\nCannot initialise project",
+            config_file
+        )));
     }
     let mut config_file = fs::OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
         .open(config_file)
-        .unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not create config file: {}", why),
-            );
-            std::process::exit(1);
-        });
+        .map_err(|why| Error::io("Could not create config file", why))?;
 
     let c_compiler = match config.get_default_compiler().as_str() {
         "gcc" => "gcc",
         "clang" => "clang",
         _ => {
-            log(LogLevel::Error, "Invalid default compiler");
-            std::process::exit(1);
+            return Err(Error::Config("Invalid default compiler".to_string()));
         }
     };
     let cpp_compiler = match config.get_default_compiler().as_str() {
         "gcc" => "g++",
         "clang" => "clang++",
         _ => {
-            log(LogLevel::Error, "Invalid default compiler");
-            std::process::exit(1);
+            return Err(Error::Config("Invalid default compiler".to_string()));
         }
     };
     let sample_cpp_config = format!("[build]\ncompiler = \"{}\"\n\n[[targets]]\nname = \"main\"\nsrc = \"./src/\"\ninclude_dir = \"./src/include/\"\ntype = \"exe\"\ncflags = \"-g -Wall -Wextra\"\nldflags = \"\"\ndeps = []\n", cpp_compiler);
@@ -839,42 +882,28 @@ pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfi
             "c" => sample_c_config,
             "cpp" => sample_cpp_config,
             _ => {
-                log(LogLevel::Error, "Invalid default language");
-                std::process::exit(1);
+                return Err(Error::Config("Invalid default language".to_string()));
             }
         },
     };
     config_file
         .write_all(sample_config.as_bytes())
-        .unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not write to config file: {}", why),
-            );
-            std::process::exit(1);
-        });
+        .map_err(|why| Error::io("Could not write to config file", why))?;
 
     //Create src and src/include directories
     let src_dir = project_name.to_owned() + "/src";
     let include_dir = project_name.to_owned() + "/src/include";
     if !Path::new(&src_dir).exists() {
-        fs::create_dir(&src_dir).unwrap_or_else(|why| {
-            log(LogLevel::Warn, &format!("Project name {}", project_name));
-            log(
-                LogLevel::Error,
-                &format!("Could not create src directory: {}", why),
-            );
-            std::process::exit(1);
-        });
+        fs::create_dir(&src_dir).map_err(|why| {
+            Error::io(
+                format!("Could not create src directory of project {}", project_name),
+                why,
+            )
+        })?;
     }
     if !Path::new(&include_dir).exists() {
-        fs::create_dir(&include_dir).unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not create src/include directory: {}", why),
-            );
-            std::process::exit(1);
-        });
+        fs::create_dir(&include_dir)
+            .map_err(|why| Error::io("Could not create src/include directory", why))?;
     }
 
     //Create main.c or main.cpp
@@ -886,8 +915,7 @@ pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfi
             "c" => main_path = src_dir.to_owned() + "/main.c",
             "cpp" => main_path = src_dir.to_owned() + "/main.cpp",
             _ => {
-                log(LogLevel::Error, "Invalid default language");
-                std::process::exit(1);
+                return Err(Error::Config("Invalid default language".to_string()));
             }
         },
     }
@@ -897,55 +925,28 @@ pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfi
             .create(true)
             .truncate(true)
             .open(&main_path)
-            .unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not create main.cpp: {}", why),
-                );
-                std::process::exit(1);
-            });
+            .map_err(|why| Error::io("Could not create main.cpp", why))?;
 
         let c_sample_program =
             b"#include <stdio.h>\n\nint main() {\n\tprintf(\"Here is a Ruxgo example!\\n\");\n\treturn 0;\n}";
         let cpp_sample_program =
             b"#include <iostream>\n\nint main() {\n\tstd::cout << \"Here is a Ruxgo example!\" << std::endl;\n\treturn 0;\n}";
         match is_c {
-            Some(true) => main_file.write_all(c_sample_program).unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not write to main.c: {}", why),
-                );
-                std::process::exit(1);
-            }),
+            Some(true) => main_file
+                .write_all(c_sample_program)
+                .map_err(|why| Error::io("Could not write to main.c", why))?,
             Some(false) => main_file
                 .write_all(cpp_sample_program)
-                .unwrap_or_else(|why| {
-                    log(
-                        LogLevel::Error,
-                        &format!("Could not write to main.cpp: {}", why),
-                    );
-                    std::process::exit(1);
-                }),
+                .map_err(|why| Error::io("Could not write to main.cpp", why))?,
             None => match config.get_default_language().as_str() {
-                "c" => main_file.write_all(c_sample_program).unwrap_or_else(|why| {
-                    log(
-                        LogLevel::Error,
-                        &format!("Could not write to main.c: {}", why),
-                    );
-                    std::process::exit(1);
-                }),
+                "c" => main_file
+                    .write_all(c_sample_program)
+                    .map_err(|why| Error::io("Could not write to main.c", why))?,
                 "cpp" => main_file
                     .write_all(cpp_sample_program)
-                    .unwrap_or_else(|why| {
-                        log(
-                            LogLevel::Error,
-                            &format!("Could not write to main.cpp: {}", why),
-                        );
-                        std::process::exit(1);
-                    }),
+                    .map_err(|why| Error::io("Could not write to main.cpp", why))?,
                 _ => {
-                    log(LogLevel::Error, "Invalid default language");
-                    std::process::exit(1);
+                    return Err(Error::Config("Invalid default language".to_string()));
                 }
             },
         }
@@ -959,22 +960,10 @@ pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfi
             .create(true)
             .truncate(true)
             .open(&gitignore_path)
-            .unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not create .gitignore: {}", why),
-                );
-                std::process::exit(1);
-            });
+            .map_err(|why| Error::io("Could not create .gitignore", why))?;
         gitignore_file
             .write_all(b"ruxgo_bld\ncompile_commands.json\n.cache\n")
-            .unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not write to .gitignore: {}", why),
-                );
-                std::process::exit(1);
-            });
+            .map_err(|why| Error::io("Could not write to .gitignore", why))?;
     }
 
     //Create README.md
@@ -983,22 +972,10 @@ pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfi
         .create(true)
         .truncate(true)
         .open(project_name.to_owned() + "/README.md")
-        .unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not create README.md: {}", why),
-            );
-            std::process::exit(1);
-        });
+        .map_err(|why| Error::io("Could not create README.md", why))?;
     readme_file
         .write_all(format!("# {}", project_name).as_bytes())
-        .unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not write to README.md: {}", why),
-            );
-            std::process::exit(1);
-        });
+        .map_err(|why| Error::io("Could not write to README.md", why))?;
 
     //Create LICENSE
     let mut license_file = fs::OpenOptions::new()
@@ -1006,53 +983,85 @@ pub fn init_project(project_name: &str, is_c: Option<bool>, config: &GlobalConfi
         .create(true)
         .truncate(true)
         .open(project_name.to_owned() + "/LICENSE")
-        .unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not create LICENSE: {}", why),
-            );
-            std::process::exit(1);
-        });
+        .map_err(|why| Error::io("Could not create LICENSE", why))?;
 
     let license = config.get_license();
     if license.as_str() == "NONE" {
-        license_file.write_all(b"No license").unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not write to LICENSE: {}", why),
-            );
-            std::process::exit(1);
-        });
+        license_file
+            .write_all(b"No license")
+            .map_err(|why| Error::io("Could not write to LICENSE", why))?;
     } else {
         license_file
             .write_all(license.as_bytes())
-            .unwrap_or_else(|why| {
-                log(
-                    LogLevel::Error,
-                    &format!("Could not write to LICENSE: {}", why),
-                );
-                std::process::exit(1);
-            });
+            .map_err(|why| Error::io("Could not write to LICENSE", why))?;
     }
 
     log(
         LogLevel::Log,
         &format!("Project {} initialised", project_name),
     );
+    Ok(())
+}
+
+/// Locates the config file of the project
+/// Returns the project root, i.e. the directory holding the config, and the file name
+/// of the config, which is relative to the project root.
+/// # Arguments
+/// * `config` - The config file or directory given by `--config`
+/// * `variant` - The config variant given by `--variant`
+pub fn locate_config(config: Option<&Path>, variant: Option<&str>) -> Result<(PathBuf, String)> {
+    let config_path = paths::find_config(config, variant).map_err(Error::Config)?;
+    log(
+        LogLevel::Info,
+        &format!("Using config: {}", config_path.display()),
+    );
+    let project_dir = config_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf();
+    let config_file = config_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(paths::CONFIG_FILE)
+        .to_string();
+    Ok((project_dir, config_file))
 }
 
 /// Parses the config file of local project
-pub fn parse_config() -> (BuildConfig, OSConfig, Vec<TargetConfig>) {
-    #[cfg(target_os = "linux")]
-    let (build_config, os_config, mut targets) = parser::parse_config("./config_linux.toml", false);
-    #[cfg(target_os = "windows")]
-    let (build_config, os_config, targets) = utils::parse_config("./config_win32.toml", true);
+/// # Arguments
+/// * `config_file` - The config file, relative to the project root
+/// * `build_dir` - Overrides the build directory given by `[build] out_dir`
+pub fn parse_config(
+    config_file: &str,
+    build_dir: Option<&str>,
+) -> Result<(BuildConfig, OSConfig, Vec<TargetConfig>)> {
+    let (build_config, os_config, targets) = parser::parse_config(config_file, build_dir)?;
+    let targets = prepare_targets(&build_config, &os_config, targets)?;
+    Ok((build_config, os_config, targets))
+}
 
+/// Prepares parsed targets for building
+/// # Notes
+/// Adds the loader of the `app` to the targets, checks that there is exactly one
+/// executable outside a workspace and configures the environment of the OS build.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The local os configuration
+/// * `targets` - The parsed targets
+pub fn prepare_targets(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    mut targets: Vec<TargetConfig>,
+) -> Result<Vec<TargetConfig>> {
     if !build_config.app.is_empty() {
-        if let Some(app_filename) = Path::new(&build_config.app).file_name() {
-            let loader_program = Path::new("/bin").join(app_filename);
+        let app_filename = Path::new(&build_config.app)
+            .file_name()
+            .and_then(|name| name.to_str());
+        if let Some(app_filename) = app_filename {
+            let loader_program = format!("/bin/{}", app_filename);
             // Adds the loader's TargetConfig to targets
-            targets.push(build_loader(loader_program.to_str().unwrap()));
+            targets.push(build_loader(&loader_program, build_config, os_config)?);
         }
     }
 
@@ -1065,44 +1074,40 @@ pub fn parse_config() -> (BuildConfig, OSConfig, Vec<TargetConfig>) {
             exe_target = Some(target);
         }
     }
-    if num_exe != 1 || exe_target.is_none() {
-        log(
-            LogLevel::Error,
-            "Exactly one executable target must be specified",
-        );
-        std::process::exit(1);
+    // A workspace may contain an executable per member
+    if (num_exe != 1 || exe_target.is_none()) && build_config.workspace.is_empty() {
+        return Err(Error::Config(
+            "Exactly one executable target must be specified".to_string(),
+        ));
     }
 
     // Add environment config
-    env::config_env(&os_config);
+    env::config_env(os_config);
 
-    (build_config, os_config, targets)
+    Ok(targets)
 }
 
-pub fn pre_gen_cc() {
-    if !Path::new("./compile_commands.json").exists() {
-        fs::File::create(Path::new("./compile_commands.json")).unwrap();
-    } else {
-        fs::remove_file(Path::new("./compile_commands.json")).unwrap();
-        fs::File::create(Path::new("./compile_commands.json")).unwrap();
-    }
-}
-
-pub fn pre_gen_vsc() {
-    if !Path::new("./.vscode").exists() {
-        fs::create_dir(Path::new("./.vscode")).unwrap();
-    }
-
-    if !Path::new("./.vscode/c_cpp_properties.json").exists() {
-        fs::File::create(Path::new("./.vscode/c_cpp_properties.json")).unwrap();
-    } else {
-        fs::remove_file(Path::new("./.vscode/c_cpp_properties.json")).unwrap();
-        fs::File::create(Path::new("./.vscode/c_cpp_properties.json")).unwrap();
+/// Returns the single executable target, which is the one that is run
+/// # Arguments
+/// * `targets` - The targets of the project
+pub fn exe_target(targets: &[TargetConfig]) -> Result<&TargetConfig> {
+    let exe_targets: Vec<&TargetConfig> = targets.iter().filter(|x| x.typ == "exe").collect();
+    if exe_targets.len() != 1 {
+        let names: Vec<&str> = exe_targets.iter().map(|x| x.name.as_str()).collect();
+        return Err(Error::Config(format!(
+            "Exactly one executable target can be run, found: {}",
+            names.join(", ")
+        )));
     }
+    Ok(exe_targets[0])
 }
 
 // Creates the loader function
-pub fn build_loader(loader_program: &str) -> TargetConfig {
+pub fn build_loader(
+    loader_program: &str,
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+) -> Result<TargetConfig> {
     // Defines the C source code for the loader
     let loader_src = format!(
         r#"
@@ -1118,78 +1123,64 @@ int main(int argc, char **argv)
         loader_program
     );
 
-    if !Path::new(BUILD_DIR).exists() {
-        fs::create_dir(BUILD_DIR).unwrap_or_else(|why| {
-            log(
-                LogLevel::Error,
-                &format!("Could not create ruxgo_bld directory: {}", why),
-            );
-            std::process::exit(1);
-        });
+    let paths = &build_config.paths;
+    if !Path::new(&paths.root).exists() {
+        fs::create_dir_all(&paths.root)
+            .map_err(|why| Error::io(format!("Could not create {} directory", paths.root), why))?;
     }
     // Writes the loader to a temporary file
-    let loader_src_path = Path::new(BUILD_DIR).join("loader.c");
-    fs::write(loader_src_path, loader_src).expect("Failed to write to loader.c");
+    let loader_src_path = paths.loader_src();
+    fs::write(&loader_src_path, loader_src)
+        .map_err(|why| Error::io("Failed to write to loader.c", why))?;
 
     // Creates an instance of TargetConfig to compile the loader
-    TargetConfig {
+    Ok(TargetConfig {
         name: "loader".to_string(),
-        src: "ruxgo_bld/loader.c".to_string(),
+        member: String::new(),
+        src: Vec::new(),
+        files: vec![loader_src_path],
         src_only: Vec::new(),
         src_exclude: Vec::new(),
+        src_match: "glob".to_string(),
         include_dir: Vec::new(),
         typ: "exe".to_string(),
         cflags: "-g -Wall".to_string(),
         archive: String::new(),
-        linker: "rust-lld -flavor gnu".to_string(),
+        // The derived `ld` is the compiler, the loader is linked by rust-lld unless set
+        linker: build_config
+            .explicit_ld(os_config)
+            .unwrap_or("rust-lld -flavor gnu")
+            .to_string(),
         ldflags: String::new(),
         deps: Vec::new(),
-    }
+        steps: Vec::new(),
+        patches: Vec::new(),
+    })
 }
 
 // Creates the app file system and related content
-pub fn create_app_fs(build_config: &BuildConfig, os_config: &OSConfig) {
+pub fn create_app_fs(build_config: &BuildConfig, os_config: &OSConfig) -> Result<()> {
     // Copys the dynamic_lib if needed
     let lib_name = format!("ld-musl-{}.so.1", os_config.platform.arch.as_str());
-    let lib_src = Path::new(RUXMUSL_DIR).join("install/lib/libc.so");
+    let lib_src = Path::new(&build_config.paths.ruxmusl_dir()).join("install/lib/libc.so");
     let lib_dest = Path::new(&os_config.platform.qemu.v9p_path).join("lib");
-    if let Err(e) = fs::create_dir_all(&lib_dest) {
-        log(
-            LogLevel::Error,
-            &format!("Failed to create directories: {}", e),
-        );
-        std::process::exit(1);
-    }
+    fs::create_dir_all(&lib_dest).map_err(|e| Error::io("Failed to create directories", e))?;
     if lib_src.exists() {
-        if let Err(err) = fs::copy(&lib_src, lib_dest.join(lib_name)) {
-            log(LogLevel::Error, &format!("Failed to copy file: {}", err));
-            std::process::exit(1);
-        }
+        fs::copy(&lib_src, lib_dest.join(lib_name))
+            .map_err(|err| Error::io("Failed to copy file", err))?;
     } else {
-        log(
-            LogLevel::Error,
-            "The ruxmusl dynamic library does not exist",
-        );
-        std::process::exit(1);
+        return Err(Error::Run(
+            "The ruxmusl dynamic library does not exist".to_string(),
+        ));
     }
 
     // Copys the bin file
     let app_src = Path::new(&build_config.app);
     let app_dest = Path::new(&os_config.platform.qemu.v9p_path).join("bin");
-    if let Err(e) = fs::create_dir_all(&app_dest) {
-        log(
-            LogLevel::Error,
-            &format!("Failed to create bin directory: {}", e),
-        );
-        std::process::exit(1);
-    }
+    fs::create_dir_all(&app_dest).map_err(|e| Error::io("Failed to create bin directory", e))?;
     if let Some(app_filename) = app_src.file_name() {
-        if let Err(err) = fs::copy(app_src, app_dest.join(app_filename)) {
-            log(
-                LogLevel::Error,
-                &format!("Binary file does not exist or path is incorrect: {}", err),
-            );
-            std::process::exit(1);
-        }
+        fs::copy(app_src, app_dest.join(app_filename))
+            .map_err(|err| Error::io("Binary file does not exist or path is incorrect", err))?;
     }
+    Ok(())
 }
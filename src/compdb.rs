@@ -0,0 +1,116 @@
+//! Compilation Database Module
+
+use crate::builder::Target;
+use crate::commands;
+use crate::error::{Error, Result};
+use crate::hasher::Hasher;
+use crate::parser::{BuildConfig, OSConfig, TargetConfig};
+use crate::utils::log::{log, LogLevel};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::process::Command;
+
+/// Struct describing an entry of compile_commands.json
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompileCommand {
+    /// The working directory of the compilation
+    pub directory: String,
+    pub file: String,
+    /// The argv of the compiler, as the shell passes it
+    pub arguments: Vec<String>,
+    pub output: String,
+}
+
+/// Returns the entries of compile_commands.json, without building
+/// # Notes
+/// Every source of every target is listed, along with the C sources of ruxlibc if it is
+/// the user library. The arguments are those of the build, after the shell expanded the
+/// command substitutions of `cflags`.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The local os configuration
+/// * `targets` - The targets, including the loader, see `commands::prepare_targets`
+pub fn entries(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    targets: &Vec<TargetConfig>,
+) -> Result<Vec<CompileCommand>> {
+    let directory = std::env::current_dir()
+        .map_err(|why| Error::io("Could not get the current directory", why))?
+        .to_string_lossy()
+        .to_string();
+    let mut units = Vec::new();
+    if os_config.ulib == "ruxlibc" {
+        let ulib_tgt = commands::ruxlibc_target(build_config);
+        let ulib_targets = Vec::new();
+        let tgt = Target::new(build_config, os_config, &ulib_tgt, &ulib_targets)?;
+        units.extend(tgt.compile_units());
+    }
+    for target in targets {
+        let tgt = Target::new(build_config, os_config, target, targets)?;
+        units.extend(tgt.compile_units());
+    }
+    units
+        .into_par_iter()
+        .map(|unit| {
+            Ok(CompileCommand {
+                directory: directory.clone(),
+                arguments: split_command(&unit.command)?,
+                file: unit.file,
+                output: unit.object,
+            })
+        })
+        .collect()
+}
+
+/// Writes compile_commands.json into the build directory, without building
+/// Returns the path of the written file.
+/// # Notes
+/// The file is replaced atomically, so that editors never read a partial database.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The local os configuration
+/// * `targets` - The targets, including the loader, see `commands::prepare_targets`
+pub fn write(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    targets: &Vec<TargetConfig>,
+) -> Result<String> {
+    let entries = entries(build_config, os_config, targets)?;
+    let paths = &build_config.paths;
+    fs::create_dir_all(&paths.root)
+        .map_err(|why| Error::io(format!("Could not create {} directory", paths.root), why))?;
+    let cc_path = paths.compile_commands();
+    let contents = serde_json::to_string_pretty(&entries)
+        .map_err(|why| Error::io(format!("Could not serialize {}", cc_path), why.into()))?;
+    Hasher::write_atomic(&cc_path, contents.as_bytes())?;
+    log(
+        LogLevel::Log,
+        &format!("Generated {} ({} entries)", cc_path, entries.len()),
+    );
+    Ok(cc_path)
+}
+
+/// Splits a compile command into the argv the shell runs it with
+/// # Notes
+/// The shell does the splitting, so quoting and command substitutions behave exactly
+/// as when the command is run.
+pub fn split_command(command: &str) -> Result<Vec<String>> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("printf '%s\\0' {}", command))
+        .output()
+        .map_err(|why| Error::io("Failed to execute process", why))?;
+    if !output.status.success() {
+        return Err(Error::Compile(format!(
+            "Failed to expand the command: {}\n  Stderr: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split_terminator('\0')
+        .map(|arg| arg.to_string())
+        .collect())
+}
@@ -0,0 +1,61 @@
+//! Error Module
+
+use thiserror::Error as ThisError;
+
+/// Enum describing the errors returned by ruxgo
+/// # Notes
+/// The library never exits the process, it is up to the caller to print the error
+/// and choose an exit code.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The config file is missing, invalid or inconsistent
+    #[error("{0}")]
+    Config(String),
+    /// A tool of the toolchain is missing or unusable
+    #[error("{0}")]
+    Toolchain(String),
+    /// A source file failed to compile
+    #[error("{0}")]
+    Compile(String),
+    /// A target failed to link or archive
+    #[error("{0}")]
+    Link(String),
+    /// A file or directory operation failed
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Building the OS or the user library failed
+    #[error("{0}")]
+    OsBuild(String),
+    /// A package or source archive could not be fetched
+    #[error("{0}")]
+    Package(String),
+    /// A patch could not be applied or reverted
+    #[error("{0}")]
+    Patch(String),
+    /// A custom build step failed
+    #[error("{0}")]
+    Step(String),
+    /// Running the application failed
+    #[error("{0}")]
+    Run(String),
+}
+
+/// Result type of ruxgo
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Creates an IO error with a description of the failed operation
+    /// # Arguments
+    /// * `context` - The operation that failed, e.g. "Could not create directory 'x'"
+    /// * `source` - The underlying IO error
+    pub fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        Error::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Export Module
+
+use crate::builder::Target;
+use crate::commands;
+use crate::error::{Error, Result};
+use crate::parser::{BuildConfig, OSConfig, TargetConfig};
+use crate::sources;
+use crate::utils::features;
+use std::path::Path;
+
+/// Struct describing a shell command of an exported build
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildCommand {
+    pub description: String,
+    /// The command, run with `sh` from the project root
+    pub command: String,
+    /// The files the outputs are rebuilt from
+    pub inputs: Vec<String>,
+    /// The files that have to exist before the command runs, without triggering it
+    pub order_inputs: Vec<String>,
+    /// The files the command writes, every command needs at least one
+    pub outputs: Vec<String>,
+    /// Whether the command runs on every build, like steps without outputs
+    pub always: bool,
+}
+
+/// Struct describing the resolved build, independent of the exported format
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildPlan {
+    /// The commands in build order
+    pub commands: Vec<BuildCommand>,
+    /// The artifacts of the targets, built by default
+    pub defaults: Vec<String>,
+}
+
+/// Resolves the commands building the project
+/// # Notes
+/// The plan fetches the sources, builds the OS and the user library, runs the custom
+/// steps and compiles and links the targets with the same commands as ruxgo.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The local os configuration
+/// * `targets` - The targets, including the loader, see `commands::prepare_targets`
+/// * `config_file` - The config file, the OS is rebuilt when it changes
+pub fn plan(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    targets: &Vec<TargetConfig>,
+    config_file: &str,
+) -> Result<BuildPlan> {
+    let paths = &build_config.paths;
+    let mut plan = BuildPlan::default();
+    let mut resolved = Vec::new();
+    for target in targets {
+        resolved.push(Target::new(build_config, os_config, target, targets)?);
+    }
+
+    // The fetch of a source outputs the files of the targets it contains
+    let mut fetched = Vec::new();
+    for source in &build_config.sources {
+        let mut outputs = vec![sources::marker_path(source)];
+        for tgt in &resolved {
+            for unit in tgt.compile_units() {
+                let files = std::iter::once(&unit.file).chain(&unit.includes);
+                for file in files.filter(|file| Path::new(file).starts_with(&source.dest)) {
+                    if !outputs.contains(file) {
+                        outputs.push(file.clone());
+                    }
+                }
+            }
+        }
+        fetched.push(sources::marker_path(source));
+        plan.commands.push(BuildCommand {
+            description: format!("Fetching source {}", source.name),
+            command: sources::fetch_command(source),
+            inputs: source.patches.clone(),
+            outputs,
+            ..Default::default()
+        });
+    }
+
+    // The OS and the user library are linked into the executables
+    let mut os_libs = Vec::new();
+    if !os_config.name.is_empty() {
+        let (rux_feats, lib_feats) = features::cfg_feat_addprefix(os_config)?;
+        let target_dir = paths.target_dir();
+        let cmd = if Path::new(commands::RUXOS_DIR).exists() {
+            let target_dir = format!("\"$OLDPWD/{}\"", target_dir);
+            let cmd = commands::os_build_command(
+                os_config,
+                &os_config.ulib,
+                &target_dir,
+                &rux_feats,
+                &lib_feats,
+            );
+            format!("cd {} && {}", commands::RUXOS_DIR, cmd)
+        } else {
+            commands::os_build_command(
+                os_config,
+                &os_config.ulib,
+                &target_dir,
+                &rux_feats,
+                &lib_feats,
+            )
+        };
+        let mode = match os_config.platform.mode.as_str() {
+            "" => "debug",
+            mode => mode,
+        };
+        let os_lib = format!(
+            "{}/{}/{}/lib{}.a",
+            target_dir, os_config.platform.target, mode, os_config.ulib
+        );
+        plan.commands.push(BuildCommand {
+            description: format!("Compiling OS {}", os_config.name),
+            command: cmd,
+            inputs: vec![config_file.to_string()],
+            outputs: vec![os_lib.clone()],
+            always: os_config.develop == "y",
+            ..Default::default()
+        });
+        os_libs.push(os_lib);
+
+        if os_config.ulib == "ruxlibc" {
+            let ulib_tgt = commands::ruxlibc_target(build_config);
+            let ulib_targets = Vec::new();
+            let tgt = Target::new(build_config, os_config, &ulib_tgt, &ulib_targets)?;
+            target_commands(&mut plan, &tgt, &ulib_tgt, &[], &[], &[]);
+            os_libs.push(paths.ruxlibc_bin());
+        } else if os_config.ulib == "ruxmusl" {
+            let musl = commands::musl_source();
+            plan.commands.push(BuildCommand {
+                description: format!("Fetching source {}", musl.name),
+                command: sources::fetch_command(&musl),
+                outputs: vec![sources::marker_path(&musl)],
+                ..Default::default()
+            });
+            let configure = commands::ruxmusl_configure_command(
+                build_config,
+                os_config,
+                &format!("\"$OLDPWD/{}\"", musl.dest),
+            );
+            plan.commands.push(BuildCommand {
+                description: "Compiling and installing Musl".to_string(),
+                command: format!(
+                    "mkdir -p {dir} && cd {dir} && {} && make -j && make install",
+                    configure,
+                    dir = paths.ruxmusl_dir()
+                ),
+                inputs: vec![sources::marker_path(&musl)],
+                outputs: vec![paths.ruxmusl_bin()],
+                ..Default::default()
+            });
+            os_libs.push(paths.ruxmusl_bin());
+        }
+    }
+
+    for (target, tgt) in targets.iter().zip(&resolved) {
+        let mut order_inputs = fetched.clone();
+        // The headers of ruxmusl are installed with it
+        if os_config.ulib == "ruxmusl" {
+            order_inputs.push(paths.ruxmusl_bin());
+        }
+        for step in &target.steps {
+            let mut command = format!("cd {} && {}", step.working_dir, step.command);
+            let mut outputs = step.outputs.clone();
+            // A step without outputs is tracked by a stamp, like the manifest of ruxgo
+            if outputs.is_empty() {
+                let stamp = format!("{}/{}.{}.step", paths.profile_dir(), target.name, step.name);
+                command = format!("({}) && touch {}", command, stamp);
+                outputs.push(stamp);
+            }
+            plan.commands.push(BuildCommand {
+                description: format!("Running step {} of target {}", step.name, target.name),
+                command,
+                inputs: step.inputs.clone(),
+                order_inputs: fetched.clone(),
+                outputs: outputs.clone(),
+                always: step.inputs.is_empty() && step.outputs.is_empty(),
+            });
+            order_inputs.extend(outputs);
+        }
+        let dep_bins: Vec<String> = target
+            .deps
+            .iter()
+            .filter_map(|dep| targets.iter().find(|t| t.name == *dep))
+            .map(|dep| paths.target_bin(&dep.name, &dep.typ).0)
+            .collect();
+        let link_libs = match target.typ.as_str() {
+            "exe" => os_libs.as_slice(),
+            _ => &[],
+        };
+        target_commands(&mut plan, tgt, target, &order_inputs, &dep_bins, link_libs);
+    }
+    Ok(plan)
+}
+
+/// Adds the commands compiling and linking a target
+/// # Arguments
+/// * `tgt` - The resolved target
+/// * `target` - The target config
+/// * `order_inputs` - The files needed before compiling, e.g. the outputs of steps
+/// * `dep_bins` - The artifacts of the dependant libs
+/// * `os_libs` - The libraries of the OS linked into an executable
+fn target_commands(
+    plan: &mut BuildPlan,
+    tgt: &Target,
+    target: &TargetConfig,
+    order_inputs: &[String],
+    dep_bins: &[String],
+    os_libs: &[String],
+) {
+    let mut objects = Vec::new();
+    for unit in tgt.compile_units() {
+        plan.commands.push(BuildCommand {
+            description: format!("Compiling {}", unit.file),
+            command: unit.command,
+            inputs: std::iter::once(unit.file).chain(unit.includes).collect(),
+            order_inputs: order_inputs.to_vec(),
+            outputs: vec![unit.object.clone()],
+            always: false,
+        });
+        objects.push(unit.object);
+    }
+    let (link_cmd, objcopy_cmd) = tgt.link_commands();
+    if link_cmd.is_empty() {
+        return;
+    }
+    let mut inputs = objects;
+    inputs.extend_from_slice(dep_bins);
+    inputs.extend_from_slice(os_libs);
+    // The ELF of an executable for an OS is turned into the bin by objcopy
+    let linked = match objcopy_cmd.is_empty() {
+        true => tgt.bin_path.clone(),
+        false => tgt.elf_path.clone(),
+    };
+    plan.commands.push(BuildCommand {
+        description: format!("Linking target {}", target.name),
+        command: link_cmd,
+        inputs,
+        outputs: vec![linked.clone()],
+        ..Default::default()
+    });
+    if !objcopy_cmd.is_empty() {
+        plan.commands.push(BuildCommand {
+            description: format!("Generating {}", tgt.bin_path),
+            command: objcopy_cmd,
+            inputs: vec![linked],
+            outputs: vec![tgt.bin_path.clone()],
+            ..Default::default()
+        });
+    }
+    plan.defaults.push(tgt.bin_path.clone());
+}
+
+/// Returns the build file of a plan in the given format
+/// # Arguments
+/// * `plan` - The resolved build, see `plan`
+/// * `format` - `ninja`, `make` or `cmake`
+pub fn export(plan: &BuildPlan, format: &str) -> Result<String> {
+    // None of the formats can express a command without outputs
+    if let Some(cmd) = plan.commands.iter().find(|cmd| cmd.outputs.is_empty()) {
+        return Err(Error::Config(format!(
+            "Command '{}' of the build plan has no outputs",
+            cmd.description
+        )));
+    }
+    match format {
+        "ninja" => Ok(ninja(plan)),
+        "make" => Ok(make(plan)),
+        "cmake" => Ok(cmake(plan)),
+        _ => Err(Error::Config(format!(
+            "Unsupported export format: {}, expected one of ninja, make, cmake",
+            format
+        ))),
+    }
+}
+
+/// Returns the command creating the directories of the outputs before running
+fn with_output_dirs(cmd: &BuildCommand) -> String {
+    let mut dirs: Vec<&str> = cmd
+        .outputs
+        .iter()
+        .filter_map(|output| Path::new(output).parent()?.to_str())
+        .filter(|dir| !dir.is_empty())
+        .collect();
+    dirs.sort_unstable();
+    dirs.dedup();
+    match dirs.is_empty() {
+        true => cmd.command.clone(),
+        false => format!("mkdir -p {} && {}", dirs.join(" "), cmd.command),
+    }
+}
+
+/// Returns the plan as a Ninja build file
+fn ninja(plan: &BuildPlan) -> String {
+    let path = |path: &String| {
+        path.replace('$', "$$")
+            .replace(' ', "$ ")
+            .replace(':', "$:")
+    };
+    let paths = |paths: &[String]| paths.iter().map(path).collect::<Vec<_>>().join(" ");
+    let mut output = String::from("# Generated by ruxgo export, run from the project root\n\n");
+    output.push_str("rule run\n  command = $cmd\n  description = $desc\n\n");
+    output.push_str("build always: phony\n\n");
+    for cmd in &plan.commands {
+        output.push_str(&format!(
+            "build {}: run {}",
+            paths(&cmd.outputs),
+            paths(&cmd.inputs)
+        ));
+        if cmd.always {
+            output.push_str(" | always");
+        }
+        if !cmd.order_inputs.is_empty() {
+            output.push_str(&format!(" || {}", paths(&cmd.order_inputs)));
+        }
+        output.push_str(&format!(
+            "\n  cmd = {}\n  desc = {}\n\n",
+            with_output_dirs(cmd).replace('$', "$$"),
+            cmd.description.replace('$', "$$")
+        ));
+    }
+    output.push_str(&format!("default {}\n", paths(&plan.defaults)));
+    output
+}
+
+/// Returns the plan as a Makefile
+fn make(plan: &BuildPlan) -> String {
+    let path = |path: &String| path.replace('$', "$$").replace(' ', "\\ ");
+    let paths = |paths: &[String]| paths.iter().map(path).collect::<Vec<_>>().join(" ");
+    let mut output = String::from("# Generated by ruxgo export, run from the project root\n\n");
+    let phony: Vec<String> = plan
+        .commands
+        .iter()
+        .filter(|cmd| cmd.always)
+        .flat_map(|cmd| cmd.outputs.clone())
+        .collect();
+    output.push_str(&format!(
+        ".PHONY: {}\n",
+        paths(&[vec!["all".to_string()], phony].concat())
+    ));
+    output.push_str(&format!("all: {}\n\n", paths(&plan.defaults)));
+    for cmd in &plan.commands {
+        let Some((first, others)) = cmd.outputs.split_first() else {
+            continue;
+        };
+        output.push_str(&format!("{}: {}", path(first), paths(&cmd.inputs)));
+        if !cmd.order_inputs.is_empty() {
+            output.push_str(&format!(" | {}", paths(&cmd.order_inputs)));
+        }
+        output.push_str(&format!(
+            "\n\t@echo '{}'\n\t{}\n",
+            cmd.description.replace('\'', "'\\''").replace('$', "$$"),
+            with_output_dirs(cmd).replace('$', "$$")
+        ));
+        // The other outputs are made by the same command
+        if !others.is_empty() {
+            output.push_str(&format!("{}: {}\n", paths(others), path(first)));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Returns the plan as a CMakeLists.txt of custom commands
+fn cmake(plan: &BuildPlan) -> String {
+    let quote = |s: &str| {
+        format!(
+            "\"{}\"",
+            s.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "\\$")
+                .replace(';', "\\;")
+        )
+    };
+    let path = |path: &String| match Path::new(path).is_absolute() {
+        true => quote(path),
+        false => format!("\"${{RUXGO_ROOT}}/{}", &quote(path)[1..]),
+    };
+    let paths = |paths: &[String]| paths.iter().map(path).collect::<Vec<_>>().join(" ");
+    let name = std::env::current_dir()
+        .ok()
+        .and_then(|dir| Some(dir.file_name()?.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "ruxgo".to_string());
+    let mut output = String::from("# Generated by ruxgo export, place it in the project root\n");
+    output.push_str("cmake_minimum_required(VERSION 3.10)\n");
+    output.push_str(&format!("project({} NONE)\n\n", quote(&name)));
+    output.push_str("set(RUXGO_ROOT ${CMAKE_CURRENT_SOURCE_DIR})\n\n");
+    for cmd in &plan.commands {
+        output.push_str(&format!(
+            "add_custom_command(\n  OUTPUT {}\n  COMMAND sh -c {}\n",
+            paths(&cmd.outputs),
+            quote(&with_output_dirs(cmd))
+        ));
+        // CMake has no order-only dependencies, they are plain ones
+        let inputs: Vec<String> = cmd
+            .inputs
+            .iter()
+            .chain(&cmd.order_inputs)
+            .cloned()
+            .collect();
+        if !inputs.is_empty() {
+            output.push_str(&format!("  DEPENDS {}\n", paths(&inputs)));
+        }
+        output.push_str(&format!(
+            "  WORKING_DIRECTORY ${{RUXGO_ROOT}}\n  COMMENT {}\n  VERBATIM\n)\n\n",
+            quote(&cmd.description)
+        ));
+    }
+    output.push_str(&format!(
+        "add_custom_target(ruxgo ALL DEPENDS {})\n",
+        paths(&plan.defaults)
+    ));
+    output
+}
@@ -1,4 +1,4 @@
-use crate::utils::log::{log, LogLevel};
+use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -8,8 +8,8 @@ enum ConfigParam {
     License(String),
 }
 
-fn set_config_param(param: ConfigParam, config_file: &PathBuf) {
-    let mut global_conf = GlobalConfig::from_file(config_file);
+fn set_config_param(param: ConfigParam, config_file: &PathBuf) -> Result<()> {
+    let mut global_conf = GlobalConfig::from_file(config_file)?;
     match param {
         ConfigParam::DefaultCompiler(value) => {
             global_conf.default_compiler = value;
@@ -22,7 +22,14 @@ fn set_config_param(param: ConfigParam, config_file: &PathBuf) {
         }
     }
 
-    std::fs::write(config_file, toml::to_string(&global_conf).unwrap()).unwrap();
+    let contents = toml::to_string(&global_conf)
+        .map_err(|e| Error::Config(format!("Could not serialize global config: {}", e)))?;
+    std::fs::write(config_file, contents).map_err(|e| {
+        Error::io(
+            format!("Could not write global config '{}'", config_file.display()),
+            e,
+        )
+    })
 }
 
 #[derive(Serialize, Deserialize)]
@@ -33,71 +40,57 @@ pub struct GlobalConfig {
 }
 
 impl GlobalConfig {
-    pub fn set_defaults(config: &PathBuf, parameter: &str, value: &str) {
+    pub fn set_defaults(config: &PathBuf, parameter: &str, value: &str) -> Result<()> {
         match parameter {
             "default_compiler" => {
                 if value == "gcc" || value == "clang" {
-                    set_config_param(ConfigParam::DefaultCompiler(value.to_string()), config);
+                    set_config_param(ConfigParam::DefaultCompiler(value.to_string()), config)?;
                 } else {
-                    log(
-                        LogLevel::Error,
-                        "Invalid compiler. See `ruxgo config --help` for more info",
-                    );
-                    std::process::exit(1);
+                    return Err(Error::Config(
+                        "Invalid compiler. See `ruxgo config --help` for more info".to_string(),
+                    ));
                 }
             }
             "default_language" => {
                 if value == "c" || value == "cpp" {
-                    set_config_param(ConfigParam::DefaultLanguage(value.to_string()), config);
+                    set_config_param(ConfigParam::DefaultLanguage(value.to_string()), config)?;
                 } else {
-                    log(
-                        LogLevel::Error,
-                        "Invalid language. See `ruxgo config --help` for more info",
-                    );
-                    std::process::exit(1);
+                    return Err(Error::Config(
+                        "Invalid language. See `ruxgo config --help` for more info".to_string(),
+                    ));
                 }
             }
             "license" => {
                 if std::path::Path::new(value).exists() {
-                    let value = std::fs::read_to_string(value).unwrap();
-                    set_config_param(ConfigParam::License(value), config);
+                    let value = std::fs::read_to_string(value).map_err(|e| {
+                        Error::io(format!("Could not read license file '{}'", value), e)
+                    })?;
+                    set_config_param(ConfigParam::License(value), config)?;
                 } else {
-                    log(
-                        LogLevel::Error,
-                        "Invalid license file. See `ruxgo config --help` for more info",
-                    );
-                    std::process::exit(1);
+                    return Err(Error::Config(
+                        "Invalid license file. See `ruxgo config --help` for more info".to_string(),
+                    ));
                 }
             }
             _ => {
-                log(
-                    LogLevel::Error,
-                    "Invalid parameter. See `ruxgo config --help` for more info",
-                );
-                std::process::exit(1);
+                return Err(Error::Config(
+                    "Invalid parameter. See `ruxgo config --help` for more info".to_string(),
+                ));
             }
         }
+        Ok(())
     }
 
-    pub fn from_file(path: &PathBuf) -> Self {
-        let config = std::fs::read_to_string(path).unwrap();
-        let mut config: toml::Value = toml::from_str(&config).unwrap();
-        let config = config.as_table_mut().unwrap();
-        GlobalConfig {
-            default_compiler: config
-                .get("default_compiler")
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .to_string(),
-            default_language: config
-                .get("default_language")
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .to_string(),
-            license: config.get("license").unwrap().as_str().unwrap().to_string(),
-        }
+    pub fn from_file(path: &PathBuf) -> Result<Self> {
+        let config = std::fs::read_to_string(path).map_err(|e| {
+            Error::io(
+                format!("Could not read global config '{}'", path.display()),
+                e,
+            )
+        })?;
+        toml::from_str(&config).map_err(|e| {
+            Error::Config(format!("Invalid global config '{}': {}", path.display(), e))
+        })
     }
 
     pub fn get_default_compiler(&self) -> String {
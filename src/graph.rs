@@ -0,0 +1,158 @@
+//! Graph Module
+
+use crate::builder::Target;
+use crate::error::Result;
+use crate::parser::{BuildConfig, OSConfig, TargetConfig};
+use std::collections::BTreeSet;
+
+/// Returns the dependency tree of the targets, as printed by `ruxgo tree`
+/// # Notes
+/// Each target that no other target depends on is a root. A target is shown with its
+/// type and artifact; a target that was already expanded is marked with `(*)` instead of
+/// repeating its dependencies.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `targets` - The targets in build order
+pub fn tree(build_config: &BuildConfig, targets: &[TargetConfig]) -> String {
+    let mut output = String::new();
+    let mut expanded = BTreeSet::new();
+    for root in targets
+        .iter()
+        .rev()
+        .filter(|target| !targets.iter().any(|t| t.deps.contains(&target.name)))
+    {
+        tree_node(
+            build_config,
+            targets,
+            &root.name,
+            "",
+            "",
+            &mut expanded,
+            &mut output,
+        );
+    }
+    output
+}
+
+/// Appends a target and its dependencies to the tree
+/// # Arguments
+/// * `name` - The name of the target
+/// * `prefix` - The prefix of the line of the target
+/// * `indent` - The prefix of the lines of its dependencies
+/// * `expanded` - The targets whose dependencies were already shown
+/// * `output` - Receives the tree
+fn tree_node(
+    build_config: &BuildConfig,
+    targets: &[TargetConfig],
+    name: &str,
+    prefix: &str,
+    indent: &str,
+    expanded: &mut BTreeSet<String>,
+    output: &mut String,
+) {
+    let Some(target) = targets.iter().find(|target| target.name == name) else {
+        output.push_str(&format!("{}{} (not found)\n", prefix, name));
+        return;
+    };
+    let (bin_path, _) = build_config.paths.target_bin(&target.name, &target.typ);
+    let repeated = !target.deps.is_empty() && !expanded.insert(target.name.clone());
+    output.push_str(&format!(
+        "{}{} ({}) {}{}\n",
+        prefix,
+        target.name,
+        target.typ,
+        bin_path,
+        if repeated { " (*)" } else { "" }
+    ));
+    if repeated {
+        return;
+    }
+    for (i, dep) in target.deps.iter().enumerate() {
+        let last = i + 1 == target.deps.len();
+        let (branch, next) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        tree_node(
+            build_config,
+            targets,
+            dep,
+            &format!("{}{}", indent, branch),
+            &format!("{}{}", indent, next),
+            expanded,
+            output,
+        );
+    }
+}
+
+/// Returns the dependency graph of the targets in the Graphviz dot format
+/// # Notes
+/// An edge points from a target to the target it depends on. With `headers`, the
+/// sources of each target are added, with an edge from the target to each source and
+/// from each source to every header it includes.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The local os configuration
+/// * `targets` - The targets in build order
+/// * `headers` - Whether to add the sources and the headers they include
+pub fn dot(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    targets: &Vec<TargetConfig>,
+    headers: bool,
+) -> Result<String> {
+    let mut output = String::from("digraph ruxgo {\n");
+    for target in targets {
+        output.push_str(&format!(
+            "    {} [shape=box, label={}];\n",
+            quote(&target.name),
+            quote(&format!("{}\n{}", target.name, target.typ))
+        ));
+    }
+    for target in targets {
+        for dep in &target.deps {
+            output.push_str(&format!("    {} -> {};\n", quote(&target.name), quote(dep)));
+        }
+    }
+    if headers {
+        let mut files = BTreeSet::new();
+        let mut edges = Vec::new();
+        for target in targets {
+            let tgt = Target::new(build_config, os_config, target, targets)?;
+            for unit in tgt.compile_units() {
+                edges.push(format!(
+                    "    {} -> {} [style=dashed];\n",
+                    quote(&target.name),
+                    quote(&unit.file)
+                ));
+                for include in &unit.includes {
+                    edges.push(format!(
+                        "    {} -> {} [style=dotted];\n",
+                        quote(&unit.file),
+                        quote(include)
+                    ));
+                    files.insert(include.clone());
+                }
+                files.insert(unit.file);
+            }
+        }
+        for file in files {
+            output.push_str(&format!("    {} [shape=note];\n", quote(&file)));
+        }
+        edges.dedup();
+        edges.iter().for_each(|edge| output.push_str(edge));
+    }
+    output.push_str("}\n");
+    Ok(output)
+}
+
+/// Quotes a node name or label of the dot format
+fn quote(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
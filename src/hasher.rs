@@ -1,16 +1,144 @@
 //! This module contains functions for hashing files and checking if they have changed.
 
+use crate::error::{Error, Result};
 use crate::utils::log::{log, LogLevel};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write as OtherWrite;
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::RwLock;
+use std::time::UNIX_EPOCH;
 
 const CHUNK_SIZE: usize = 1024 * 1024; // 1MB: read files in chunks for efficiency
 
+lazy_static! {
+    /// Hashes computed during this invocation, keyed by path and tagged with the
+    /// size and mtime they were computed for, so every file is hashed at most once
+    /// unless it changes in between.
+    static ref HASH_CACHE: RwLock<HashMap<String, (u64, u64, String)>> =
+        RwLock::new(HashMap::new());
+}
+
+/// The version of the manifest format, bumped on incompatible changes
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Struct describing the recorded state of a single file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ManifestEntry {
+    /// SHA-1 of the file content
+    pub hash: String,
+    /// File size in bytes
+    pub size: u64,
+    /// Modification time in nanoseconds since the unix epoch
+    pub mtime: u64,
+    /// Fingerprint of the command that consumed the file, empty for headers
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    /// Name of the target that produced this entry
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target: String,
+}
+
+/// Struct describing the build state of a target
+/// # Notes
+/// The manifest is stored as versioned JSON so that arbitrary paths (including spaces)
+/// round-trip, and is written through a temp file and a rename so that an interrupted
+/// build never leaves a truncated file behind.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub version: u32,
+    /// Fingerprint of the toolchain the entries were built with
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub toolchain: String,
+    pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            version: MANIFEST_VERSION,
+            toolchain: String::new(),
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Loads the manifest from a file.
+    /// # Arguments
+    /// * `path` - The path of the manifest.
+    pub fn load(path: &str) -> Manifest {
+        if !Path::new(path).exists() {
+            return Manifest::default();
+        }
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(why) => {
+                log(
+                    LogLevel::Warn,
+                    &format!("Failed to read manifest '{}': {}", path, why),
+                );
+                return Manifest::default();
+            }
+        };
+        match serde_json::from_str::<Manifest>(&contents) {
+            Ok(manifest) if manifest.version == MANIFEST_VERSION => manifest,
+            Ok(manifest) => {
+                log(
+                    LogLevel::Warn,
+                    &format!(
+                        "Manifest '{}' has unsupported version {}, rebuilding",
+                        path, manifest.version
+                    ),
+                );
+                Manifest::default()
+            }
+            Err(why) => {
+                log(
+                    LogLevel::Warn,
+                    &format!("Manifest '{}' is corrupted, rebuilding: {}", path, why),
+                );
+                Manifest::default()
+            }
+        }
+    }
+
+    /// Saves the manifest to a file atomically.
+    /// # Arguments
+    /// * `path` - The path of the manifest.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|why| Error::io(format!("Could not serialize {}", path), why.into()))?;
+        Hasher::write_atomic(path, contents.as_bytes())
+    }
+
+    /// Records the toolchain fingerprint, dropping all entries if it changed.
+    /// Returns whether the toolchain changed since the last build.
+    /// # Arguments
+    /// * `fingerprint` - The fingerprint of the current toolchain.
+    pub fn check_toolchain(&mut self, fingerprint: &str) -> bool {
+        // Entries recorded without a fingerprint were built with an unknown toolchain
+        let changed = !self.entries.is_empty() && self.toolchain != fingerprint;
+        if changed {
+            self.entries.clear();
+        }
+        self.toolchain = fingerprint.to_string();
+        changed
+    }
+
+    /// Returns the entry of a file if it exists.
+    /// # Arguments
+    /// * `path` - The path of the file.
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.entries.get(path)
+    }
+}
+
 pub struct Hasher;
 
 impl Hasher {
@@ -61,6 +189,34 @@ impl Hasher {
         )
     }
 
+    /// Returns the hash, size and mtime of a file, hashing it only if it is not cached.
+    fn cached_hash(path: &str) -> Option<(String, u64, u64)> {
+        let (size, mtime) = Hasher::file_stat(path)?;
+        if let Some((cached_size, cached_mtime, hash)) = HASH_CACHE.read().unwrap().get(path) {
+            if *cached_size == size && *cached_mtime == mtime {
+                return Some((hash.clone(), size, mtime));
+            }
+        }
+        let hash = Hasher::hash_file(path)?;
+        HASH_CACHE
+            .write()
+            .unwrap()
+            .insert(path.to_string(), (size, mtime, hash.clone()));
+        Some((hash, size, mtime))
+    }
+
+    /// Returns the size and mtime (in nanoseconds) of a file.
+    fn file_stat(path: &str) -> Option<(u64, u64)> {
+        let metadata = fs::metadata(path).ok()?;
+        let mtime = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos() as u64;
+        Some((metadata.len(), mtime))
+    }
+
     /// Hashes a string and returns the hash as a string.
     /// # Arguments
     /// * `content` - Contains the content to be hashed.
@@ -78,134 +234,224 @@ impl Hasher {
         result
     }
 
-    /// Returns the hash of a file if it exists in the path_hash.
-    /// Otherwise returns None.
+    /// Writes a file atomically through a temp file and a rename.
     /// # Arguments
-    /// * `path` - The path of the file to get the hash of.
-    /// * `path_hash` - The hashmap of paths and hashes.
-    pub fn get_hash(path: &str, path_hash: &HashMap<String, String>) -> Option<String> {
-        if path_hash.contains_key(path) {
-            return Some(path_hash.get(path).unwrap().to_string());
-        }
-        None
-    }
-
-    /// Loads the hashes from a file and returns them as a hashmap.
-    /// # Arguments
-    /// * `path` - The path of the file to load the hashes from.
-    pub fn load_hashes_from_file(path: &str) -> HashMap<String, String> {
-        let mut path_hash: HashMap<String, String> = HashMap::new();
-        let path = Path::new(path);
-        if !path.exists() {
-            return path_hash;
-        }
-        let mut file = OpenOptions::new().read(true).open(path).unwrap();
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).unwrap();
-        for line in contents.lines() {
-            if line.is_empty() {
-                continue;
-            }
-            let mut split = line.split(' ');
-            let path = split.next().unwrap();
-            let hash = split.next().unwrap();
-            path_hash.insert(path.to_string(), hash.to_string());
-        }
-        path_hash
-    }
-
-    /// Saves the hashes to a file.
-    /// # Arguments
-    /// * `path` - The path of the file to save the hashes to.
-    /// * `path_hash` - The hashmap of paths and hashes.
-    pub fn save_hashes_to_file(path: &str, path_hash: &HashMap<String, String>) {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)
-            .unwrap_or_else(|_| {
-                log(LogLevel::Error, &format!("Failed to open file: {}", path));
-                std::process::exit(1);
-            });
-        for (path, hash) in path_hash {
-            let line = format!("{} {}\n", path, hash);
-            file.write_all(line.as_bytes()).unwrap();
-        }
+    /// * `path` - The path of the file to write.
+    /// * `contents` - The contents to write.
+    pub fn write_atomic(path: impl AsRef<Path>, contents: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let result = File::create(&tmp_path)
+            .and_then(|mut file| {
+                file.write_all(contents)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp_path, path));
+        result.map_err(|why| {
+            let _ = fs::remove_file(&tmp_path);
+            Error::io(format!("Failed to write file '{}'", path.display()), why)
+        })
     }
 
     /// Saves a string hash to a file.
     /// # Arguments
     /// * `path` - The path of the file to save the string hash to.
     /// * `hash` - The string hash value.
-    pub fn save_hash_to_file(path: &str, hash: &str) {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)
-            .unwrap_or_else(|_| {
-                log(
-                    LogLevel::Error,
-                    &format!("Failed to open hash file: {}", path),
-                );
-                std::process::exit(1);
-            });
-        file.write_all(hash.as_bytes()).unwrap();
+    pub fn save_hash_to_file(path: &str, hash: &str) -> Result<()> {
+        Hasher::write_atomic(path, hash.as_bytes())
     }
 
     /// Reads a string hash from a file.
     /// # Arguments
     /// * `path` - The path of the file to read.
-    pub fn read_hash_from_file(path: &str) -> String {
-        let mut hash = String::new();
+    pub fn read_hash_from_file(path: &str) -> Result<String> {
         if !Path::new(path).exists() {
-            return hash;
-        }
-        if let Ok(mut file) = File::open(path) {
-            file.read_to_string(&mut hash).unwrap();
-            hash
-        } else {
-            log(
-                LogLevel::Warn,
-                &format!("Failed to open hash file '{}'", path),
-            );
-            std::process::exit(1);
+            return Ok(String::new());
         }
+        fs::read_to_string(path)
+            .map_err(|why| Error::io(format!("Failed to read hash file '{}'", path), why))
     }
 
     /// Checks if a file has changed.
+    /// # Notes
+    /// The file is only hashed when its size or mtime differ from the recorded ones.
+    /// # Arguments
+    /// * `path` - The path of the file to check.
+    /// * `manifest` - The manifest of the target.
+    pub fn is_file_changed(path: &str, manifest: &Manifest) -> bool {
+        let entry = match manifest.get(path) {
+            Some(entry) => entry,
+            None => return true,
+        };
+        let (size, mtime) = match Hasher::file_stat(path) {
+            Some(stat) => stat,
+            None => return true,
+        };
+        if entry.size == size && entry.mtime == mtime {
+            return false;
+        }
+        let new_hash = Hasher::cached_hash(path).unwrap_or_default().0;
+        entry.hash != new_hash
+    }
+
+    /// Checks if the command that consumed a file has changed.
+    /// Entries without a recorded fingerprint are considered changed.
     /// # Arguments
     /// * `path` - The path of the file to check.
-    /// * `path_hash` - The hashmap of paths and hashes.
-    pub fn is_file_changed(path: &str, path_hash: &HashMap<String, String>) -> bool {
-        let hash = Hasher::get_hash(path, path_hash);
-        if hash.is_none() {
-            return true;
+    /// * `command` - The command the file is consumed by.
+    /// * `manifest` - The manifest of the target.
+    pub fn is_command_changed(path: &str, command: &str, manifest: &Manifest) -> bool {
+        match manifest.get(path) {
+            Some(entry) => entry.command != Hasher::hash_string(command),
+            None => true,
         }
-        let hash = hash.unwrap();
-        let new_hash = Hasher::hash_file(path).unwrap_or_default();
-        hash != new_hash
     }
 
-    /// Saves the hash of a file to the hashmap.
+    /// Saves the state of a file to the manifest.
     /// # Arguments
     /// * `path` - The path of the file to save the hash of.
-    /// * `path_hash` - The hashmap of paths and hashes.
-    pub fn save_hash(path: &str, path_hash: &mut HashMap<String, String>) {
-        let new_hash = Hasher::hash_file(path).unwrap_or_default();
-        let hash = Hasher::get_hash(path, path_hash);
-        if hash.is_none() {
-            path_hash.insert(path.to_string(), new_hash);
-            return;
+    /// * `manifest` - The manifest of the target.
+    /// * `target` - The name of the target that consumed the file.
+    /// * `command` - The command the file was consumed by, if any.
+    pub fn save_hash(path: &str, manifest: &mut Manifest, target: &str, command: Option<&str>) {
+        let (new_hash, size, mtime) = Hasher::cached_hash(path).unwrap_or_default();
+        if let Some(entry) = manifest.get(path) {
+            if entry.hash != new_hash {
+                log(
+                    LogLevel::Info,
+                    &format!("File changed, updating hash for file: {}", path),
+                );
+            }
         }
-        let hash = hash.unwrap();
-        if hash != new_hash {
-            log(
-                LogLevel::Info,
-                &format!("File changed, updating hash for file: {}", path),
-            );
-            path_hash.insert(path.to_string(), new_hash);
+        let command = command.map(Hasher::hash_string).unwrap_or_default();
+        manifest.entries.insert(
+            path.to_string(),
+            ManifestEntry {
+                hash: new_hash,
+                size,
+                mtime,
+                command,
+                target: target.to_string(),
+            },
+        );
+    }
+
+    /// Saves the state of several files to the manifest, hashing them in parallel.
+    /// # Arguments
+    /// * `paths` - The paths of the files to save the hash of.
+    /// * `manifest` - The manifest of the target.
+    /// * `target` - The name of the target that consumed the files.
+    pub fn save_hashes(paths: &[&str], manifest: &mut Manifest, target: &str) {
+        paths.par_iter().for_each(|path| {
+            Hasher::cached_hash(path);
+        });
+        for path in paths {
+            Hasher::save_hash(path, manifest, target, None);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir;
+
+    fn path_str(dir: &Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn load_missing_manifest_is_empty() {
+        let tmp = test_dir("hasher-missing");
+        let dir = tmp.path();
+        let manifest = Manifest::load(&path_str(dir, "a.manifest.json"));
+        assert_eq!(manifest, Manifest::default());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let tmp = test_dir("hasher-round-trip");
+        let dir = tmp.path();
+        let path = path_str(dir, "a.manifest.json");
+        let mut manifest = Manifest {
+            toolchain: "gcc 13".to_string(),
+            ..Default::default()
+        };
+        manifest.entries.insert(
+            "src/main.c".to_string(),
+            ManifestEntry {
+                hash: "abc".to_string(),
+                size: 3,
+                mtime: 42,
+                command: "cmd".to_string(),
+                target: "app".to_string(),
+            },
+        );
+        manifest.save(&path).unwrap();
+        assert_eq!(Manifest::load(&path), manifest);
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+    }
+
+    #[test]
+    fn corrupted_or_unsupported_manifest_is_empty() {
+        let tmp = test_dir("hasher-corrupted");
+        let dir = tmp.path();
+        let path = path_str(dir, "a.manifest.json");
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(Manifest::load(&path), Manifest::default());
+        fs::write(&path, r#"{"version": 999, "entries": {}}"#).unwrap();
+        assert_eq!(Manifest::load(&path), Manifest::default());
+    }
+
+    #[test]
+    fn recorded_entries_are_unchanged_until_the_file_or_command_changes() {
+        let tmp = test_dir("hasher-recorded-entries");
+        let dir = tmp.path();
+        let source = path_str(dir, "main.c");
+        fs::write(&source, "int main(void) { return 0; }\n").unwrap();
+        let mut manifest = Manifest::default();
+        Hasher::save_hash(&source, &mut manifest, "app", Some("gcc -c main.c"));
+        assert!(!Hasher::is_file_changed(&source, &manifest));
+        assert!(!Hasher::is_command_changed(
+            &source,
+            "gcc -c main.c",
+            &manifest
+        ));
+        assert!(Hasher::is_command_changed(
+            &source,
+            "gcc -O2 -c main.c",
+            &manifest
+        ));
+        fs::write(&source, "int main(void) { return 1; }\n").unwrap();
+        assert!(Hasher::is_file_changed(&source, &manifest));
+
+        // An entry without a command fingerprint cannot be trusted
+        Hasher::save_hash(&source, &mut manifest, "app", None);
+        assert!(Hasher::is_command_changed(
+            &source,
+            "gcc -c main.c",
+            &manifest
+        ));
+    }
+
+    #[test]
+    fn check_toolchain_drops_entries_of_an_unknown_toolchain() {
+        let mut manifest = Manifest::default();
+        assert!(!manifest.check_toolchain("gcc 13"));
+        manifest
+            .entries
+            .insert("a.c".to_string(), ManifestEntry::default());
+        assert!(!manifest.check_toolchain("gcc 13"));
+        assert_eq!(manifest.entries.len(), 1);
+        assert!(manifest.check_toolchain("gcc 14"));
+        assert!(manifest.entries.is_empty());
+
+        manifest
+            .entries
+            .insert("a.c".to_string(), ManifestEntry::default());
+        manifest.toolchain.clear();
+        assert!(manifest.check_toolchain("gcc 14"));
+        assert!(manifest.entries.is_empty());
+    }
+}
@@ -0,0 +1,824 @@
+//! Import Module
+
+use crate::error::{Error, Result};
+use crate::parser::{TargetConfig, ToolchainConfig};
+use crate::project::Project;
+use crate::utils::log::{log, LogLevel};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Flags taking their value as the next argument
+static FLAGS_WITH_VALUE: [&str; 9] = [
+    "-D",
+    "-U",
+    "-I",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-include",
+    "-imacros",
+    "-x",
+];
+
+/// Flags whose value is a path, rebased onto the project root
+static PATH_FLAGS: [&str; 6] = [
+    "-I",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-include",
+    "-imacros",
+];
+
+/// Flags generating make dependencies, ruxgo tracks the headers itself
+static DEPFILE_FLAGS: [&str; 3] = ["-MD", "-MMD", "-MP"];
+
+/// Dependency flags taking a value
+static DEPFILE_VALUE_FLAGS: [&str; 3] = ["-MF", "-MT", "-MQ"];
+
+/// Struct describing an entry of compile_commands.json
+#[derive(Debug, Clone, Deserialize)]
+struct Entry {
+    directory: String,
+    file: String,
+    #[serde(default)]
+    arguments: Vec<String>,
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    output: String,
+}
+
+/// Struct describing how a source file was compiled
+#[derive(Debug, Clone)]
+struct Source {
+    /// The source, relative to the project root
+    file: String,
+    /// The absolute path of the object
+    object: PathBuf,
+    compiler: String,
+    /// The flags without the includes, the output and the source
+    flags: Vec<String>,
+    /// The include directories, relative to the project root
+    includes: Vec<String>,
+}
+
+/// Struct describing a link command of the link log
+#[derive(Debug, Clone)]
+struct Link {
+    name: String,
+    typ: String,
+    tool: String,
+    /// The absolute path of the output
+    output: PathBuf,
+    /// The objects and libraries linked, as absolute paths
+    inputs: Vec<PathBuf>,
+    /// The libraries given as `-lname`
+    libs: Vec<String>,
+    flags: Vec<String>,
+}
+
+/// Imports an existing build into a project that rebuilds it
+/// # Notes
+/// Every link command of the link log becomes a target with the sources of the objects
+/// it links and the targets of the libraries it links as deps. Without a link log, all
+/// sources form a single executable named after the project directory. The flags of
+/// the largest group of sources of a target compiled alike become its `cflags` and
+/// `include_dir`; the other sources are moved into `object` targets it depends on.
+/// # Arguments
+/// * `compile_commands` - The path of compile_commands.json
+/// * `link_log` - The path of a file with the link commands of the build, one per line
+pub fn import(compile_commands: &Path, link_log: Option<&Path>) -> Result<Project> {
+    let root = std::env::current_dir()
+        .map_err(|why| Error::io("Could not get the current directory", why))?;
+    import_in(&root, compile_commands, link_log)
+}
+
+/// Imports an existing build into a project rooted at `root`, see `import`
+fn import_in(root: &Path, compile_commands: &Path, link_log: Option<&Path>) -> Result<Project> {
+    let contents = fs::read_to_string(compile_commands).map_err(|why| {
+        Error::io(
+            format!("Could not read '{}'", compile_commands.display()),
+            why,
+        )
+    })?;
+    let entries: Vec<Entry> = serde_json::from_str(&contents).map_err(|e| {
+        Error::Config(format!(
+            "Invalid compilation database '{}': {}",
+            compile_commands.display(),
+            e
+        ))
+    })?;
+    let mut sources = Vec::new();
+    for entry in entries {
+        let source = parse_entry(root, entry)?;
+        // A file compiled twice into the same object only needs to be built once
+        if !sources.iter().any(|s: &Source| s.object == source.object) {
+            sources.push(source);
+        }
+    }
+    if sources.is_empty() {
+        return Err(Error::Config(format!(
+            "No sources found in '{}'",
+            compile_commands.display()
+        )));
+    }
+    let dirs: Vec<PathBuf> = sources
+        .iter()
+        .filter_map(|s| s.object.parent().map(|p| p.to_path_buf()))
+        .collect();
+
+    let links = match link_log {
+        Some(link_log) => {
+            let contents = fs::read_to_string(link_log).map_err(|why| {
+                Error::io(format!("Could not read '{}'", link_log.display()), why)
+            })?;
+            parse_link_log(root, &contents, &sources, &dirs)
+        }
+        None => Vec::new(),
+    };
+
+    let mut toolchain = ToolchainConfig::default();
+    let c_compiler = sources
+        .iter()
+        .find(|s| !is_cpp(&s.file))
+        .or(sources.first())
+        .map(|s| s.compiler.clone())
+        .unwrap_or_default();
+    if let Some(cpp) = sources.iter().find(|s| is_cpp(&s.file)) {
+        toolchain.cxx = cpp.compiler.clone();
+    }
+    if let Some(ar) = links.iter().find(|link| link.typ == "static") {
+        toolchain.ar = ar.tool.clone();
+    }
+
+    let mut targets: Vec<TargetConfig> = Vec::new();
+    let mut used = vec![false; sources.len()];
+    for link in &links {
+        let members: Vec<usize> = (0..sources.len())
+            .filter(|&i| link.inputs.contains(&sources[i].object))
+            .collect();
+        members.iter().for_each(|&i| used[i] = true);
+        let mut deps: Vec<String> = links
+            .iter()
+            .filter(|other| {
+                other.output != link.output
+                    && (link.inputs.contains(&other.output)
+                        || link
+                            .libs
+                            .iter()
+                            .any(|lib| other.name == format!("lib{}", lib)))
+            })
+            .map(|other| other.name.clone())
+            .collect();
+        deps.dedup();
+        let mut target = TargetConfig::new(&link.name, &link.typ);
+        target.deps = deps;
+        target.ldflags = link.flags.join(" ");
+        if link.typ == "static" && link.tool != toolchain.ar {
+            target.archive = link.tool.clone();
+        } else if link.typ != "static" && link.tool != c_compiler {
+            target.linker = link.tool.clone();
+        }
+        add_target(&mut targets, target, &sources, &members);
+    }
+    let unlinked: Vec<usize> = (0..sources.len()).filter(|&i| !used[i]).collect();
+    if links.is_empty() {
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "app".to_string());
+        add_target(
+            &mut targets,
+            TargetConfig::new(&name, "exe"),
+            &sources,
+            &unlinked,
+        );
+    } else if !unlinked.is_empty() {
+        let files: Vec<&str> = unlinked.iter().map(|&i| sources[i].file.as_str()).collect();
+        log(
+            LogLevel::Warn,
+            &format!(
+                "Sources not linked by any command of the link log are left out: {}",
+                files.join(", ")
+            ),
+        );
+    }
+
+    targets
+        .into_iter()
+        .fold(
+            Project::builder()
+                .compiler(&c_compiler)
+                .toolchain(toolchain),
+            |builder, target| builder.target(target),
+        )
+        .finish()
+}
+
+/// Adds a target with the given sources
+/// # Notes
+/// The sources are grouped by their exact flags, as a target has no per-source flags.
+/// The flags of the largest group become the flags of the target, every other group is
+/// split into an `object` target the target depends on, and which it links or archives.
+fn add_target(
+    targets: &mut Vec<TargetConfig>,
+    mut target: TargetConfig,
+    sources: &[Source],
+    members: &[usize],
+) {
+    // Groups the sources by their exact flags, largest group first
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for &i in members {
+        let same = |group: &Vec<usize>| {
+            let first = &sources[group[0]];
+            first.flags == sources[i].flags && first.includes == sources[i].includes
+        };
+        match groups.iter_mut().find(|group| same(group)) {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+    groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+    let Some(main) = groups.first() else {
+        targets.push(target);
+        return;
+    };
+    let configure = |target: &mut TargetConfig, group: &[usize]| {
+        let first = &sources[group[0]];
+        target.cflags = first.flags.join(" ");
+        target.include_dir = first.includes.clone();
+        target.files = group.iter().map(|&i| sources[i].file.clone()).collect();
+    };
+    configure(&mut target, main);
+
+    for (n, group) in groups.iter().enumerate().skip(1) {
+        let mut part = TargetConfig::new(&format!("{}_{}", target.name, n), "object");
+        part.ldflags = "-r -nostdlib".to_string();
+        configure(&mut part, group);
+        target.deps.push(part.name.clone());
+        targets.push(part);
+    }
+    targets.push(target);
+}
+
+/// Parses an entry of the compilation database
+fn parse_entry(root: &Path, entry: Entry) -> Result<Source> {
+    let directory = Path::new(&entry.directory);
+    let argv = match entry.arguments.is_empty() {
+        true => shell_split(&entry.command),
+        false => entry.arguments,
+    };
+    let Some((compiler, args)) = argv.split_first() else {
+        return Err(Error::Config(format!(
+            "Empty command for '{}' in the compilation database",
+            entry.file
+        )));
+    };
+    let file = normalize(&directory.join(&entry.file));
+    let mut object = entry.output.clone();
+    let mut flags = Vec::new();
+    let mut includes = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-c" || DEPFILE_FLAGS.contains(&arg.as_str()) {
+            continue;
+        }
+        if arg == "-o" || DEPFILE_VALUE_FLAGS.contains(&arg.as_str()) {
+            let value = args.next().cloned().unwrap_or_default();
+            if arg == "-o" {
+                object = value;
+            }
+            continue;
+        }
+        if normalize(&directory.join(arg)) == file {
+            continue;
+        }
+        // Splits the flag from its value, attached or as the next argument
+        let flag = FLAGS_WITH_VALUE
+            .iter()
+            .find(|flag| arg.starts_with(**flag))
+            .filter(|flag| arg.len() > flag.len() || args.len() > 0);
+        let Some(flag) = flag else {
+            flags.push(shell_quote(arg));
+            continue;
+        };
+        let mut value = arg[flag.len()..].to_string();
+        if value.is_empty() {
+            value = args.next().cloned().unwrap_or_default();
+        }
+        if PATH_FLAGS.contains(flag) {
+            value = relative_path(root, &directory.join(&value));
+        }
+        match *flag {
+            "-I" => includes.push(value),
+            "-D" | "-U" => flags.push(shell_quote(&format!("{}{}", flag, value))),
+            _ => flags.push(format!("{} {}", flag, shell_quote(&value))),
+        }
+    }
+    if object.is_empty() {
+        return Err(Error::Config(format!(
+            "No output for '{}' in the compilation database",
+            entry.file
+        )));
+    }
+    Ok(Source {
+        file: relative_path(root, &file),
+        object: normalize(&directory.join(object)),
+        compiler: compiler.clone(),
+        flags,
+        includes,
+    })
+}
+
+/// Parses the link commands of a link log
+/// # Notes
+/// Lines that link none of the objects of the compilation database are ignored, so a
+/// complete build log can be used. Relative paths are resolved against the project
+/// root or the directory of an object, whichever names a known object.
+fn parse_link_log(root: &Path, contents: &str, sources: &[Source], dirs: &[PathBuf]) -> Vec<Link> {
+    let objects: Vec<&PathBuf> = sources.iter().map(|s| &s.object).collect();
+    let mut links: Vec<Link> = Vec::new();
+    for line in contents.replace("\\\n", " ").lines() {
+        let argv = shell_split(line);
+        let Some((tool, args)) = argv.split_first() else {
+            continue;
+        };
+        // The directory the command ran in is the one its objects are found from
+        let Some(base) = std::iter::once(root.to_path_buf())
+            .chain(dirs.iter().cloned())
+            .find(|dir| {
+                args.iter()
+                    .any(|arg| objects.contains(&&normalize(&dir.join(arg))))
+            })
+        else {
+            continue;
+        };
+        let resolve = |arg: &str| normalize(&base.join(arg));
+        let is_ar = Path::new(tool)
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().ends_with("ar"));
+        let mut link = Link {
+            name: String::new(),
+            typ: String::new(),
+            tool: tool.clone(),
+            output: PathBuf::new(),
+            inputs: Vec::new(),
+            libs: Vec::new(),
+            flags: Vec::new(),
+        };
+        if is_ar {
+            // ar <operations> <archive> <members>...
+            let Some((ops, rest)) = args.split_first() else {
+                continue;
+            };
+            let Some((output, members)) = rest.split_first() else {
+                continue;
+            };
+            link.typ = "static".to_string();
+            link.flags.push(ops.clone());
+            link.output = resolve(output);
+            link.inputs = members.iter().map(|m| resolve(m)).collect();
+        } else {
+            let mut args = args.iter();
+            let mut typ = "exe";
+            while let Some(arg) = args.next() {
+                if arg == "-o" {
+                    link.output = resolve(args.next().map(|s| s.as_str()).unwrap_or_default());
+                } else if arg == "-shared" {
+                    typ = "dll";
+                } else if arg == "-r" {
+                    typ = "object";
+                } else if let Some(lib) = arg.strip_prefix("-l") {
+                    link.libs.push(lib.to_string());
+                    link.flags.push(shell_quote(arg));
+                } else if !arg.starts_with('-') && Path::new(arg).extension().is_some() {
+                    link.inputs.push(resolve(arg));
+                } else {
+                    link.flags.push(shell_quote(arg));
+                }
+            }
+            if link.output.as_os_str().is_empty() {
+                continue;
+            }
+            link.typ = typ.to_string();
+        }
+        if link.typ == "object" {
+            link.flags.insert(0, "-r".to_string());
+        }
+        link.name = target_name(&link.output, &link.typ);
+        while links.iter().any(|other| other.name == link.name) {
+            link.name.push('_');
+        }
+        links.push(link);
+    }
+    // The libraries built by the log are linked as deps, the others stay in the flags
+    let built_libs: Vec<String> = links
+        .iter()
+        .filter_map(|link| link.name.strip_prefix("lib"))
+        .map(|lib| format!("-l{}", lib))
+        .collect();
+    let outputs: Vec<PathBuf> = links.iter().map(|link| link.output.clone()).collect();
+    for link in &mut links {
+        link.flags.retain(|flag| !built_libs.contains(flag));
+        let external: Vec<String> = link
+            .inputs
+            .iter()
+            .filter(|input| !objects.contains(input) && !outputs.contains(input))
+            .map(|input| shell_quote(&relative_path(root, input)))
+            .collect();
+        link.flags.extend(external);
+    }
+    links
+}
+
+/// Returns the target name of a linked output
+fn target_name(output: &Path, typ: &str) -> String {
+    let name = output
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match typ {
+        "exe" => name,
+        _ => {
+            let stem = name.split('.').next().unwrap_or_default().to_string();
+            // ruxgo links dll deps with -l, which needs the lib prefix
+            if typ == "dll" && !stem.starts_with("lib") {
+                format!("lib{}", stem)
+            } else {
+                stem
+            }
+        }
+    }
+}
+
+/// Returns whether a source is compiled as C++
+fn is_cpp(file: &str) -> bool {
+    matches!(
+        Path::new(file).extension().and_then(|ext| ext.to_str()),
+        Some("cpp") | Some("cc") | Some("cxx")
+    )
+}
+
+/// Returns a path relative to the project root, or absolute if it is outside of it
+fn relative_path(root: &Path, path: &Path) -> String {
+    let path = normalize(&root.join(path));
+    match path.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => relative.to_string_lossy().to_string(),
+        Err(_) => path.to_string_lossy().to_string(),
+    }
+}
+
+/// Removes the `.` and `..` components of a path without resolving links
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Splits a command line like the shell, without expanding anything
+fn shell_split(command: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut in_arg = false;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_arg = true;
+                chars
+                    .by_ref()
+                    .take_while(|&c| c != '\'')
+                    .for_each(|c| arg.push(c));
+            }
+            '"' => {
+                in_arg = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(next @ ('"' | '\\' | '$' | '`')) => arg.push(next),
+                            Some(next) => {
+                                arg.push('\\');
+                                arg.push(next);
+                            }
+                            None => arg.push('\\'),
+                        },
+                        c => arg.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                if let Some(next) = chars.next() {
+                    arg.push(next);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            c => {
+                in_arg = true;
+                arg.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(arg);
+    }
+    args
+}
+
+/// Quotes an argument for the shell, if needed
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c));
+    match plain {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', "'\\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir;
+
+    fn entry(directory: &str, file: &str, arguments: &[&str]) -> Entry {
+        Entry {
+            directory: directory.to_string(),
+            file: file.to_string(),
+            arguments: arguments.iter().map(|arg| arg.to_string()).collect(),
+            command: String::new(),
+            output: String::new(),
+        }
+    }
+
+    fn source(file: &str, object: &str, flags: &[&str], includes: &[&str]) -> Source {
+        Source {
+            file: file.to_string(),
+            object: PathBuf::from(object),
+            compiler: "gcc".to_string(),
+            flags: flags.iter().map(|flag| flag.to_string()).collect(),
+            includes: includes.iter().map(|dir| dir.to_string()).collect(),
+        }
+    }
+
+    fn target<'a>(targets: &'a [TargetConfig], name: &str) -> &'a TargetConfig {
+        targets
+            .iter()
+            .find(|target| target.name == name)
+            .unwrap_or_else(|| panic!("no target '{}'", name))
+    }
+
+    #[test]
+    fn shell_split_handles_quotes_and_escapes() {
+        assert_eq!(
+            shell_split(r#"gcc  -DNAME="a b" 'c d' e\ f "x\"y\n" '' -c"#),
+            vec!["gcc", "-DNAME=a b", "c d", "e f", "x\"y\\n", "", "-c"]
+        );
+        assert!(shell_split("   ").is_empty());
+    }
+
+    #[test]
+    fn shell_quote_round_trips() {
+        assert_eq!(shell_quote("-DX=1"), "-DX=1");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote(""), "''");
+        for arg in ["it's", "a b", "$HOME", "-DS=\"x\""] {
+            assert_eq!(shell_split(&shell_quote(arg)), vec![arg]);
+        }
+    }
+
+    #[test]
+    fn normalize_and_relative_path() {
+        assert_eq!(normalize(Path::new("/a/./b/../c")), PathBuf::from("/a/c"));
+        let root = Path::new("/p");
+        assert_eq!(relative_path(root, Path::new("/p/src/a.c")), "src/a.c");
+        assert_eq!(relative_path(root, Path::new("src/../inc")), "inc");
+        assert_eq!(relative_path(root, Path::new("/p/build/..")), ".");
+        assert_eq!(
+            relative_path(root, Path::new("/usr/include")),
+            "/usr/include"
+        );
+    }
+
+    #[test]
+    fn target_name_of_outputs() {
+        assert_eq!(target_name(Path::new("/b/app"), "exe"), "app");
+        assert_eq!(target_name(Path::new("/b/libfoo.a"), "static"), "libfoo");
+        assert_eq!(target_name(Path::new("/b/foo.so"), "dll"), "libfoo");
+        assert_eq!(target_name(Path::new("/b/libbar.so.1"), "dll"), "libbar");
+        assert_eq!(target_name(Path::new("/b/part.o"), "object"), "part");
+    }
+
+    #[test]
+    fn parse_entry_splits_flags() {
+        let root = Path::new("/p");
+        let source = parse_entry(
+            root,
+            entry(
+                "/p/build",
+                "../src/a.c",
+                &[
+                    "gcc",
+                    "-c",
+                    "-MD",
+                    "-MF",
+                    "a.d",
+                    "-I../inc",
+                    "-I",
+                    "/usr/x",
+                    "-isystem",
+                    "../sys",
+                    "-DX=1",
+                    "-D",
+                    "Y",
+                    "-DS=a b",
+                    "-O2",
+                    "-o",
+                    "a.o",
+                    "../src/a.c",
+                ],
+            ),
+        )
+        .unwrap();
+        assert_eq!(source.file, "src/a.c");
+        assert_eq!(source.object, PathBuf::from("/p/build/a.o"));
+        assert_eq!(source.compiler, "gcc");
+        assert_eq!(source.includes, vec!["inc", "/usr/x"]);
+        assert_eq!(
+            source.flags,
+            vec!["-isystem sys", "-DX=1", "-DY", "'-DS=a b'", "-O2"]
+        );
+    }
+
+    #[test]
+    fn parse_entry_uses_command_and_output() {
+        let mut e = entry("/p", "src/a.c", &[]);
+        e.command = "cc -c 'src/a.c' -Wall".to_string();
+        e.output = "obj/a.o".to_string();
+        let source = parse_entry(Path::new("/p"), e).unwrap();
+        assert_eq!(source.compiler, "cc");
+        assert_eq!(source.object, PathBuf::from("/p/obj/a.o"));
+        assert_eq!(source.flags, vec!["-Wall"]);
+
+        let err = parse_entry(Path::new("/p"), entry("/p", "a.c", &["gcc", "-c", "a.c"]));
+        assert!(matches!(err, Err(Error::Config(msg)) if msg.contains("No output for 'a.c'")));
+        let err = parse_entry(Path::new("/p"), entry("/p", "a.c", &[]));
+        assert!(matches!(err, Err(Error::Config(msg)) if msg.contains("Empty command")));
+    }
+
+    #[test]
+    fn parse_link_log_groups_commands() {
+        let root = Path::new("/p");
+        let sources = [
+            source("src/a.c", "/p/build/a.o", &[], &[]),
+            source("src/b.c", "/p/build/b.o", &[], &[]),
+            source("src/m.c", "/p/build/m.o", &[], &[]),
+        ];
+        let dirs = vec![PathBuf::from("/p/build")];
+        let log = "ar rcs build/libfoo.a build/a.o build/b.o\n\
+                   echo done\n\
+                   gcc -o build/app build/m.o -Lbuild -lfoo -lm \\\n  extra/x.o\n\
+                   gcc -shared -o bar.so a.o\n";
+        let links = parse_link_log(root, log, &sources, &dirs);
+        assert_eq!(links.len(), 3);
+
+        assert_eq!(links[0].name, "libfoo");
+        assert_eq!(links[0].typ, "static");
+        assert_eq!(links[0].tool, "ar");
+        assert_eq!(links[0].flags, vec!["rcs"]);
+        assert_eq!(links[0].output, PathBuf::from("/p/build/libfoo.a"));
+        let inputs = [PathBuf::from("/p/build/a.o"), PathBuf::from("/p/build/b.o")];
+        assert_eq!(links[0].inputs, inputs);
+
+        // The built library becomes a dep, the external object stays a flag
+        assert_eq!(links[1].name, "app");
+        assert_eq!(links[1].typ, "exe");
+        assert_eq!(links[1].libs, vec!["foo", "m"]);
+        assert_eq!(links[1].flags, vec!["-Lbuild", "-lm", "extra/x.o"]);
+
+        // Objects not found from the root are found from their directory
+        assert_eq!(links[2].name, "libbar");
+        assert_eq!(links[2].typ, "dll");
+        assert_eq!(links[2].output, PathBuf::from("/p/build/bar.so"));
+        assert_eq!(links[2].inputs, [PathBuf::from("/p/build/a.o")]);
+    }
+
+    #[test]
+    fn add_target_splits_sources_by_flags() {
+        let sources = [
+            source("a.c", "/p/a.o", &["-O2"], &["inc"]),
+            source("b.c", "/p/b.o", &["-O0"], &["inc"]),
+            source("c.c", "/p/c.o", &["-O2"], &["inc"]),
+        ];
+        let mut targets = Vec::new();
+        add_target(
+            &mut targets,
+            TargetConfig::new("app", "exe"),
+            &sources,
+            &[0, 1, 2],
+        );
+        assert_eq!(targets.len(), 2);
+        let part = target(&targets, "app_1");
+        assert_eq!(part.typ, "object");
+        assert_eq!(part.files, vec!["b.c"]);
+        assert_eq!(part.cflags, "-O0");
+        let app = target(&targets, "app");
+        assert_eq!(app.files, vec!["a.c", "c.c"]);
+        assert_eq!(app.cflags, "-O2");
+        assert_eq!(app.include_dir, vec!["inc"]);
+        assert_eq!(app.deps, vec!["app_1"]);
+
+        // Libraries are split alike, they archive or link the objects
+        let mut targets = Vec::new();
+        add_target(
+            &mut targets,
+            TargetConfig::new("libfoo", "static"),
+            &sources,
+            &[1, 0, 2],
+        );
+        assert_eq!(targets.len(), 2);
+        assert_eq!(target(&targets, "libfoo_1").files, vec!["b.c"]);
+        let lib = target(&targets, "libfoo");
+        assert_eq!(lib.files, vec!["a.c", "c.c"]);
+        assert_eq!(lib.cflags, "-O2");
+        assert_eq!(lib.deps, vec!["libfoo_1"]);
+    }
+
+    #[test]
+    fn import_compile_commands() {
+        let tmp = test_dir("import-project");
+        let dir = tmp.path();
+        let root = dir.to_string_lossy();
+        let database = dir.join("compile_commands.json");
+        fs::write(
+            &database,
+            format!(
+                r#"[
+                    {{"directory": "{root}", "file": "src/a.c",
+                      "command": "gcc -Iinc -c src/a.c -o build/a.o"}},
+                    {{"directory": "{root}", "file": "src/m.c",
+                      "arguments": ["gcc", "-Iinc", "-c", "src/m.c", "-o", "build/m.o"]}},
+                    {{"directory": "{root}", "file": "src/m.c",
+                      "arguments": ["gcc", "-Iinc", "-c", "src/m.c", "-o", "build/m.o"]}}
+                ]"#
+            ),
+        )
+        .unwrap();
+
+        // Without a link log, the sources form an executable named after the directory
+        let project = import_in(dir, &database, None).unwrap();
+        let name = dir.file_name().unwrap().to_string_lossy();
+        assert_eq!(project.targets.len(), 1);
+        let exe = target(&project.targets, &name);
+        assert_eq!(exe.typ, "exe");
+        assert_eq!(exe.files, vec!["src/a.c", "src/m.c"]);
+        assert_eq!(exe.include_dir, vec!["inc"]);
+
+        let link_log = dir.join("link.log");
+        fs::write(
+            &link_log,
+            "ar rcs build/liba.a build/a.o\ngcc -o build/app build/m.o -Lbuild -la\n",
+        )
+        .unwrap();
+        let project = import_in(dir, &database, Some(&link_log)).unwrap();
+        assert_eq!(project.targets.len(), 2);
+        let lib = target(&project.targets, "liba");
+        assert_eq!(lib.typ, "static");
+        assert_eq!(lib.files, vec!["src/a.c"]);
+        let app = target(&project.targets, "app");
+        assert_eq!(app.files, vec!["src/m.c"]);
+        assert_eq!(app.deps, vec!["liba"]);
+        assert_eq!(app.ldflags, "-Lbuild");
+    }
+
+    #[test]
+    fn import_rejects_invalid_databases() {
+        let tmp = test_dir("import-invalid");
+        let dir = tmp.path();
+        let database = dir.join("compile_commands.json");
+        fs::write(&database, "[]").unwrap();
+        let err = import_in(dir, &database, None);
+        assert!(matches!(err, Err(Error::Config(msg)) if msg.contains("No sources found")));
+        fs::write(&database, "{").unwrap();
+        let err = import_in(dir, &database, None);
+        assert!(matches!(err, Err(Error::Config(msg)) if msg.contains("Invalid compilation")));
+    }
+}
@@ -3,23 +3,52 @@
 //! This library automatically configures various targets in your project
 //! and gives an easy interface to grab packages from github.
 //!
-//! The library uses config_linux.toml or config_win32.toml file to configure the project.
+//! The library uses a ruxgo.toml (or the older config_linux.toml or config_win32.toml) file
+//! to configure the project.
 //!
 
 /// Contains code to build projects
 pub mod builder;
 /// Contains code that handles various CLI flags
 pub mod commands;
+/// Contains the compilation database generation
+pub mod compdb;
+/// Contains the error type returned by the library
+pub mod error;
+/// Contains the export of the build to other build systems
+pub mod export;
 /// Handles global config
 pub mod global_cfg;
+/// Contains the dependency graph of the targets
+pub mod graph;
 /// Contains hashing related functions
 pub mod hasher;
+/// Contains the import of existing builds into a config
+pub mod import;
+/// Contains the resolved project metadata printed for tooling
+pub mod metadata;
 /// Contains packages management related functions
 pub mod packages;
 /// Contains parse related functions
 pub mod parser;
+/// Contains patch queue related functions
+pub mod patches;
+/// Contains the programmatic project API
+pub mod project;
+/// Contains the reports returned by builds, runs and cleans
+pub mod report;
+/// Contains the config schema used to validate config files
+pub mod schema;
+/// Contains source fetching related functions
+pub mod sources;
+/// Contains custom build step related functions
+pub mod steps;
 /// Contains logger, config parser and environment config
 pub mod utils;
+/// Contains the VS Code workspace generation
+pub mod vscode;
+
+pub use error::{Error, Result};
 
 #[macro_use]
 extern crate lazy_static;
@@ -1,13 +1,25 @@
 use clap::{Parser, Subcommand};
+use colored::Colorize;
 use dialoguer::MultiSelect;
 use directories::ProjectDirs;
 use ruxgo::commands;
+use ruxgo::compdb;
+use ruxgo::export;
 use ruxgo::global_cfg::GlobalConfig;
+use ruxgo::graph;
+use ruxgo::import;
+use ruxgo::metadata;
 use ruxgo::packages;
-use ruxgo::parser::OSConfig;
-use ruxgo::utils::log::{log, LogLevel};
+use ruxgo::parser::{BuildConfig, OSConfig, TargetConfig};
+use ruxgo::report::PatchStatus;
+use ruxgo::schema;
+use ruxgo::sources;
+use ruxgo::utils::log::{log, log_to_stderr, LogLevel};
+use ruxgo::vscode;
+use ruxgo::{Error, Result};
 use std::env;
-use std::path::PathBuf;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -36,9 +48,21 @@ struct CLIArgs {
     /// Generate compile_commands.json
     #[arg(long)]
     gen_cc: bool,
-    /// Generate .vscode/c_cpp_properties.json
+    /// Generate the VS Code c_cpp_properties.json, tasks.json and launch.json
     #[arg(long)]
     gen_vsc: bool,
+    /// Directory for all build outputs, overrides `[build] out_dir`
+    #[arg(long, num_args(1), value_name = "DIR")]
+    build_dir: Option<String>,
+    /// Config file or directory, defaults to `ruxgo.toml` in the current or a parent directory
+    #[arg(long, num_args(1), value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Select the `ruxgo.<VARIANT>.toml` config
+    #[arg(long, num_args(1), value_name = "VARIANT")]
+    variant: Option<String>,
+    /// Only use cached sources, never download
+    #[arg(long)]
+    offline: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -77,30 +101,179 @@ enum Commands {
         #[arg(long)]
         clean_all: bool,
     },
+    /// Patch queue management
+    #[clap(name = "patch", arg_required_else_help = true)]
+    Patch {
+        /// Show which patches are applied, changed or pending
+        #[arg(long)]
+        status: bool,
+        /// Revert all applied patches
+        #[arg(long, conflicts_with = "status")]
+        revert: bool,
+    },
+    /// Print the fully resolved configuration for tooling
+    Metadata {
+        /// The output format
+        #[arg(long, default_value = "json", value_parser = ["json"])]
+        format: String,
+    },
+    /// Generate compile_commands.json without building
+    GenCc,
+    /// Export the build to a standalone build file
+    Export {
+        /// The build system to export to
+        #[arg(long, value_parser = ["ninja", "make", "cmake"])]
+        format: String,
+        /// The file to write, prints to stdout if not given
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Create a config that rebuilds an existing build
+    Import {
+        /// The compile_commands.json of the existing build
+        #[arg(long, value_name = "FILE")]
+        from: PathBuf,
+        /// A file with the link commands of the existing build, one per line
+        #[arg(long, value_name = "FILE")]
+        link_log: Option<PathBuf>,
+        /// The config file to write
+        #[arg(short, long, value_name = "FILE", default_value = "config_linux.toml")]
+        output: PathBuf,
+    },
+    /// Print the dependency tree of the targets
+    Tree,
+    /// Print the dependency graph of the targets
+    Graph {
+        /// The output format
+        #[arg(long, default_value = "dot", value_parser = ["dot"])]
+        format: String,
+        /// Also show the sources of each target and the headers they include
+        #[arg(long)]
+        headers: bool,
+    },
     /// Configuration settings
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
         /// Parameter to set currently supported parameters:
         ///     - `default_compiler`: Sets the default compiler to use
         ///     - `default_language`: Sets the default language to use
         ///     - `license`: Sets the license to use. Give the path to the license file
         #[clap(verbatim_doc_comment)]
-        parameter: String,
+        #[arg(required = true)]
+        parameter: Option<String>,
         /// Value to set the parameter to currently supported values:
         ///     - `compiler`: `gcc`, `clang` Uses g++ or clang++ respectively
         ///     - `language`: `c`, `cpp`
         ///     - `license`: `path/to/license/file`
         #[clap(verbatim_doc_comment)]
-        value: String,
+        #[arg(required = true)]
+        value: Option<String>,
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Print the JSON Schema of ruxgo.toml
+    Schema,
+}
+
 #[tokio::main]
 async fn main() {
+    // Parse args
+    let args = CLIArgs::parse();
+
+    if let Err(error) = run(args).await {
+        for line in error.to_string().lines() {
+            log(LogLevel::Error, line);
+        }
+        std::process::exit(exit_code(&error));
+    }
+}
+
+/// Returns the exit code of an error, so that scripts can tell failures apart
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Config(_) => 2,
+        Error::Toolchain(_) => 3,
+        Error::Compile(_) => 4,
+        Error::Link(_) => 5,
+        Error::Io { .. } => 6,
+        Error::OsBuild(_) => 7,
+        Error::Package(_) => 8,
+        Error::Patch(_) => 9,
+        Error::Step(_) => 10,
+        Error::Run(_) => 11,
+    }
+}
+
+/// Converts the error of a package command
+fn package_error(error: Box<dyn std::error::Error>) -> Error {
+    Error::Package(error.to_string())
+}
+
+/// Parses the config, with `--offline` applied to every command resolving sources
+fn parse_config(
+    config_file: &str,
+    build_dir: Option<&str>,
+    offline: bool,
+) -> Result<(BuildConfig, OSConfig, Vec<TargetConfig>)> {
+    let (mut build_config, os_config, targets) = commands::parse_config(config_file, build_dir)?;
+    build_config.offline = offline;
+    Ok((build_config, os_config, targets))
+}
+
+/// Prints the output of a command to stdout
+/// # Arguments
+/// * `output` - The output to print
+/// * `what` - What is printed, for the error message
+fn print_output(output: &str, what: &str) -> Result<()> {
+    // Output piped into e.g. `head` may be closed early
+    match std::io::stdout().write_all(output.as_bytes()) {
+        Err(why) if why.kind() != ErrorKind::BrokenPipe => {
+            Err(Error::io(format!("Could not print {}", what), why))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Locates the config and changes to the project root holding it
+/// Returns the file name of the config, relative to the project root.
+fn enter_project(config: Option<&Path>, variant: Option<&str>) -> Result<String> {
+    let (project_dir, config_file) = commands::locate_config(config, variant)?;
+    env::set_current_dir(&project_dir).map_err(|why| {
+        Error::io(
+            format!(
+                "Could not change to project directory '{}'",
+                project_dir.display()
+            ),
+            why,
+        )
+    })?;
+    Ok(config_file)
+}
+
+/// Runs the command given on the command line
+async fn run(mut args: CLIArgs) -> Result<()> {
+    // Paths given on the command line are relative to the invocation directory, which
+    // is left for `--path` and the project root
+    let invocation_dir =
+        env::current_dir().map_err(|e| Error::io("Could not get the current directory", e))?;
+    args.build_dir = args.build_dir.map(|build_dir| {
+        invocation_dir
+            .join(build_dir)
+            .to_string_lossy()
+            .into_owned()
+    });
+    args.config = args.config.map(|config| invocation_dir.join(config));
+
     // Add global config
     let project_dirs = ProjectDirs::from("com", "RuxosApps", "ruxos-c").unwrap();
     let config_dir = project_dirs.config_dir();
     if !config_dir.exists() {
-        std::fs::create_dir_all(config_dir).unwrap();
+        std::fs::create_dir_all(config_dir)
+            .map_err(|e| Error::io("Could not create the global config directory", e))?;
     }
     let config = config_dir.join("config.toml");
     if !config.exists() {
@@ -112,12 +285,9 @@ default_language = "cpp"
 license = "NONE"
 "#,
         )
-        .unwrap();
+        .map_err(|e| Error::io("Could not write the global config", e))?;
     }
-    let global_config = GlobalConfig::from_file(&config);
-
-    // Parse args
-    let args = CLIArgs::parse();
+    let global_config = GlobalConfig::from_file(&config)?;
 
     if let Some(ref path_buf) = args.path {
         if let Err(e) = env::set_current_dir(path_buf) {
@@ -125,54 +295,45 @@ license = "NONE"
         }
     }
 
-    if args.commands.is_some() {
-        match args.commands {
-            Some(Commands::Init { name, c, cpp }) => {
+    if let Some(command) = args.commands.take() {
+        match command {
+            Commands::Init { name, c, cpp } => {
                 if c && cpp {
-                    log(LogLevel::Error, "Only one of --c or --cpp can be specified");
-                    std::process::exit(1);
-                }
-                if !c && !cpp {
-                    commands::init_project(&name, None, &global_config);
-                    std::process::exit(0);
-                }
-
-                if c {
-                    commands::init_project(&name, Some(true), &global_config);
-                } else {
-                    commands::init_project(&name, Some(false), &global_config);
+                    return Err(Error::Config(
+                        "Only one of --c or --cpp can be specified".to_string(),
+                    ));
                 }
+                let is_c = (c || cpp).then_some(c);
+                commands::init_project(&name, is_c, &global_config)?;
             }
-            Some(Commands::Pkg {
+            Commands::Pkg {
                 list,
                 pull,
                 run,
                 update,
                 clean,
                 clean_all,
-            }) => {
+            } => {
                 if list {
-                    packages::list_packages()
-                        .await
-                        .expect("Failed to list packages");
+                    packages::list_packages().await.map_err(package_error)?;
                 }
                 if let Some(pkg_name) = pull {
                     packages::pull_packages(&pkg_name)
                         .await
-                        .expect("Failed to pull package");
+                        .map_err(package_error)?;
                 }
                 if let Some(app_name) = run {
-                    packages::run_app(&app_name).expect("Failed to run app-bin");
+                    packages::run_app(&app_name).map_err(package_error)?;
                 }
                 if let Some(pkg_name) = update {
                     packages::update_package(&pkg_name)
                         .await
-                        .expect("Failed to update package");
+                        .map_err(package_error)?;
                 }
                 if let Some(pkg_name) = clean {
                     packages::clean_package(&pkg_name)
                         .await
-                        .expect("Failed to clean package");
+                        .map_err(package_error)?;
                 }
                 if clean_all {
                     let items = vec!["All", "App-bin", "App-src", "Kernel", "Cache"];
@@ -190,43 +351,179 @@ license = "NONE"
                     log(LogLevel::Log, "Cleaning packages...");
                     packages::clean_all_packages(choices)
                         .await
-                        .expect("Failed to clean choice packages");
+                        .map_err(package_error)?;
+                }
+            }
+            Commands::Patch { status, revert } => {
+                let config_file = enter_project(args.config.as_deref(), args.variant.as_deref())?;
+                let (build_config, _, targets) =
+                    parse_config(&config_file, args.build_dir.as_deref(), args.offline)?;
+                if revert {
+                    return commands::revert_patches(&build_config, &targets);
                 }
+                if !status {
+                    return Ok(());
+                }
+                let mut output = String::new();
+                for report in commands::patch_status(&build_config, &targets)? {
+                    output.push_str(&format!("{}\n", report.dir.bold()));
+                    for (patch, status) in report.patches {
+                        let status = match status {
+                            PatchStatus::Applied => "applied".green(),
+                            PatchStatus::Changed => "changed".yellow(),
+                            PatchStatus::Pending => "pending".cyan(),
+                            PatchStatus::Stale => "stale".red(),
+                        };
+                        output.push_str(&format!("    {:<8} {}\n", status, patch));
+                    }
+                }
+                return print_output(&output, "the patch status");
+            }
+            Commands::Metadata { format: _ } => {
+                // Keep stdout for the metadata only
+                log_to_stderr();
+                let config_file = enter_project(args.config.as_deref(), args.variant.as_deref())?;
+                let (build_config, os_config, targets) =
+                    parse_config(&config_file, args.build_dir.as_deref(), args.offline)?;
+                // The sources of the targets may live in fetched archives
+                sources::fetch_sources(&build_config)?;
+                let metadata = metadata::resolve(&build_config, &os_config, &targets)?;
+                let metadata = serde_json::to_string_pretty(&metadata)
+                    .map_err(|why| Error::io("Could not serialize the metadata", why.into()))?;
+                return print_output(&format!("{}\n", metadata), "the metadata");
             }
-            Some(Commands::Config { parameter, value }) => {
+            Commands::GenCc => {
+                let config_file = enter_project(args.config.as_deref(), args.variant.as_deref())?;
+                let (build_config, os_config, targets) =
+                    parse_config(&config_file, args.build_dir.as_deref(), args.offline)?;
+                // The sources of the targets may live in fetched archives
+                sources::fetch_sources(&build_config)?;
+                compdb::write(&build_config, &os_config, &targets)?;
+                return Ok(());
+            }
+            Commands::Export { format, output } => {
+                // Keep stdout for the build file only
+                log_to_stderr();
+                let config_file = enter_project(args.config.as_deref(), args.variant.as_deref())?;
+                let (build_config, os_config, targets) =
+                    parse_config(&config_file, args.build_dir.as_deref(), args.offline)?;
+                // The sources of the targets may live in fetched archives
+                sources::fetch_sources(&build_config)?;
+                let plan = export::plan(&build_config, &os_config, &targets, &config_file)?;
+                let contents = export::export(&plan, &format)?;
+                match output {
+                    Some(output) => {
+                        std::fs::write(&output, contents).map_err(|why| {
+                            Error::io(format!("Could not write '{}'", output.display()), why)
+                        })?;
+                        log(
+                            LogLevel::Log,
+                            &format!("Exported {} to {}", format, output.display()),
+                        );
+                    }
+                    None => print!("{}", contents),
+                }
+                return Ok(());
+            }
+            Commands::Import {
+                from,
+                link_log,
+                output,
+            } => {
+                if output.exists() {
+                    return Err(Error::Config(format!(
+                        "'{}' already exists",
+                        output.display()
+                    )));
+                }
+                let project = import::import(&from, link_log.as_deref())?;
+                std::fs::write(&output, project.to_toml()?).map_err(|why| {
+                    Error::io(format!("Could not write '{}'", output.display()), why)
+                })?;
+                log(
+                    LogLevel::Log,
+                    &format!(
+                        "Imported {} targets into {}",
+                        project.targets.len(),
+                        output.display()
+                    ),
+                );
+                return Ok(());
+            }
+            Commands::Tree => {
+                log_to_stderr();
+                let config_file = enter_project(args.config.as_deref(), args.variant.as_deref())?;
+                let (build_config, _, targets) =
+                    parse_config(&config_file, args.build_dir.as_deref(), args.offline)?;
+                print!("{}", graph::tree(&build_config, &targets));
+                return Ok(());
+            }
+            Commands::Graph { format: _, headers } => {
+                log_to_stderr();
+                let config_file = enter_project(args.config.as_deref(), args.variant.as_deref())?;
+                let (build_config, os_config, targets) =
+                    parse_config(&config_file, args.build_dir.as_deref(), args.offline)?;
+                if headers {
+                    sources::fetch_sources(&build_config)?;
+                }
+                print!(
+                    "{}",
+                    graph::dot(&build_config, &os_config, &targets, headers)?
+                );
+                return Ok(());
+            }
+            Commands::Config {
+                command: Some(ConfigCommands::Schema),
+                ..
+            } => {
+                let schema =
+                    serde_json::to_string_pretty(&schema::json_schema()).map_err(|why| {
+                        Error::Config(format!("Could not serialize the schema: {}", why))
+                    })?;
+                return print_output(&format!("{}\n", schema), "the schema");
+            }
+            Commands::Config {
+                command: None,
+                parameter: Some(parameter),
+                value: Some(value),
+            } => {
                 let parameter = parameter.as_str();
                 let value = value.as_str();
-                GlobalConfig::set_defaults(&config, parameter, value);
+                GlobalConfig::set_defaults(&config, parameter, value)?;
                 log(
                     LogLevel::Log,
                     format!("Setting {} to {}", parameter, value).as_str(),
                 );
-                std::process::exit(0);
+                return Ok(());
             }
-            None => {
-                log(LogLevel::Error, "Rust is broken");
-                std::process::exit(1);
+            // Clap already requires both a parameter and a value without a subcommand
+            Commands::Config { .. } => {
+                return Err(Error::Config(
+                    "Config needs a parameter and a value".to_string(),
+                ));
             }
         }
     }
 
-    let mut gen_cc = false;
-    if args.gen_cc {
-        gen_cc = true;
-        commands::pre_gen_cc();
+    let build_dir = args.build_dir.as_deref();
+    if !(args.clean || args.build || args.run || args.gen_vsc) {
+        return Ok(());
     }
+    let config_file = enter_project(args.config.as_deref(), args.variant.as_deref())?;
 
-    let mut gen_vsc = false;
     if args.gen_vsc {
-        gen_vsc = true;
-        commands::pre_gen_vsc();
+        let (build_config, os_config, targets) =
+            parse_config(&config_file, build_dir, args.offline)?;
+        sources::fetch_sources(&build_config)?;
+        vscode::write(&build_config, &os_config, &targets, &config_file)?;
     }
 
     // If clean flag is provided, prompt user for choices
     if args.clean {
-        let (_, os_config, targets) = commands::parse_config();
+        let (build_config, os_config, targets) =
+            parse_config(&config_file, build_dir, args.offline)?;
         let mut items = vec!["All", "App_bins", "Obj"];
-        if os_config != OSConfig::default() {
+        if !os_config.name.is_empty() {
             items.push("OS");
             if !os_config.ulib.is_empty() {
                 items.push("Ulib");
@@ -245,24 +542,42 @@ license = "NONE"
             .collect();
 
         log(LogLevel::Log, "Cleaning...");
-        commands::clean(&targets, &os_config, choices);
+        let report = commands::clean(&build_config, &targets, &os_config, choices);
+        // Each failure is already logged as it happens
+        if !report.diagnostics.is_empty() {
+            return Err(Error::io(
+                format!(
+                    "Could not clean {} of {} files and directories",
+                    report.diagnostics.len(),
+                    report.diagnostics.len() + report.removed.len()
+                ),
+                std::io::Error::other("see the errors above"),
+            ));
+        }
+        log(
+            LogLevel::Log,
+            &format!("Removed {} files and directories", report.removed.len()),
+        );
     }
 
     if args.build {
-        let (build_config, os_config, targets) = commands::parse_config();
+        let (build_config, os_config, targets) =
+            parse_config(&config_file, build_dir, args.offline)?;
         log(LogLevel::Log, "Building...");
-        commands::build(&build_config, &targets, &os_config, gen_cc, gen_vsc);
+        commands::build(&build_config, &targets, &os_config, args.gen_cc)?;
     }
 
     if args.run {
-        let (build_config, os_config, targets) = commands::parse_config();
+        let (build_config, os_config, targets) =
+            parse_config(&config_file, build_dir, args.offline)?;
         let bin_args: Option<Vec<&str>> = args
             .bin_args
             .as_ref()
             .map(|x| x.iter().map(|x| x.as_str()).collect());
 
         log(LogLevel::Log, "Running...");
-        let exe_target = targets.iter().find(|x| x.typ == "exe").unwrap();
-        commands::run(bin_args, &build_config, &os_config, exe_target, &targets);
+        let exe_target = commands::exe_target(&targets)?;
+        commands::run(bin_args, &build_config, &os_config, exe_target, &targets)?;
     }
+    Ok(())
 }
@@ -0,0 +1,169 @@
+//! Metadata Module
+
+use crate::builder::{CompileUnit, Target};
+use crate::commands;
+use crate::error::Result;
+use crate::parser::{
+    BuildConfig, OSConfig, PlatformConfig, QemuConfig, SourceConfig, StepConfig, TargetConfig,
+    ToolchainConfig,
+};
+use crate::utils::features;
+use serde::Serialize;
+
+/// Struct describing the fully resolved project, printed by `ruxgo metadata`
+/// # Notes
+/// Paths are relative to `root`, the project directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct Metadata {
+    /// The version of ruxgo that resolved the project
+    pub version: String,
+    pub root: String,
+    pub build: BuildMetadata,
+    /// The OS, `None` for host builds
+    pub os: Option<OsMetadata>,
+    /// The targets in build order, including the loader of `app`
+    pub targets: Vec<TargetMetadata>,
+}
+
+/// Struct describing the resolved build config
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildMetadata {
+    pub app: String,
+    pub build_dir: String,
+    pub profile_dir: String,
+    pub bin_dir: String,
+    pub obj_dir: String,
+    pub toolchain: ToolchainConfig,
+    pub sources: Vec<SourceConfig>,
+    /// Member directories of the workspace, empty if the project is not a workspace
+    pub workspace: Vec<String>,
+}
+
+/// Struct describing the resolved OS config
+#[derive(Debug, Clone, Serialize)]
+pub struct OsMetadata {
+    pub name: String,
+    pub ulib: String,
+    pub develop: String,
+    /// The services including the features they imply
+    pub features: Vec<String>,
+    /// The features of the OS build, prefixed with `ruxfeat/`
+    pub rux_features: Vec<String>,
+    /// The features of the user library, prefixed with its crate
+    pub lib_features: Vec<String>,
+    pub platform: PlatformConfig,
+    /// The qemu command lines, `None` if the platform is not a qemu one
+    pub qemu: Option<QemuMetadata>,
+}
+
+/// Struct describing how the executable is run in qemu
+#[derive(Debug, Clone, Serialize)]
+pub struct QemuMetadata {
+    pub command: Vec<String>,
+    /// The command waiting for gdb, used if `debug = "y"`
+    pub debug_command: Vec<String>,
+}
+
+/// Struct describing a resolved target
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetMetadata {
+    pub name: String,
+    /// The workspace member the target belongs to, empty outside a workspace
+    pub member: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub deps: Vec<String>,
+    pub include_dir: Vec<String>,
+    /// The linked output of the target
+    pub artifact: String,
+    /// The ELF image of an executable, empty for the other types
+    pub elf: String,
+    pub sources: Vec<CompileUnit>,
+    pub link_command: String,
+    /// The command turning the ELF into the bin, empty if there is none
+    pub objcopy_command: String,
+    pub steps: Vec<StepConfig>,
+}
+
+/// Resolves the metadata of a project without building it
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The local os configuration
+/// * `targets` - The targets, including the loader, see `commands::prepare_targets`
+pub fn resolve(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    targets: &Vec<TargetConfig>,
+) -> Result<Metadata> {
+    let paths = &build_config.paths;
+    let build = BuildMetadata {
+        app: build_config.app.clone(),
+        build_dir: paths.root.clone(),
+        profile_dir: paths.profile_dir(),
+        bin_dir: paths.bin_dir(),
+        obj_dir: paths.obj_dir(),
+        toolchain: build_config.toolchain.clone(),
+        sources: build_config.sources.clone(),
+        workspace: build_config.workspace.clone(),
+    };
+
+    let mut target_metadata = Vec::new();
+    for target in targets {
+        let tgt = Target::new(build_config, os_config, target, targets)?;
+        let (link_command, objcopy_command) = tgt.link_commands();
+        target_metadata.push(TargetMetadata {
+            name: target.name.clone(),
+            member: target.member.clone(),
+            typ: target.typ.clone(),
+            deps: target.deps.clone(),
+            include_dir: target.include_dir.clone(),
+            artifact: tgt.bin_path.clone(),
+            elf: tgt.elf_path.clone(),
+            sources: tgt.compile_units(),
+            link_command,
+            objcopy_command,
+            steps: target.steps.clone(),
+        });
+    }
+
+    let os = if !os_config.name.is_empty() {
+        let (rux_features, lib_features) = features::cfg_feat_addprefix(os_config)?;
+        let qemu = &os_config.platform.qemu;
+        // The qemu command boots the executable, a workspace may not have a single one
+        let exe_target = commands::exe_target(targets).ok();
+        let qemu = match exe_target {
+            Some(exe_target) if qemu != &QemuConfig::default() => {
+                let tgt = Target::new(build_config, os_config, exe_target, targets)?;
+                let (command, debug_command) = qemu.config_qemu(&os_config.platform, &tgt)?;
+                Some(QemuMetadata {
+                    command,
+                    debug_command,
+                })
+            }
+            _ => None,
+        };
+        Some(OsMetadata {
+            name: os_config.name.clone(),
+            ulib: os_config.ulib.clone(),
+            develop: os_config.develop.clone(),
+            features: os_config.features.clone(),
+            rux_features,
+            lib_features,
+            platform: os_config.platform.clone(),
+            qemu,
+        })
+    } else {
+        None
+    };
+
+    let root = std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(Metadata {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        root,
+        build,
+        os,
+        targets: target_metadata,
+    })
+}
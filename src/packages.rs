@@ -82,7 +82,7 @@ async fn fetch_url(url: &str) -> Result<String, Box<dyn Error>> {
 }
 
 /// Processes the HTTP GET request and handle binary responses
-async fn fetch_binary(url: &str) -> Result<Bytes, Box<dyn Error>> {
+pub(crate) async fn fetch_binary(url: &str) -> Result<Bytes, Box<dyn Error>> {
     let resp = reqwest::get(url).await.map_err(|err| {
         log(LogLevel::Error, &format!("Failed to fetch URL: {}", err));
         Box::new(err) as Box<dyn Error>
@@ -128,6 +128,7 @@ pub async fn list_packages() -> Result<(), Box<dyn Error>> {
 }
 
 /// Pulls the specified package
+#[allow(clippy::needless_borrows_for_generic_args)]
 pub async fn pull_packages(pkg_name: &str) -> Result<(), Box<dyn Error>> {
     // load or refresh packages
     let pkgs = load_or_refresh_packages(false).await?;
@@ -182,12 +183,10 @@ pub async fn pull_packages(pkg_name: &str) -> Result<(), Box<dyn Error>> {
                         &format!("Package '{}' pulled successfully!", pkg_name),
                     );
                 } else {
-                    log(LogLevel::Error, "git clone command failed");
-                    std::process::exit(1);
+                    return Err("git clone command failed".into());
                 }
             } else {
-                log(LogLevel::Error, "Failed to run git clone command");
-                std::process::exit(1);
+                return Err("Failed to run git clone command".into());
             }
         }
         PackageType::Unknown => {
@@ -1,14 +1,26 @@
 //! Parsing Module
 
 use crate::builder::Target;
+use crate::error::{Error, Result};
+use crate::hasher::Hasher;
+use crate::schema::{
+    self, ConfigFile, LogLevel as OsLogLevel, Mode, NetDev, OsTable, PlatformTable, QemuTable,
+    SourceTable, SrcMatch, StepTable, Strings, TargetEntry, TargetTable, ToolchainTable, Ulib,
+    WorkspaceTable, YesNo,
+};
 use crate::utils::log::{log, LogLevel};
-use serde::Serialize;
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::utils::matcher::SrcFilter;
+use crate::utils::paths::{BuildPaths, CONFIG_FILE, DEFAULT_BUILD_DIR};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::default::Default;
 use std::fs::File;
 use std::process::Command;
 use std::sync::{Arc, RwLock};
-use std::{io::Read, path::Path};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
 use toml::{Table, Value};
 use walkdir::WalkDir;
 
@@ -17,10 +29,212 @@ use walkdir::WalkDir;
 pub struct BuildConfig {
     pub compiler: Arc<RwLock<String>>,
     pub app: String,
+    pub toolchain: ToolchainConfig,
+    pub paths: BuildPaths,
+    pub sources: Vec<SourceConfig>,
+    /// Only use cached sources, set with `--offline`
+    pub offline: bool,
+    /// Member directories of the workspace, empty if the config is not a workspace
+    pub workspace: Vec<String>,
 }
 
-/// Struct descibing the OS config of the local project
+impl BuildConfig {
+    /// Returns the `[build]`, `[toolchain]`, `[[sources]]` and `[workspace]` tables of
+    /// the config file describing the build config
+    /// # Notes
+    /// Tools matching the toolchain derived from the compiler and the OS are left out,
+    /// so the OS config the build config was parsed with is needed.
+    /// # Arguments
+    /// * `os_config` - The OS config of the project
+    pub fn to_table(&self, os_config: &OSConfig) -> Table {
+        let compiler = &self.base_compiler(os_config);
+        let mut config = Table::new();
+        let mut build = Table::new();
+        insert_string(&mut build, "compiler", compiler, "");
+        insert_string(&mut build, "app", &self.app, "");
+        insert_string(&mut build, "out_dir", &self.paths.root, DEFAULT_BUILD_DIR);
+        config.insert("build".to_string(), Value::Table(build));
+
+        let defaults = default_toolchain(compiler, os_config);
+        let mut toolchain = Table::new();
+        insert_string(&mut toolchain, "cc", &self.toolchain.cc, &defaults.cc);
+        insert_string(&mut toolchain, "cxx", &self.toolchain.cxx, &defaults.cxx);
+        insert_string(&mut toolchain, "ar", &self.toolchain.ar, &defaults.ar);
+        insert_string(&mut toolchain, "ld", &self.toolchain.ld, &defaults.ld);
+        insert_string(
+            &mut toolchain,
+            "objcopy",
+            &self.toolchain.objcopy,
+            &defaults.objcopy,
+        );
+        insert_string(
+            &mut toolchain,
+            "sysroot",
+            &self.toolchain.sysroot,
+            &defaults.sysroot,
+        );
+        insert_string(
+            &mut toolchain,
+            "target",
+            &self.toolchain.target,
+            &defaults.target,
+        );
+        if !toolchain.is_empty() {
+            config.insert("toolchain".to_string(), Value::Table(toolchain));
+        }
+
+        if !self.sources.is_empty() {
+            let sources = self.sources.iter().map(|s| Value::Table(s.to_table()));
+            config.insert("sources".to_string(), Value::Array(sources.collect()));
+        }
+        if !self.workspace.is_empty() {
+            let mut workspace = Table::new();
+            insert_vector(&mut workspace, "members", &self.workspace);
+            config.insert("workspace".to_string(), Value::Table(workspace));
+        }
+        config
+    }
+
+    /// Returns the `ld` given in `[toolchain]`, if it differs from the derived one
+    /// # Arguments
+    /// * `os_config` - The OS config of the project
+    pub fn explicit_ld(&self, os_config: &OSConfig) -> Option<&str> {
+        let defaults = default_toolchain(&self.base_compiler(os_config), os_config);
+        (self.toolchain.ld != defaults.ld).then_some(self.toolchain.ld.as_str())
+    }
+
+    /// Returns the compiler of `[build]` the toolchain was derived from
+    fn base_compiler(&self, os_config: &OSConfig) -> String {
+        // The compiler was replaced by the resolved `cc`, strip the cross prefix again
+        let cc = self.compiler.read().unwrap().clone();
+        let has_os = !os_config.name.is_empty();
+        match cc.strip_prefix(&os_config.platform.cross_compile) {
+            Some(compiler) if has_os && !cc.contains("clang") => compiler.to_string(),
+            _ => cc,
+        }
+    }
+}
+
+/// Struct describing a source archive fetched before building
+/// # Notes
+/// `url` may be a remote url, a `file://` url or a plain local path. `archive` is one
+/// of `tar.gz`, `tar.xz`, `tar`, `zip` or `file`, and is derived from the url if omitted.
 #[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct SourceConfig {
+    pub name: String,
+    pub url: String,
+    pub sha256: String,
+    pub archive: String,
+    pub strip_components: usize,
+    pub dest: String,
+    pub patches: Vec<String>,
+}
+
+impl SourceConfig {
+    /// Returns the archive type derived from the file name of the url
+    pub fn archive_from_url(url: &str) -> &'static str {
+        let url = url.to_lowercase();
+        if url.ends_with(".tar.gz") || url.ends_with(".tgz") {
+            "tar.gz"
+        } else if url.ends_with(".tar.xz") || url.ends_with(".txz") {
+            "tar.xz"
+        } else if url.ends_with(".tar") {
+            "tar"
+        } else if url.ends_with(".zip") {
+            "zip"
+        } else {
+            "file"
+        }
+    }
+
+    /// Returns the source as a `[[sources]]` table of the config file
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        insert_string(&mut table, "name", &self.name, "");
+        insert_string(&mut table, "url", &self.url, "");
+        insert_string(&mut table, "sha256", &self.sha256, "");
+        insert_string(
+            &mut table,
+            "archive",
+            &self.archive,
+            SourceConfig::archive_from_url(&self.url),
+        );
+        if self.strip_components != 0 {
+            table.insert(
+                "strip_components".to_string(),
+                Value::Integer(self.strip_components as i64),
+            );
+        }
+        insert_string(&mut table, "dest", &self.dest, &self.name);
+        insert_vector(&mut table, "patches", &self.patches);
+        table
+    }
+}
+
+/// Struct descibing the toolchain config of the local project
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct ToolchainConfig {
+    pub cc: String,
+    pub cxx: String,
+    pub ar: String,
+    pub ld: String,
+    pub objcopy: String,
+    pub sysroot: String,
+    pub target: String,
+}
+
+impl ToolchainConfig {
+    /// Returns the compiler used for the given source file
+    pub fn compiler_for(&self, src_path: &str) -> &str {
+        match Path::new(src_path).extension().and_then(|ext| ext.to_str()) {
+            Some("cpp") | Some("cc") | Some("cxx") => &self.cxx,
+            _ => &self.cc,
+        }
+    }
+
+    /// Returns the extra compiler flags implied by `sysroot` and `target`
+    pub fn compiler_flags(&self, compiler: &str) -> String {
+        let mut flags = String::new();
+        if !self.sysroot.is_empty() {
+            flags.push_str(&format!(" --sysroot={}", self.sysroot));
+        }
+        // Only clang understands `--target`, the gnu toolchain encodes it in the prefix
+        if !self.target.is_empty() && compiler.contains("clang") {
+            flags.push_str(&format!(" --target={}", self.target));
+        }
+        flags
+    }
+
+    /// Returns a fingerprint of the toolchain identity
+    /// # Notes
+    /// The fingerprint covers the resolved path and the `--version` output of the
+    /// compilers, the archiver and the linker, so that upgrading or switching any of
+    /// them invalidates the objects built with the previous one.
+    pub fn fingerprint(&self) -> String {
+        let mut identity = String::new();
+        for tool in [&self.cc, &self.cxx, &self.ar, &self.ld] {
+            let program = tool.split_whitespace().next().unwrap_or_default();
+            let path = resolve_program(program)
+                .map(|path| path.canonicalize().unwrap_or(path))
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let version = Command::new("sh")
+                .arg("-c")
+                .arg(format!("{} --version", tool))
+                .output()
+                .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+                .unwrap_or_default();
+            identity.push_str(&format!("{}\n{}\n{}\n", tool, path, version));
+        }
+        identity.push_str(&format!("{}\n{}\n", self.sysroot, self.target));
+        Hasher::hash_string(&identity)
+    }
+}
+
+/// Struct descibing the OS config of the local project
+/// # Notes
+/// It is (de)serialized as the `[os]` table of the config file.
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct OSConfig {
     pub name: String,
     pub features: Vec<String>,
@@ -65,20 +279,109 @@ pub struct QemuConfig {
     pub envs: String,
 }
 
+impl OSConfig {
+    /// Creates an OS config with the default platform
+    /// # Arguments
+    /// * `name` - The OS, e.g. `ruxos`
+    /// * `ulib` - The user library, `ruxlibc` or `ruxmusl`
+    pub fn new(name: &str, ulib: &str) -> Self {
+        OSConfig {
+            name: name.to_string(),
+            ulib: ulib.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the OS config as the `[os]` table of the config file
+    /// # Notes
+    /// Empty fields and fields holding their default are left out, the features implied
+    /// by the services and the user library are kept.
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        insert_string(&mut table, "name", &self.name, "");
+        insert_string(&mut table, "ulib", &self.ulib, "");
+        insert_string(&mut table, "develop", &self.develop, "n");
+        insert_vector(&mut table, "services", &self.features);
+        let platform = self.platform.to_table();
+        if !platform.is_empty() {
+            table.insert("platform".to_string(), Value::Table(platform));
+        }
+        table
+    }
+}
+
+impl Serialize for OSConfig {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.to_table().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OSConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let os = OsTable::deserialize(deserializer)?;
+        parse_os_config(Some(&os)).map_err(serde::de::Error::custom)
+    }
+}
+
+impl PlatformConfig {
+    /// Returns the platform config as the `[os.platform]` table of the config file
+    /// # Notes
+    /// The architecture, cross prefix and target are derived from the name and are left out.
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        insert_string(&mut table, "name", &self.name, "x86_64-qemu-q35");
+        insert_string(&mut table, "smp", &self.smp, "1");
+        insert_string(&mut table, "mode", &self.mode, "");
+        insert_string(&mut table, "log", &self.log, "warn");
+        insert_string(&mut table, "v", &self.v, "");
+        let qemu = self.qemu.to_table();
+        if !qemu.is_empty() {
+            table.insert("qemu".to_string(), Value::Table(qemu));
+        }
+        table
+    }
+}
+
 impl QemuConfig {
+    /// Returns the qemu config as the `[os.platform.qemu]` table of the config file
+    /// # Notes
+    /// The bus and the acceleration are derived from the architecture and the host and
+    /// are left out.
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        insert_string(&mut table, "debug", &self.debug, "n");
+        insert_string(&mut table, "blk", &self.blk, "n");
+        insert_string(&mut table, "net", &self.net, "n");
+        insert_string(&mut table, "memory", &self.mem, "128M");
+        insert_string(&mut table, "graphic", &self.graphic, "n");
+        insert_string(&mut table, "disk_img", &self.disk_img, "disk.img");
+        insert_string(&mut table, "v9p", &self.v9p, "n");
+        insert_string(&mut table, "v9p_path", &self.v9p_path, "./");
+        insert_string(&mut table, "qemu_log", &self.qemu_log, "n");
+        insert_string(&mut table, "net_dump", &self.net_dump, "n");
+        insert_string(&mut table, "net_dev", &self.net_dev, "user");
+        insert_string(&mut table, "ip", &self.ip, "10.0.2.15");
+        insert_string(&mut table, "gw", &self.gw, "10.0.2.2");
+        insert_string(&mut table, "args", &self.args, "");
+        insert_string(&mut table, "envs", &self.envs, "");
+        table
+    }
+
     /// This function is used to config qemu parameters when running on qemu
+    #[allow(unexpected_cfgs)]
     pub fn config_qemu(
         &self,
         platform_config: &PlatformConfig,
         trgt: &Target,
-    ) -> (Vec<String>, Vec<String>) {
+    ) -> Result<(Vec<String>, Vec<String>)> {
         // vdev_suffix
         let vdev_suffix = match self.bus.as_str() {
             "mmio" => "device",
             "pci" => "pci",
             _ => {
-                log(LogLevel::Error, "BUS must be one of 'mmio' or 'pci'");
-                std::process::exit(1);
+                return Err(Error::Config(
+                    "BUS must be one of 'mmio' or 'pci'".to_string(),
+                ));
             }
         };
         // config qemu
@@ -127,8 +430,7 @@ impl QemuConfig {
                 );
             }
             _ => {
-                log(LogLevel::Error, "Unsupported architecture");
-                std::process::exit(1);
+                return Err(Error::Config("Unsupported architecture".to_string()));
             }
         };
         // args and envs
@@ -171,8 +473,9 @@ impl QemuConfig {
                 qemu_args.push("-netdev".to_string());
                 qemu_args.push("tap,id=net0,ifname=tap0,script=no,downscript=no".to_string());
             } else {
-                log(LogLevel::Error, "NET_DEV must be one of 'user' or 'tap'");
-                std::process::exit(1);
+                return Err(Error::Config(
+                    "NET_DEV must be one of 'user' or 'tap'".to_string(),
+                ));
             }
             // net_dump
             if self.net_dump == "y" {
@@ -218,17 +521,26 @@ impl QemuConfig {
             }
         }
 
-        (qemu_args, qemu_args_debug)
+        Ok((qemu_args, qemu_args_debug))
     }
 }
 
 /// Struct describing the target config of the local project
-#[derive(Debug, Clone)]
+/// # Notes
+/// It is (de)serialized as a `[[targets]]` table of the config file.
+#[derive(Debug, PartialEq, Clone)]
 pub struct TargetConfig {
     pub name: String,
-    pub src: String,
+    /// The workspace member the target belongs to, empty outside a workspace
+    pub member: String,
+    /// Directories that are searched for sources, recursively
+    pub src: Vec<String>,
+    /// Source files that are compiled in addition to the ones found in `src`
+    pub files: Vec<String>,
     pub src_only: Vec<String>,
     pub src_exclude: Vec<String>,
+    /// How `src_only` and `src_exclude` are matched, `glob` or the legacy `substring`
+    pub src_match: String,
     pub include_dir: Vec<String>,
     pub typ: String,
     pub cflags: String,
@@ -236,485 +548,1602 @@ pub struct TargetConfig {
     pub linker: String,
     pub ldflags: String,
     pub deps: Vec<String>,
+    pub steps: Vec<StepConfig>,
+    pub patches: Vec<String>,
+}
+
+/// Struct describing a custom build step of a target
+/// # Notes
+/// `inputs` and `outputs` are relative to the project root, while `command` runs in
+/// `working_dir`. A step is rerun when an input or an output changed, or when the
+/// command changed; a step without inputs and outputs runs on every build.
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct StepConfig {
+    pub name: String,
+    pub command: String,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub working_dir: String,
+}
+
+impl StepConfig {
+    /// Returns the step as a `[[targets.steps]]` table of the config file
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        insert_string(&mut table, "name", &self.name, "");
+        insert_string(&mut table, "command", &self.command, "");
+        insert_vector(&mut table, "inputs", &self.inputs);
+        insert_vector(&mut table, "outputs", &self.outputs);
+        insert_string(&mut table, "working_dir", &self.working_dir, ".");
+        table
+    }
 }
 
 impl TargetConfig {
-    /// Returns a vec of all filenames ending in .cpp or .c in the src directory
+    /// Creates a target with the defaults of the config file
     /// # Arguments
-    /// * `path` - The path to the src directory
-    fn get_src_names(&self, tgt_path: &str) -> Vec<String> {
+    /// * `name` - The unique name of the target
+    /// * `typ` - The type of the target, `exe`, `dll`, `static` or `object`
+    pub fn new(name: &str, typ: &str) -> Self {
+        TargetConfig {
+            name: name.to_string(),
+            member: String::new(),
+            src: Vec::new(),
+            files: Vec::new(),
+            src_only: Vec::new(),
+            src_exclude: Vec::new(),
+            src_match: "glob".to_string(),
+            include_dir: vec!["./".to_string()],
+            typ: typ.to_string(),
+            cflags: String::new(),
+            archive: String::new(),
+            linker: String::new(),
+            ldflags: String::new(),
+            deps: Vec::new(),
+            steps: Vec::new(),
+            patches: Vec::new(),
+        }
+    }
+
+    /// Returns the target as a `[[targets]]` table of the config file
+    /// # Notes
+    /// Fields holding their default are left out. `member` is not written, it is derived
+    /// from the workspace layout.
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        insert_string(&mut table, "name", &self.name, "");
+        insert_string(&mut table, "type", &self.typ, "");
+        insert_vector(&mut table, "src", &self.src);
+        insert_vector(&mut table, "files", &self.files);
+        insert_vector(&mut table, "src_only", &self.src_only);
+        insert_vector(&mut table, "src_exclude", &self.src_exclude);
+        insert_string(&mut table, "src_match", &self.src_match, "glob");
+        // An empty include_dir differs from the default `./`
+        if self.include_dir != ["./"] {
+            table.insert(
+                "include_dir".to_string(),
+                Value::from(self.include_dir.clone()),
+            );
+        }
+        insert_string(&mut table, "cflags", &self.cflags, "");
+        insert_string(&mut table, "archive", &self.archive, "");
+        insert_string(&mut table, "linker", &self.linker, "");
+        insert_string(&mut table, "ldflags", &self.ldflags, "");
+        insert_vector(&mut table, "deps", &self.deps);
+        if !self.steps.is_empty() {
+            let steps = self.steps.iter().map(|step| Value::Table(step.to_table()));
+            table.insert("steps".to_string(), Value::Array(steps.collect()));
+        }
+        insert_vector(&mut table, "patches", &self.patches);
+        table
+    }
+
+    /// Returns the directory the patches of the target are applied to, its only `src` root
+    pub fn src_root(&self) -> &str {
+        self.src.first().map(String::as_str).unwrap_or(".")
+    }
+
+    /// Returns whether a file is an output of a step of the target, or inside one
+    fn is_step_output(&self, file: &str) -> bool {
+        let components = |path: &str| -> Vec<String> {
+            Path::new(path)
+                .components()
+                .filter(|component| component != &std::path::Component::CurDir)
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect()
+        };
+        let file = components(file);
+        self.steps
+            .iter()
+            .flat_map(|step| &step.outputs)
+            .any(|output| file.starts_with(&components(output)))
+    }
+
+    /// Returns all files ending in .cpp or .c in the src directories, followed by the
+    /// explicitly listed files, filtered by `src_only` and `src_exclude`
+    /// # Notes
+    /// A file that is reached more than once, e.g. through overlapping src directories,
+    /// is an error. Listed files that are outputs of the target's steps may not exist
+    /// yet, since the steps only run when building.
+    pub fn get_src_names(&self) -> Result<Vec<String>> {
         let mut src_names = Vec::new();
-        let src_path = Path::new(tgt_path);
+        let src_filter = SrcFilter::new(self)?;
 
-        let walker = WalkDir::new(src_path)
-            .into_iter()
-            .filter_entry(|e| self.should_include(e.path()) && !self.should_exclude(e.path()));
-        for entry in walker.filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if ext == "cpp" || ext == "c" {
-                        if let Some(file_path_str) = path.to_str() {
-                            #[cfg(target_os = "windows")]
-                            let formatted_path_str = file_path_str.replace('\\', "/");
-                            #[cfg(target_os = "linux")]
-                            let formatted_path_str = file_path_str.to_string();
-                            src_names.push(formatted_path_str);
+        for tgt_path in &self.src {
+            let src_path = Path::new(tgt_path);
+            let walker = WalkDir::new(src_path)
+                .into_iter()
+                .filter_entry(|e| !src_filter.is_excluded(src_path, e.path()));
+            for entry in walker.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if path.is_file() && src_filter.is_included(src_path, path) {
+                    if let Some(ext) = path.extension() {
+                        if ext == "cpp" || ext == "c" {
+                            if let Some(file_path_str) = path.to_str() {
+                                #[cfg(target_os = "windows")]
+                                let formatted_path_str = file_path_str.replace('\\', "/");
+                                #[cfg(target_os = "linux")]
+                                let formatted_path_str = file_path_str.to_string();
+                                src_names.push(formatted_path_str);
+                            }
                         }
                     }
                 }
             }
         }
 
-        src_names
+        // Explicit files are matched relative to the project root
+        let root = Path::new(".");
+        for file in &self.files {
+            let path = Path::new(file);
+            if !path.is_file() && !self.is_step_output(file) {
+                return Err(Error::Config(format!(
+                    "Source file of target '{}' not found: {}",
+                    self.name, file
+                )));
+            }
+            if !src_filter.is_excluded(root, path) && src_filter.is_included(root, path) {
+                src_names.push(file.clone());
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for src_name in &src_names {
+            let canonical = Path::new(src_name)
+                .canonicalize()
+                .unwrap_or_else(|_| PathBuf::from(src_name));
+            if !seen.insert(canonical) {
+                return Err(Error::Config(format!(
+                    "Source file is listed more than once in target '{}': {}\n\
+                     Check for overlapping src directories or files",
+                    self.name, src_name
+                )));
+            }
+        }
+
+        Ok(src_names)
     }
 
-    /// Exclusion logic: Check if the path is in src_exclude
-    fn should_exclude(&self, path: &Path) -> bool {
-        self.src_exclude
+    /// Rearrange the input targets
+    /// Using topological sorting to respect dependencies.
+    fn arrange_targets(targets: Vec<TargetConfig>) -> Result<Vec<TargetConfig>> {
+        // Map the target names to their position in the config
+        let index: HashMap<&str, usize> = targets
             .iter()
-            .any(|excluded| path.to_str().map_or(false, |p| p.contains(excluded)))
+            .enumerate()
+            .map(|(i, target)| (target.name.as_str(), i))
+            .collect();
+
+        // Build a graph and an in-degree table
+        let mut graph: Vec<Vec<usize>> = vec![Vec::new(); targets.len()];
+        let mut in_degree = vec![0; targets.len()];
+        for (i, target) in targets.iter().enumerate() {
+            for dep in &target.deps {
+                if let Some(&dep) = index.get(dep.as_str()) {
+                    graph[dep].push(i);
+                    in_degree[i] += 1;
+                }
+            }
+        }
+
+        // Using topological sort, taking the ready target that comes first in the config,
+        // so that independent targets keep their order
+        let mut ready: BTreeSet<usize> =
+            (0..targets.len()).filter(|&i| in_degree[i] == 0).collect();
+        let mut sorted = Vec::new();
+        while let Some(i) = ready.pop_first() {
+            sorted.push(i);
+            for &dependant in &graph[i] {
+                in_degree[dependant] -= 1;
+                if in_degree[dependant] == 0 {
+                    ready.insert(dependant);
+                }
+            }
+        }
+
+        // Check for rings
+        if sorted.len() != targets.len() {
+            let sorted_names: Vec<String> =
+                sorted.iter().map(|&i| targets[i].name.clone()).collect();
+            let target_map: HashMap<String, TargetConfig> = targets
+                .into_iter()
+                .map(|target| (target.name.clone(), target))
+                .collect();
+            let cycle = TargetConfig::find_cycle(&target_map, &sorted_names);
+            return Err(Error::Config(format!(
+                "Circular dependency detected: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        // Rebuild the target list based on the sorted positions
+        let mut targets: Vec<Option<TargetConfig>> = targets.into_iter().map(Some).collect();
+        Ok(sorted
+            .into_iter()
+            .filter_map(|i| targets[i].take())
+            .collect())
     }
 
-    /// Inclusion logic: Apply src_only logic only to files
-    fn should_include(&self, path: &Path) -> bool {
-        if self.src_only.is_empty() {
-            return true;
+    /// Returns a dependency cycle among the targets that could not be sorted, e.g.
+    /// `[a, b, a]` if `a` depends on `b` and `b` on `a`
+    /// # Notes
+    /// Every target left unsorted depends on another unsorted target, so following the
+    /// dependencies from any of them runs into a cycle. The smallest names are followed
+    /// first, so that the reported cycle does not change between runs.
+    fn find_cycle(target_map: &HashMap<String, TargetConfig>, sorted: &[String]) -> Vec<String> {
+        let unsorted = |name: &String| target_map.contains_key(name) && !sorted.contains(name);
+        let mut current = target_map
+            .keys()
+            .filter(|name| unsorted(name))
+            .min()
+            .cloned()
+            .unwrap_or_default();
+        let mut path: Vec<String> = Vec::new();
+        while !path.contains(&current) {
+            path.push(current.clone());
+            let Some(next) = target_map[&current]
+                .deps
+                .iter()
+                .filter(|dep| unsorted(dep))
+                .min()
+            else {
+                break;
+            };
+            current = next.clone();
         }
-        self.src_only
-            .iter()
-            .any(|included| path.to_str().map_or(false, |p| p.contains(included)))
+        let start = path.iter().position(|name| *name == current).unwrap_or(0);
+        let mut cycle = path.split_off(start);
+        cycle.push(current);
+        cycle
     }
+}
 
-    /// Checks for duplicate source files in the target
-    fn check_duplicate_srcs(&self) {
-        let mut src_file_names = self.get_src_names(&self.src);
-        src_file_names.sort_unstable();
-        src_file_names.dedup();
-        let mut last_name: Option<String> = None;
-        let mut duplicates = Vec::new();
+impl Serialize for TargetConfig {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.to_table().serialize(serializer)
+    }
+}
 
-        for file_name in &src_file_names {
-            if let Some(ref last) = last_name {
-                if last == file_name {
-                    duplicates.push(file_name.clone());
-                }
+impl<'de> Deserialize<'de> for TargetConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let target = TargetTable::deserialize(deserializer)?;
+        parse_target(&target).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Reads a config file
+fn read_config_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .map_err(|e| Error::io(format!("Could not open config file: {}", path.display()), e))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .map_err(|e| Error::io(format!("Could not read config file: {}", path.display()), e))?;
+    Ok(contents)
+}
+
+/// Parses a config file and merges the files it includes
+/// # Notes
+/// The files listed in `include` are merged in order, and the including file is merged
+/// last, so that later files override earlier ones. Included files may include other
+/// files, with paths relative to the including file. See `merge_tables` for how values
+/// are merged.
+/// # Arguments
+/// * `path` - The path to the config file
+/// * `contents` - The contents of the config file
+/// * `stack` - The files currently being included, to detect cycles
+/// * `errors` - Receives the validation errors of all loaded files
+fn load_config_table(
+    path: &Path,
+    contents: &str,
+    stack: &mut Vec<PathBuf>,
+    errors: &mut Vec<String>,
+) -> Result<Table> {
+    let mut config = contents.parse::<Table>().map_err(|e| {
+        Error::Config(format!(
+            "Could not parse config file: {}\nError: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    errors.extend(schema::validate(path, contents));
+
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(Error::Config(format!(
+            "Config file includes itself: {}",
+            path.display()
+        )));
+    }
+    let includes = match config.remove("include") {
+        Some(Value::String(include)) => vec![include],
+        Some(Value::Array(arr)) => arr
+            .into_iter()
+            .map(|include| match include {
+                Value::String(include) => Ok(include),
+                _ => Err(Error::Config(format!(
+                    "Invalid include in config file: {}",
+                    path.display()
+                ))),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => {
+            return Err(Error::Config(format!(
+                "Invalid include in config file: {}",
+                path.display()
+            )));
+        }
+        None => return Ok(config),
+    };
+
+    stack.push(canonical);
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = Table::new();
+    for include in includes {
+        let include_path = base_dir.join(include);
+        let included = load_config_table(
+            &include_path,
+            &read_config_file(&include_path)?,
+            stack,
+            errors,
+        )?;
+        merge_tables(&mut merged, included);
+    }
+    stack.pop();
+    merge_tables(&mut merged, config);
+    Ok(merged)
+}
+
+/// Returns an error listing all validation errors of a config, if there are any
+fn check_errors(errors: &[String]) -> Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(Error::Config(format!(
+        "{}\nFound {} error(s) in the config",
+        errors.join("\n"),
+        errors.len()
+    )))
+}
+
+/// Merges `overlay` into `base`
+/// # Notes
+/// Tables are merged key by key, arrays of tables such as `[[targets]]` are appended,
+/// and all other values of `overlay` replace the ones of `base`.
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(table)) => {
+                merge_tables(base_table, table)
+            }
+            (Some(Value::Array(base_arr)), Value::Array(arr))
+                if is_table_array(base_arr) && is_table_array(&arr) =>
+            {
+                base_arr.extend(arr)
+            }
+            (_, value) => {
+                base.insert(key, value);
             }
-            last_name = Some(file_name.clone());
         }
-        if !duplicates.is_empty() {
-            log(
-                LogLevel::Error,
-                &format!("Duplicate source files found for target: {}", self.name),
-            );
-            log(LogLevel::Error, "Source files must be unique");
-            for duplicate in duplicates {
-                log(LogLevel::Error, &format!("Duplicate file: {}", duplicate));
+    }
+}
+
+/// Checks if an array is a non-empty array of tables
+fn is_table_array(arr: &[Value]) -> bool {
+    !arr.is_empty() && arr.iter().all(Value::is_table)
+}
+
+/// Applies the `[templates.<name>]` tables to the targets that `extends` them
+/// # Notes
+/// `extends` is either a template name or a list of names applied in order. Templates
+/// can extend other templates, and the keys of the target override those of its
+/// templates.
+fn expand_templates(config: &mut Table) -> Result<()> {
+    let templates = match config.remove("templates") {
+        Some(Value::Table(templates)) => templates,
+        Some(_) => {
+            return Err(Error::Config("Templates must be a table".to_string()));
+        }
+        None => Table::new(),
+    };
+    if let Some(Value::Array(targets)) = config.get_mut("targets") {
+        for target in targets.iter_mut() {
+            if let Value::Table(target_tb) = target {
+                let name = parse_cfg_string(target_tb, "name", "")?;
+                let expanded =
+                    resolve_template(target_tb.clone(), &templates, &mut Vec::new(), &name)?;
+                *target_tb = expanded;
             }
-            std::process::exit(1);
         }
     }
+    Ok(())
+}
 
-    /// Rearrange the input targets
-    /// Using topological sorting to respect dependencies.
-    fn arrange_targets(targets: Vec<TargetConfig>) -> Vec<TargetConfig> {
-        // Create a mapping from the target name to the target configuration
-        let mut target_map: HashMap<String, TargetConfig> = targets
+/// Resolves the `extends` of a target or template against the templates
+fn resolve_template(
+    mut table: Table,
+    templates: &Table,
+    stack: &mut Vec<String>,
+    target_name: &str,
+) -> Result<Table> {
+    let extends = match table.remove("extends") {
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Array(arr)) => arr
             .into_iter()
-            .map(|target| (target.name.clone(), target))
-            .collect();
+            .filter_map(|name| name.as_str().map(String::from))
+            .collect(),
+        Some(_) => {
+            return Err(Error::Config(format!(
+                "Invalid extends in target '{}'",
+                target_name
+            )));
+        }
+        None => return Ok(table),
+    };
+    let mut merged = Table::new();
+    for name in extends {
+        if stack.contains(&name) {
+            return Err(Error::Config(format!(
+                "Template '{}' extends itself, in target '{}'",
+                name, target_name
+            )));
+        }
+        let template = match templates.get(&name) {
+            Some(Value::Table(template)) => template.clone(),
+            _ => {
+                return Err(Error::Config(format!(
+                    "Unknown template '{}' extended by target '{}'",
+                    name, target_name
+                )));
+            }
+        };
+        stack.push(name);
+        let template = resolve_template(template, templates, stack, target_name)?;
+        stack.pop();
+        merge_tables(&mut merged, template);
+    }
+    merge_tables(&mut merged, table);
+    Ok(merged)
+}
+
+/// Names of the built-in variables, which cannot be redefined in `[vars]`
+const BUILTIN_VARS: [&str; 5] = ["root", "arch", "platform", "mode", "build_dir"];
+
+/// Struct resolving the `${...}` variables in config values
+/// # Notes
+/// Variables are the user-defined `[vars]`, `${env:NAME}` or `${env:NAME:-default}` for
+/// environment variables, `${target.name}` inside a target and the built-ins `root`,
+/// `arch`, `platform`, `mode` and `build_dir`. Variables are resolved lazily, so they
+/// may refer to each other, and `$${` is a literal `${`.
+#[derive(Clone)]
+struct Interpolator {
+    /// Unresolved values of the user-defined and built-in variables
+    defs: HashMap<String, String>,
+    /// Whether the config has no `[os]`, in which case `arch` is the host's
+    host: bool,
+}
 
-        // Build a graph and an in-degree table,and initialize
-        let mut graph: HashMap<String, Vec<String>> = HashMap::new();
-        let mut in_degree: HashMap<String, usize> = HashMap::new();
-        for name in target_map.keys() {
-            graph.entry(name.clone()).or_default();
-            in_degree.entry(name.clone()).or_insert(0);
+impl Interpolator {
+    /// Creates the interpolator of a config, taking its `[vars]` out of it
+    /// # Arguments
+    /// * `config` - The config
+    /// * `root` - The directory of the config file
+    /// * `build_dir` - The build directory overriding `[build] out_dir`
+    fn new(config: &mut Table, root: &Path, build_dir: Option<&str>) -> Result<Self> {
+        let os = config.get("os").and_then(Value::as_table);
+        let platform = os
+            .and_then(|os| os.get("platform"))
+            .and_then(Value::as_table);
+        let build = config.get("build").and_then(Value::as_table);
+        let raw = |table: Option<&Table>, field: &str, default: &str| match table {
+            Some(table) => parse_cfg_string(table, field, default),
+            None => Ok(default.to_string()),
+        };
+        let mut defs = HashMap::new();
+        let platform_name = match os {
+            Some(_) => raw(platform, "name", "x86_64-qemu-q35")?,
+            None => "host".to_string(),
+        };
+        defs.insert("platform".to_string(), platform_name);
+        // An unset mode builds in debug, as in the paths of the OS build
+        defs.insert("mode".to_string(), raw(platform, "mode", "debug")?);
+        let build_dir = match build_dir {
+            Some(build_dir) => build_dir.to_string(),
+            None => raw(build, "out_dir", DEFAULT_BUILD_DIR)?,
+        };
+        defs.insert("build_dir".to_string(), build_dir);
+        let mut interpolator = Interpolator {
+            defs,
+            host: os.is_none(),
+        };
+        interpolator.set_root(root);
+        interpolator.add_vars(config)?;
+        Ok(interpolator)
+    }
+
+    /// Returns the interpolator of a workspace member, which sees the variables of the
+    /// workspace and its own `[vars]`
+    fn for_member(&self, config: &mut Table, member_dir: &Path) -> Result<Self> {
+        let mut interpolator = self.clone();
+        interpolator.set_root(member_dir);
+        interpolator.add_vars(config)?;
+        Ok(interpolator)
+    }
+
+    /// Sets `${root}` to the absolute path of a directory
+    fn set_root(&mut self, dir: &Path) {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let root = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        self.defs.insert(
+            "root".to_string(),
+            root.to_string_lossy().replace("${", "$${"),
+        );
+    }
+
+    /// Takes the `[vars]` table out of a config and adds its variables
+    fn add_vars(&mut self, config: &mut Table) -> Result<()> {
+        let vars = match config.remove("vars") {
+            Some(Value::Table(vars)) => vars,
+            Some(_) => {
+                return Err(Error::Config("Vars must be a table".to_string()));
+            }
+            None => return Ok(()),
+        };
+        for (name, value) in vars {
+            if BUILTIN_VARS.contains(&name.as_str()) || name.contains(['.', ':']) {
+                return Err(Error::Config(format!(
+                    "Variable name '{}' in vars is reserved",
+                    name
+                )));
+            }
+            let value = match value {
+                Value::String(value) => value,
+                Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+                _ => {
+                    return Err(Error::Config(format!(
+                        "vars.{} must be a string, number or boolean",
+                        name
+                    )));
+                }
+            };
+            self.defs.insert(name, value);
         }
+        Ok(())
+    }
 
-        // Fill the graph and update the in-degree table
-        for target in target_map.values() {
-            for dep in &target.deps {
-                if let Some(deps) = graph.get_mut(dep) {
-                    deps.push(target.name.clone());
-                    *in_degree.entry(target.name.clone()).or_insert(0) += 1;
+    /// Interpolates all string values of a config in place
+    fn interpolate_config(&self, config: &mut Table) -> Result<()> {
+        for (key, value) in config.iter_mut() {
+            match value {
+                Value::Array(targets) if key == "targets" => {
+                    for (i, target) in targets.iter_mut().enumerate() {
+                        let key = format!("targets[{}]", i);
+                        let name = match target.get("name") {
+                            Some(Value::String(name)) => self.interpolate(
+                                name,
+                                &format!("{}.name", key),
+                                None,
+                                &mut Vec::new(),
+                            )?,
+                            _ => String::new(),
+                        };
+                        self.interpolate_value(target, &key, Some(&name))?;
+                    }
                 }
+                value => self.interpolate_value(value, key, None)?,
             }
         }
+        Ok(())
+    }
 
-        // Using topological sort
-        let mut queue: VecDeque<String> = VecDeque::new();
-        for (name, &degree) in &in_degree {
-            if degree == 0 {
-                queue.push_back(name.clone());
+    /// Interpolates a value and everything it contains
+    /// # Arguments
+    /// * `value` - The value to interpolate
+    /// * `key` - The key of the value, used in errors
+    /// * `target` - The name of the enclosing target
+    fn interpolate_value(&self, value: &mut Value, key: &str, target: Option<&str>) -> Result<()> {
+        match value {
+            Value::String(s) => *s = self.interpolate(s, key, target, &mut Vec::new())?,
+            Value::Array(arr) => {
+                for (i, value) in arr.iter_mut().enumerate() {
+                    self.interpolate_value(value, &format!("{}[{}]", key, i), target)?;
+                }
             }
-        }
-        let mut sorted_names = Vec::new();
-        while let Some(name) = queue.pop_front() {
-            sorted_names.push(name.clone());
-            if let Some(deps) = graph.get(&name) {
-                for dep in deps {
-                    let degree = in_degree.entry(dep.clone()).or_default();
-                    *degree -= 1;
-                    if *degree == 0 {
-                        queue.push_back(dep.clone());
-                    }
+            Value::Table(table) => {
+                for (field, value) in table.iter_mut() {
+                    self.interpolate_value(value, &format!("{}.{}", key, field), target)?;
                 }
             }
+            _ => {}
         }
+        Ok(())
+    }
 
-        // Check for rings
-        if sorted_names.len() != target_map.len() {
-            log(LogLevel::Error, "Circular dependency detected");
-            std::process::exit(1);
+    /// Replaces the variables in a string
+    /// # Arguments
+    /// * `s` - The string to interpolate
+    /// * `key` - The key of the string, used in errors
+    /// * `target` - The name of the enclosing target
+    /// * `stack` - The variables currently being resolved, to detect cycles
+    fn interpolate(
+        &self,
+        s: &str,
+        key: &str,
+        target: Option<&str>,
+        stack: &mut Vec<String>,
+    ) -> Result<String> {
+        let mut result = String::new();
+        let mut rest = s;
+        while let Some(pos) = rest.find('$') {
+            result.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+            if let Some(after) = rest.strip_prefix("$${") {
+                result.push_str("${");
+                rest = after;
+                continue;
+            }
+            let Some(after) = rest.strip_prefix("${") else {
+                result.push('$');
+                rest = &rest[1..];
+                continue;
+            };
+            // Find the matching brace, defaults may contain variables themselves
+            let mut depth = 1;
+            let end = after.find(|c| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            });
+            let Some(end) = end else {
+                return Err(Error::Config(format!(
+                    "Unterminated variable '{}' in '{}'",
+                    rest, key
+                )));
+            };
+            result.push_str(&self.lookup(&after[..end], key, target, stack)?);
+            rest = &after[end + 1..];
         }
+        result.push_str(rest);
+        Ok(result)
+    }
 
-        // Rebuild the target list based on the sorted names
-        sorted_names
-            .into_iter()
-            .map(|name| target_map.remove(&name).unwrap())
-            .collect()
+    /// Resolves a single variable
+    fn lookup(
+        &self,
+        name: &str,
+        key: &str,
+        target: Option<&str>,
+        stack: &mut Vec<String>,
+    ) -> Result<String> {
+        if let Some(env) = name.strip_prefix("env:") {
+            let (env_name, default) = match env.split_once(":-") {
+                Some((env_name, default)) => (env_name, Some(default)),
+                None => (env, None),
+            };
+            return match (std::env::var(env_name), default) {
+                (Ok(value), _) => Ok(value),
+                (Err(_), Some(default)) => self.interpolate(default, key, target, stack),
+                (Err(_), None) => Err(Error::Config(format!(
+                    "Undefined environment variable '{}' in '{}'",
+                    env_name, key
+                ))),
+            };
+        }
+        if name == "target.name" {
+            return target.map(String::from).ok_or_else(|| {
+                Error::Config(format!(
+                    "'${{target.name}}' is only defined in targets, in '{}'",
+                    key
+                ))
+            });
+        }
+        if name == "arch" {
+            if self.host {
+                return Ok(std::env::consts::ARCH.to_string());
+            }
+            let platform = self.lookup("platform", key, target, stack)?;
+            return Ok(platform.split('-').next().unwrap_or_default().to_string());
+        }
+        let Some(def) = self.defs.get(name) else {
+            return Err(Error::Config(format!(
+                "Undefined variable '{}' in '{}'",
+                name, key
+            )));
+        };
+        if stack.iter().any(|var| var == name) {
+            return Err(Error::Config(format!(
+                "Variable '{}' refers to itself, in '{}'",
+                name, key
+            )));
+        }
+        stack.push(name.to_string());
+        let value = self.interpolate(def, key, target, stack)?;
+        stack.pop();
+        Ok(value)
     }
 }
 
 /// This function is used to parse the config file of local project
 /// # Arguments
 /// * `path` - The path to the config file
-/// * `check_dup_src` - If true, the function will check for duplicately named source files
-pub fn parse_config(path: &str, check_dup_src: bool) -> (BuildConfig, OSConfig, Vec<TargetConfig>) {
-    // Open toml file and parse it into a string
-    let mut file = File::open(path).unwrap_or_else(|_| {
-        log(
-            LogLevel::Error,
-            &format!("Could not open config file: {}", path),
-        );
-        std::process::exit(1);
-    });
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap_or_else(|_| {
-        log(
-            LogLevel::Error,
-            &format!("Could not read config file: {}", path),
-        );
-        std::process::exit(1);
-    });
-    let config = contents.parse::<Table>().unwrap_or_else(|e| {
-        log(
-            LogLevel::Error,
-            &format!("Could not parse config file: {}", path),
-        );
-        log(LogLevel::Error, &format!("Error: {}", e));
-        std::process::exit(1);
-    });
+/// * `build_dir` - The build directory overriding `[build] out_dir`
+pub fn parse_config(
+    path: &str,
+    build_dir: Option<&str>,
+) -> Result<(BuildConfig, OSConfig, Vec<TargetConfig>)> {
+    let contents = read_config_file(Path::new(path))?;
+    parse_config_str(&contents, path, build_dir)
+}
 
-    let build_config = parse_build_config(&config);
-    let os_config = parse_os_config(&config, &build_config);
-    let targets = parse_targets(&config, &build_config, check_dup_src);
+/// Parses the contents of a config file, which need not exist on disk
+/// # Arguments
+/// * `contents` - The contents of the config file
+/// * `path` - The path of the config file, includes and workspace members are relative to it
+/// * `build_dir` - The build directory overriding `[build] out_dir`
+pub fn parse_config_str(
+    contents: &str,
+    path: &str,
+    build_dir: Option<&str>,
+) -> Result<(BuildConfig, OSConfig, Vec<TargetConfig>)> {
+    let mut errors = Vec::new();
+    let mut config = load_config_table(Path::new(path), contents, &mut Vec::new(), &mut errors)?;
+    check_errors(&errors)?;
+    expand_templates(&mut config)?;
+    let root_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let interpolator = Interpolator::new(&mut config, root_dir, build_dir)?;
+    interpolator.interpolate_config(&mut config)?;
+    let config = read_config(config)?;
 
-    (build_config, os_config, targets)
+    let mut build_config = parse_build_config(&config)?;
+    if let Some(build_dir) = build_dir {
+        build_config.paths.root = build_dir.to_string();
+    }
+    let os_config = parse_os_config(config.os.as_ref())?;
+    build_config.paths = BuildPaths::new(&build_config.paths.root, &os_config);
+    build_config.toolchain = parse_toolchain(config.toolchain.as_ref(), &build_config, &os_config)?;
+    build_config.sources = parse_sources(config.sources.as_deref().unwrap_or_default())?;
+    build_config.workspace = parse_workspace(config.workspace.as_ref())?;
+    let targets = if build_config.workspace.is_empty() {
+        let targets = parse_targets(config.targets.as_deref(), &build_config, None)?;
+        for target in &targets {
+            if let Some(dep) = target.deps.iter().find(|dep| dep.contains(':')) {
+                return Err(Error::Config(format!(
+                    "Target '{}' depends on '{}', which is only available in a workspace",
+                    target.name, dep
+                )));
+            }
+        }
+        targets
+    } else {
+        parse_workspace_targets(path, &config, &interpolator, &mut build_config)?
+    };
+
+    Ok((build_config, os_config, targets))
 }
 
-/// Parses the build configuration
-fn parse_build_config(config: &Table) -> BuildConfig {
-    let build = config["build"].as_table().unwrap_or_else(|| {
-        log(LogLevel::Error, "Could not find build in config file");
-        std::process::exit(1);
-    });
-    let compiler = Arc::new(RwLock::new(parse_cfg_string(build, "compiler", "")));
-    let app = parse_cfg_string(build, "app", "");
+/// Reads a merged and interpolated config into the types describing the config file
+fn read_config(config: Table) -> Result<ConfigFile> {
+    Value::Table(config).try_into().map_err(|e| {
+        Error::Config(format!(
+            "Invalid config once its variables are interpolated: {}",
+            e
+        ))
+    })
+}
 
-    BuildConfig { compiler, app }
+/// Parses the member directories of the `[workspace]` table
+fn parse_workspace(workspace: Option<&WorkspaceTable>) -> Result<Vec<String>> {
+    let Some(workspace) = workspace else {
+        return Ok(Vec::new());
+    };
+    let members = workspace.members.clone();
+    if members.is_empty() {
+        return Err(Error::Config("Workspace has no members".to_string()));
+    }
+    Ok(members)
 }
 
-/// Parses the OS configuration
-fn parse_os_config(config: &Table, build_config: &BuildConfig) -> OSConfig {
-    let empty_os = Value::Table(toml::map::Map::default());
-    let os = config.get("os").unwrap_or(&empty_os);
-    let os_config: OSConfig;
-    if os != &empty_os {
-        if let Some(os_table) = os.as_table() {
-            let name = parse_cfg_string(os_table, "name", "");
-            let ulib = parse_cfg_string(os_table, "ulib", "");
-            let develop = parse_cfg_string(os_table, "develop", "n");
-            let mut features = parse_cfg_vector(os_table, "services");
-            if features.iter().any(|feat| {
-                feat == "fs"
-                    || feat == "net"
-                    || feat == "pipe"
-                    || feat == "select"
-                    || feat == "poll"
-                    || feat == "epoll"
-            }) {
-                features.push("fd".to_string());
-            }
-            if ulib == "ruxmusl" {
-                features.push("musl".to_string());
-                features.push("fp_simd".to_string());
-                features.push("fd".to_string());
-                features.push("tls".to_string());
-            }
-            // Parse platform (if empty, it is the default value)
-            let platform = parse_platform(os_table);
-            let current_compiler = build_config.compiler.read().unwrap();
-            let new_compiler = format!("{}{}", platform.cross_compile, *current_compiler);
-            drop(current_compiler);
-            *build_config.compiler.write().unwrap() = new_compiler;
-            os_config = OSConfig {
-                name,
-                features,
-                ulib,
-                develop,
-                platform,
+/// Parses the targets and sources of all workspace members
+/// # Notes
+/// Each member is a directory with its own `ruxgo.toml`, named after the last component
+/// of its path. Only its targets, templates and sources are used; the build directory,
+/// toolchain and OS of the workspace root are shared by all members. The paths of a
+/// member are rebased onto the workspace root. A dependency `member:target` refers to a
+/// target of another member, a plain name to a target of the same member.
+/// # Arguments
+/// * `path` - The path to the workspace config file
+/// * `config` - The workspace config
+/// * `interpolator` - The interpolator of the workspace config
+/// * `build_config` - The workspace build configuration, which receives the member sources
+fn parse_workspace_targets(
+    path: &str,
+    config: &ConfigFile,
+    interpolator: &Interpolator,
+    build_config: &mut BuildConfig,
+) -> Result<Vec<TargetConfig>> {
+    let root_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut targets = Vec::new();
+    // The workspace root may have targets of its own
+    if let Some(root_targets) = &config.targets {
+        targets.extend(parse_targets(Some(root_targets), build_config, None)?);
+    }
+
+    let mut member_names = HashSet::new();
+    for member in build_config.workspace.clone() {
+        let member_dir = root_dir.join(&member);
+        let member_name = member_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+        if member_name.is_empty() || !member_names.insert(member_name.clone()) {
+            return Err(Error::Config(format!(
+                "Invalid or duplicate workspace member: {}",
+                member
+            )));
+        }
+        let mut errors = Vec::new();
+        let member_path = member_dir.join(CONFIG_FILE);
+        let mut member_config = load_config_table(
+            &member_path,
+            &read_config_file(&member_path)?,
+            &mut Vec::new(),
+            &mut errors,
+        )?;
+        check_errors(&errors)?;
+        if member_config.contains_key("workspace") {
+            return Err(Error::Config(format!(
+                "Workspace member '{}' cannot be a workspace itself",
+                member
+            )));
+        }
+        // The [build] section stays, the member needs it to be built on its own
+        for section in ["toolchain", "os"] {
+            if member_config.contains_key(section) {
+                log(
+                    LogLevel::Warn,
+                    &format!(
+                        "The [{}] section of workspace member '{}' is ignored, the workspace's is used",
+                        section, member
+                    ),
+                );
+            }
+        }
+        if !member_config.contains_key("targets") {
+            return Err(Error::Config(format!(
+                "Workspace member '{}' has no targets",
+                member
+            )));
+        }
+        expand_templates(&mut member_config)?;
+        interpolator
+            .for_member(&mut member_config, &member_dir)?
+            .interpolate_config(&mut member_config)?;
+        let member_config = read_config(member_config)?;
+
+        let member_sources = member_config.sources.as_deref().unwrap_or_default();
+        for mut source in parse_sources(member_sources)? {
+            if build_config.sources.iter().any(|s| s.name == source.name) {
+                return Err(Error::Config(format!(
+                    "Duplicate source names found: {}",
+                    source.name
+                )));
+            }
+            if !source.url.contains("://") {
+                source.url = rebase_path(&member_dir, &source.url);
+            }
+            source.dest = rebase_path(&member_dir, &source.dest);
+            rebase_paths(&member_dir, &mut source.patches);
+            build_config.sources.push(source);
+        }
+        for mut target in parse_targets(
+            member_config.targets.as_deref(),
+            build_config,
+            Some(&member_dir),
+        )? {
+            target.member = member_name.clone();
+            targets.push(target);
+        }
+    }
+
+    // Target names stay unique so that the outputs in the shared build directory do not clash
+    let mut owners: HashMap<String, String> = HashMap::new();
+    for target in &targets {
+        if let Some(owner) = owners.insert(target.name.clone(), target.member.clone()) {
+            return Err(Error::Config(format!(
+                "Target '{}' is defined by both members '{}' and '{}'",
+                target.name, owner, target.member
+            )));
+        }
+    }
+    for target in &mut targets {
+        for dep in &mut target.deps {
+            let (member, name) = match dep.split_once(':') {
+                Some((member, name)) => (member.to_string(), name.to_string()),
+                None => (target.member.clone(), dep.clone()),
             };
-        } else {
-            log(LogLevel::Error, "OS is not a table");
-            std::process::exit(1);
+            match owners.get(&name) {
+                Some(owner) if *owner == member => *dep = name,
+                Some(owner) if !dep.contains(':') => {
+                    return Err(Error::Config(format!(
+                        "Target '{}' depends on '{}' of member '{}', use '{}:{}'",
+                        target.name, name, owner, owner, name
+                    )));
+                }
+                // Unknown plain names are reported when the target is built
+                None if !dep.contains(':') => {}
+                _ => {
+                    return Err(Error::Config(format!(
+                        "Target '{}' depends on '{}', which is not a target of the workspace",
+                        target.name, dep
+                    )));
+                }
+            }
         }
+    }
+
+    TargetConfig::arrange_targets(targets)
+}
+
+/// Rebases a path of a workspace member onto the workspace root
+fn rebase_path(member_dir: &Path, path: &str) -> String {
+    member_dir.join(path).to_string_lossy().to_string()
+}
+
+/// Rebases the paths of a workspace member onto the workspace root
+fn rebase_paths(member_dir: &Path, paths: &mut [String]) {
+    for path in paths.iter_mut() {
+        *path = rebase_path(member_dir, path);
+    }
+}
+
+/// Rebases the paths of a target of a workspace member onto the workspace root
+fn rebase_target(member_dir: &Path, target: &mut TargetConfig) {
+    rebase_paths(member_dir, &mut target.src);
+    rebase_paths(member_dir, &mut target.files);
+    rebase_paths(member_dir, &mut target.include_dir);
+    rebase_paths(member_dir, &mut target.patches);
+    for step in &mut target.steps {
+        step.working_dir = rebase_path(member_dir, &step.working_dir);
+        rebase_paths(member_dir, &mut step.inputs);
+        rebase_paths(member_dir, &mut step.outputs);
+    }
+}
+
+/// Parses the build configuration
+fn parse_build_config(config: &ConfigFile) -> Result<BuildConfig> {
+    let build = config
+        .build
+        .as_ref()
+        .ok_or_else(|| Error::Config("Could not find build in config file".to_string()))?;
+    let compiler = Arc::new(RwLock::new(build.compiler.clone().unwrap_or_default()));
+    let app = build.app.clone().unwrap_or_default();
+    let out_dir = build
+        .out_dir
+        .clone()
+        .unwrap_or_else(|| DEFAULT_BUILD_DIR.to_string());
+
+    Ok(BuildConfig {
+        compiler,
+        app,
+        toolchain: ToolchainConfig::default(),
+        paths: BuildPaths::new(&out_dir, &OSConfig::default()),
+        sources: Vec::new(),
+        offline: false,
+        workspace: Vec::new(),
+    })
+}
+
+/// Parses the toolchain configuration
+/// # Notes
+/// Missing tools are derived from the platform, e.g. `x86_64-linux-musl-gcc` for gcc
+/// or `clang --target=x86_64-linux-musl` for clang when building for RuxOS.
+/// The resolved `cc` is also written back to `build_config.compiler`.
+fn parse_toolchain(
+    toolchain: Option<&ToolchainTable>,
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+) -> Result<ToolchainConfig> {
+    let empty_toolchain = ToolchainTable::default();
+    let toolchain = toolchain.unwrap_or(&empty_toolchain);
+    let compiler = build_config.compiler.read().unwrap().clone();
+    let defaults = default_toolchain(&compiler, os_config);
+    let tool = |tool: &Option<String>, default: String| tool.clone().unwrap_or(default);
+    let toolchain_config = ToolchainConfig {
+        cc: tool(&toolchain.cc, defaults.cc),
+        cxx: tool(&toolchain.cxx, defaults.cxx),
+        ar: tool(&toolchain.ar, defaults.ar),
+        ld: tool(&toolchain.ld, defaults.ld),
+        objcopy: tool(&toolchain.objcopy, defaults.objcopy),
+        sysroot: tool(&toolchain.sysroot, defaults.sysroot),
+        target: tool(&toolchain.target, defaults.target),
+    };
+    validate_toolchain(toolchain, &toolchain_config)?;
+    *build_config.compiler.write().unwrap() = toolchain_config.cc.clone();
+
+    Ok(toolchain_config)
+}
+
+/// Returns the toolchain derived from the compiler and the platform
+fn default_toolchain(compiler: &str, os_config: &OSConfig) -> ToolchainConfig {
+    let has_os = !os_config.name.is_empty();
+    let is_clang = compiler.contains("clang");
+    let cross_compile = if has_os && !is_clang {
+        os_config.platform.cross_compile.as_str()
+    } else {
+        ""
+    };
+    let cxx = match compiler {
+        "gcc" => "g++",
+        "clang" => "clang++",
+        "cc" => "c++",
+        other => other,
+    };
+    let default_cc = format!("{}{}", cross_compile, compiler);
+    let default_cxx = format!("{}{}", cross_compile, cxx);
+    let default_ar = if has_os && is_clang {
+        "llvm-ar".to_string()
     } else {
-        os_config = OSConfig::default();
+        format!("{}ar", cross_compile)
+    };
+    let default_ld = default_cc.clone();
+    let default_objcopy = if has_os { "rust-objcopy" } else { "" };
+    let default_target = if has_os {
+        format!("{}-linux-musl", os_config.platform.arch)
+    } else {
+        String::new()
+    };
+
+    ToolchainConfig {
+        cc: default_cc,
+        cxx: default_cxx,
+        ar: default_ar,
+        ld: default_ld,
+        objcopy: default_objcopy.to_string(),
+        sysroot: String::new(),
+        target: default_target,
+    }
+}
+
+/// Checks that every tool of the toolchain can be found
+/// # Notes
+/// Tools given explicitly in `[toolchain]` must exist, derived tools only emit a warning
+/// because they may not be needed by the project (e.g. `cxx` for a C-only project).
+fn validate_toolchain(
+    toolchain: &ToolchainTable,
+    toolchain_config: &ToolchainConfig,
+) -> Result<()> {
+    let tools = [
+        ("cc", &toolchain.cc, &toolchain_config.cc),
+        ("cxx", &toolchain.cxx, &toolchain_config.cxx),
+        ("ar", &toolchain.ar, &toolchain_config.ar),
+        ("ld", &toolchain.ld, &toolchain_config.ld),
+        ("objcopy", &toolchain.objcopy, &toolchain_config.objcopy),
+    ];
+    let mut missing = Vec::new();
+    for (field, explicit, tool) in tools {
+        let program = tool.split_whitespace().next().unwrap_or_default();
+        if program.is_empty() || find_program(program) {
+            continue;
+        }
+        if explicit.is_some() {
+            missing.push(format!("Toolchain {} not found: {}", field, program));
+        } else {
+            log(
+                LogLevel::Warn,
+                &format!("Default toolchain {} not found: {}", field, program),
+            );
+        }
+    }
+    if !toolchain_config.sysroot.is_empty() && !Path::new(&toolchain_config.sysroot).is_dir() {
+        missing.push(format!(
+            "Toolchain sysroot is not a directory: {}",
+            toolchain_config.sysroot
+        ));
+    }
+    if !missing.is_empty() {
+        return Err(Error::Toolchain(missing.join("\n")));
+    }
+    Ok(())
+}
+
+/// Checks whether a program exists, either as a path or in `PATH`
+fn find_program(program: &str) -> bool {
+    resolve_program(program).is_some()
+}
+
+/// Resolves a program to its path, searching the PATH for bare names
+pub fn resolve_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = Path::new(program);
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+/// Parses the OS configuration
+fn parse_os_config(os: Option<&OsTable>) -> Result<OSConfig> {
+    let os = match os {
+        Some(os) if *os != OsTable::default() => os,
+        _ => return Ok(OSConfig::default()),
+    };
+    let name = os.name.clone().unwrap_or_default();
+    let ulib = os.ulib.map(Ulib::as_str).unwrap_or_default().to_string();
+    let develop = os.develop.unwrap_or(YesNo::No).as_str().to_string();
+    let mut features = os.services.clone().unwrap_or_default();
+    let mut implied = Vec::new();
+    if features.iter().any(|feat| {
+        feat == "fs"
+            || feat == "net"
+            || feat == "pipe"
+            || feat == "select"
+            || feat == "poll"
+            || feat == "epoll"
+    }) {
+        implied.push("fd");
     }
+    if ulib == "ruxmusl" {
+        implied.extend(["musl", "fp_simd", "fd", "tls"]);
+    }
+    // Implied features are only added once, so that the features round-trip
+    for feat in implied {
+        if !features.iter().any(|f| f == feat) {
+            features.push(feat.to_string());
+        }
+    }
+    // Parse platform (if empty, it is the default value)
+    let platform = parse_platform(os.platform.as_ref())?;
 
-    os_config
+    Ok(OSConfig {
+        name,
+        features,
+        ulib,
+        develop,
+        platform,
+    })
 }
 
 /// Parses the targets configuration
+/// # Arguments
+/// * `config` - The config holding the `[[targets]]`
+/// * `build_config` - The build configuration
+/// * `member_dir` - The directory of a workspace member, whose target paths are rebased onto it
 fn parse_targets(
-    config: &Table,
+    targets: Option<&[TargetEntry]>,
     build_config: &BuildConfig,
-    check_dup_src: bool,
-) -> Vec<TargetConfig> {
+    member_dir: Option<&Path>,
+) -> Result<Vec<TargetConfig>> {
     let mut tgts = Vec::new();
-    let targets = config.get("targets").and_then(|v| v.as_array());
     if targets.is_none() && build_config.app.is_empty() {
-        log(
-            LogLevel::Error,
-            "Could not find targets or app in config file",
-        );
-        std::process::exit(1);
+        return Err(Error::Config(
+            "Could not find targets or app in config file".to_string(),
+        ));
     }
-    if let Some(targets) = targets {
-        for target in targets {
-            let target_tb = target.as_table().unwrap_or_else(|| {
-                log(LogLevel::Error, "Target is not a table");
-                std::process::exit(1);
-            });
-            // include_dir is compatible with both string and vector types
-            let include_dir = if let Some(value) = target_tb.get("include_dir") {
-                match value {
-                    Value::String(_s) => vec![parse_cfg_string(target_tb, "include_dir", "./")],
-                    Value::Array(_arr) => parse_cfg_vector(target_tb, "include_dir"),
-                    _ => {
-                        log(LogLevel::Error, "Invalid include_dir field");
-                        std::process::exit(1);
-                    }
-                }
-            } else {
-                vec!["./".to_owned()]
-            };
-            let target_config = TargetConfig {
-                name: parse_cfg_string(target_tb, "name", ""),
-                src: parse_cfg_string(target_tb, "src", ""),
-                src_only: parse_cfg_vector(target_tb, "src_only"),
-                src_exclude: parse_cfg_vector(target_tb, "src_exclude"),
-                include_dir,
-                typ: parse_cfg_string(target_tb, "type", ""),
-                cflags: parse_cfg_string(target_tb, "cflags", ""),
-                archive: parse_cfg_string(target_tb, "archive", ""),
-                linker: parse_cfg_string(target_tb, "linker", ""),
-                ldflags: parse_cfg_string(target_tb, "ldflags", ""),
-                deps: parse_cfg_vector(target_tb, "deps"),
-            };
-            if target_config.typ != "exe"
-                && target_config.typ != "dll"
-                && target_config.typ != "static"
-                && target_config.typ != "object"
-            {
-                log(LogLevel::Error, "Type must be exe, dll, object or static");
-                std::process::exit(1);
-            }
-            tgts.push(target_config);
+    for TargetEntry(target) in targets.unwrap_or_default() {
+        let mut target = parse_target(target)?;
+        if let Some(member_dir) = member_dir {
+            rebase_target(member_dir, &mut target);
         }
+        tgts.push(target);
     }
 
     if tgts.is_empty() && build_config.app.is_empty() {
-        log(LogLevel::Error, "No targets found!");
-        std::process::exit(1);
+        return Err(Error::Config("No targets found!".to_string()));
     }
 
     // Checks for duplicate target names
     let mut names_set = HashSet::new();
     for target in &tgts {
         if !names_set.insert(&target.name) {
-            log(
-                LogLevel::Error,
-                &format!("Duplicate target names found: {}", target.name),
-            );
-            std::process::exit(1);
+            return Err(Error::Config(format!(
+                "Duplicate target names found: {}",
+                target.name
+            )));
         }
     }
 
-    // Checks for duplicate srcs in the target
-    if check_dup_src {
-        log(
-            LogLevel::Info,
-            "Checking for duplicate srcs in all targets...",
-        );
-        for target in &tgts {
-            target.check_duplicate_srcs();
+    TargetConfig::arrange_targets(tgts)
+}
+
+/// Parses a `[[targets]]` table
+fn parse_target(target: &TargetTable) -> Result<TargetConfig> {
+    let name = target.name.clone().unwrap_or_default();
+    let typ = target
+        .typ
+        .ok_or_else(|| Error::Config(format!("Target '{}' has no type", name)))?;
+    let vector = |values: &Option<Vec<String>>| values.clone().unwrap_or_default();
+    let string = |value: &Option<String>| value.clone().unwrap_or_default();
+    let target_config = TargetConfig {
+        member: String::new(),
+        // src and include_dir are either a string or an array
+        src: target.src.as_ref().map(Strings::to_vec).unwrap_or_default(),
+        files: vector(&target.files),
+        src_only: vector(&target.src_only),
+        src_exclude: vector(&target.src_exclude),
+        src_match: target
+            .src_match
+            .unwrap_or(SrcMatch::Glob)
+            .as_str()
+            .to_string(),
+        include_dir: target
+            .include_dir
+            .as_ref()
+            .map_or_else(|| vec!["./".to_string()], Strings::to_vec),
+        typ: typ.as_str().to_string(),
+        cflags: string(&target.cflags),
+        archive: string(&target.archive),
+        linker: string(&target.linker),
+        ldflags: string(&target.ldflags),
+        deps: vector(&target.deps),
+        steps: parse_steps(&name, target.steps.as_deref().unwrap_or_default())?,
+        patches: vector(&target.patches),
+        name,
+    };
+    if target_config.src.is_empty() && target_config.files.is_empty() {
+        return Err(Error::Config(format!(
+            "Target '{}' has no src or files",
+            target_config.name
+        )));
+    }
+    // The patches are relative to a single directory, they cannot span several roots
+    if !target_config.patches.is_empty() && target_config.src.len() > 1 {
+        return Err(Error::Config(format!(
+            "Target '{}' has patches and several src roots, patches need a single src root",
+            target_config.name
+        )));
+    }
+    Ok(target_config)
+}
+
+/// Parses the sources to fetch before building
+fn parse_sources(sources: &[SourceTable]) -> Result<Vec<SourceConfig>> {
+    let mut source_configs = Vec::new();
+    let mut names_set = HashSet::new();
+    for source in sources {
+        if source.name.is_empty() || source.url.is_empty() {
+            return Err(Error::Config(
+                "Sources must have a name and a url".to_string(),
+            ));
         }
+        let archive = match source.archive {
+            Some(archive) => archive.as_str(),
+            None => SourceConfig::archive_from_url(&source.url),
+        };
+        let source_config = SourceConfig {
+            name: source.name.clone(),
+            url: source.url.clone(),
+            sha256: source.sha256.clone().unwrap_or_default().to_lowercase(),
+            archive: archive.to_string(),
+            strip_components: source.strip_components.unwrap_or(0) as usize,
+            dest: source.dest.clone().unwrap_or_else(|| source.name.clone()),
+            patches: source.patches.clone().unwrap_or_default(),
+        };
+        if !names_set.insert(source_config.name.clone()) {
+            return Err(Error::Config(format!(
+                "Duplicate source names found: {}",
+                source_config.name
+            )));
+        }
+        source_configs.push(source_config);
     }
+    Ok(source_configs)
+}
 
-    TargetConfig::arrange_targets(tgts)
+/// Parses the custom build steps of a target
+/// # Arguments
+/// * `target_name` - The name of the target, used in errors
+/// * `steps` - The `[[targets.steps]]` tables of the target
+fn parse_steps(target_name: &str, steps: &[StepTable]) -> Result<Vec<StepConfig>> {
+    let mut step_configs = Vec::new();
+    let mut names_set = HashSet::new();
+    for (index, step) in steps.iter().enumerate() {
+        let step_config = StepConfig {
+            name: step.name.clone().unwrap_or_else(|| index.to_string()),
+            command: step.command.clone(),
+            inputs: step.inputs.clone().unwrap_or_default(),
+            outputs: step.outputs.clone().unwrap_or_default(),
+            working_dir: step.working_dir.clone().unwrap_or_else(|| ".".to_string()),
+        };
+        if step_config.command.is_empty() {
+            return Err(Error::Config(format!(
+                "Step '{}' of target '{}' has no command",
+                step_config.name, target_name
+            )));
+        }
+        if !names_set.insert(step_config.name.clone()) {
+            return Err(Error::Config(format!(
+                "Duplicate step names found in target '{}': {}",
+                target_name, step_config.name
+            )));
+        }
+        step_configs.push(step_config);
+    }
+    Ok(step_configs)
 }
 
 /// Parses the platform configuration
-fn parse_platform(config: &Table) -> PlatformConfig {
-    let empty_platform = Value::Table(toml::map::Map::default());
-    let platform = config.get("platform").unwrap_or(&empty_platform);
-    if let Some(platform_table) = platform.as_table() {
-        let name = parse_cfg_string(platform_table, "name", "x86_64-qemu-q35");
-        let arch = name.split('-').next().unwrap_or("x86_64").to_string();
-        let cross_compile = format!("{}-linux-musl-", arch);
-        let target = match &arch[..] {
-            "x86_64" => "x86_64-unknown-none".to_string(),
-            "riscv64" => "riscv64gc-unknown-none-elf".to_string(),
-            "aarch64" => "aarch64-unknown-none-softfloat".to_string(),
-            _ => {
-                log(
-                    LogLevel::Error,
-                    "\"ARCH\" must be one of \"x86_64\", \"riscv64\", or \"aarch64\"",
-                );
-                std::process::exit(1);
-            }
-        };
-        let smp = parse_cfg_string(platform_table, "smp", "1");
-        let mode = parse_cfg_string(platform_table, "mode", "");
-        let log = parse_cfg_string(platform_table, "log", "warn");
-        let v = parse_cfg_string(platform_table, "v", "");
-        // determine whether enable qemu
-        let qemu: QemuConfig = if name.split('-').any(|s| s == "qemu") {
-            parse_qemu(&arch, platform_table)
-        } else {
-            QemuConfig::default()
-        };
-        PlatformConfig {
-            name,
-            arch,
-            cross_compile,
-            target,
-            smp,
-            mode,
-            log,
-            v,
-            qemu,
+fn parse_platform(platform: Option<&PlatformTable>) -> Result<PlatformConfig> {
+    let empty_platform = PlatformTable::default();
+    let platform = platform.unwrap_or(&empty_platform);
+    let name = platform
+        .name
+        .clone()
+        .unwrap_or_else(|| "x86_64-qemu-q35".to_string());
+    let arch = name.split('-').next().unwrap_or("x86_64").to_string();
+    let cross_compile = format!("{}-linux-musl-", arch);
+    let target = match &arch[..] {
+        "x86_64" => "x86_64-unknown-none".to_string(),
+        "riscv64" => "riscv64gc-unknown-none-elf".to_string(),
+        "aarch64" => "aarch64-unknown-none-softfloat".to_string(),
+        _ => {
+            return Err(Error::Config(
+                "\"ARCH\" must be one of \"x86_64\", \"riscv64\", or \"aarch64\"".to_string(),
+            ));
         }
+    };
+    let smp = platform.smp.clone().unwrap_or_else(|| "1".to_string());
+    let mode = platform
+        .mode
+        .map(Mode::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let log = platform
+        .log
+        .unwrap_or(OsLogLevel::Warn)
+        .as_str()
+        .to_string();
+    let v = platform.v.clone().unwrap_or_default();
+    // determine whether enable qemu
+    let qemu: QemuConfig = if name.split('-').any(|s| s == "qemu") {
+        parse_qemu(&arch, platform.qemu.as_ref())?
     } else {
-        log(LogLevel::Error, "Platform is not a table");
-        std::process::exit(1);
-    }
+        QemuConfig::default()
+    };
+    Ok(PlatformConfig {
+        name,
+        arch,
+        cross_compile,
+        target,
+        smp,
+        mode,
+        log,
+        v,
+        qemu,
+    })
 }
 
 /// Parses the qemu configuration
-fn parse_qemu(arch: &str, config: &Table) -> QemuConfig {
-    let empty_qemu = Value::Table(toml::map::Map::default());
-    let qemu = config.get("qemu").unwrap_or(&empty_qemu);
-    if let Some(qemu_table) = qemu.as_table() {
-        let debug = parse_cfg_string(qemu_table, "debug", "n");
-        let blk = parse_cfg_string(qemu_table, "blk", "n");
-        let net = parse_cfg_string(qemu_table, "net", "n");
-        let mem = parse_cfg_string(qemu_table, "memory", "128M");
-        let graphic = parse_cfg_string(qemu_table, "graphic", "n");
-        let bus = match arch {
-            "x86_64" => "pci".to_string(),
-            _ => "mmio".to_string(),
-        };
-        let disk_img = parse_cfg_string(qemu_table, "disk_img", "disk.img");
-        let v9p = parse_cfg_string(qemu_table, "v9p", "n");
-        let v9p_path = parse_cfg_string(qemu_table, "v9p_path", "./");
-        let accel_pre = match Command::new("uname").arg("-r").output() {
-            Ok(output) => {
-                let kernel_version = String::from_utf8_lossy(&output.stdout).to_lowercase();
-                if kernel_version.contains("-microsoft") {
-                    "n"
-                } else {
-                    "y"
-                }
-            }
-            Err(_) => {
-                log(LogLevel::Error, "Failed to execute command");
-                std::process::exit(1);
+fn parse_qemu(arch: &str, qemu: Option<&QemuTable>) -> Result<QemuConfig> {
+    let empty_qemu = QemuTable::default();
+    let qemu = qemu.unwrap_or(&empty_qemu);
+    let switch = |value: Option<YesNo>| value.unwrap_or(YesNo::No).as_str().to_string();
+    let string = |value: &Option<String>, default: &str| {
+        value.clone().unwrap_or_else(|| default.to_string())
+    };
+    let bus = match arch {
+        "x86_64" => "pci".to_string(),
+        _ => "mmio".to_string(),
+    };
+    let accel_pre = match Command::new("uname").arg("-r").output() {
+        Ok(output) => {
+            let kernel_version = String::from_utf8_lossy(&output.stdout).to_lowercase();
+            if kernel_version.contains("-microsoft") {
+                "n"
+            } else {
+                "y"
             }
-        };
-        let accel = match arch {
-            "x86_64" => accel_pre.to_string(),
-            _ => "n".to_string(),
-        };
-        let qemu_log = parse_cfg_string(qemu_table, "qemu_log", "n");
-        let net_dump = parse_cfg_string(qemu_table, "net_dump", "n");
-        let net_dev = parse_cfg_string(qemu_table, "net_dev", "user");
-        let ip = parse_cfg_string(qemu_table, "ip", "10.0.2.15");
-        let gw = parse_cfg_string(qemu_table, "gw", "10.0.2.2");
-        let args = parse_cfg_string(qemu_table, "args", "");
-        let envs = parse_cfg_string(qemu_table, "envs", "");
-        QemuConfig {
-            debug,
-            blk,
-            net,
-            mem,
-            graphic,
-            bus,
-            disk_img,
-            v9p,
-            v9p_path,
-            accel,
-            qemu_log,
-            net_dump,
-            net_dev,
-            ip,
-            gw,
-            args,
-            envs,
         }
-    } else {
-        log(LogLevel::Error, "Qemu is not a table");
-        std::process::exit(1);
-    }
+        Err(_) => {
+            return Err(Error::Config("Failed to execute command".to_string()));
+        }
+    };
+    let accel = match arch {
+        "x86_64" => accel_pre.to_string(),
+        _ => "n".to_string(),
+    };
+    Ok(QemuConfig {
+        debug: switch(qemu.debug),
+        blk: switch(qemu.blk),
+        net: switch(qemu.net),
+        mem: string(&qemu.memory, "128M"),
+        graphic: switch(qemu.graphic),
+        bus,
+        disk_img: string(&qemu.disk_img, "disk.img"),
+        v9p: switch(qemu.v9p),
+        v9p_path: string(&qemu.v9p_path, "./"),
+        accel,
+        qemu_log: switch(qemu.qemu_log),
+        net_dump: switch(qemu.net_dump),
+        net_dev: qemu.net_dev.unwrap_or(NetDev::User).as_str().to_string(),
+        ip: string(&qemu.ip, "10.0.2.15"),
+        gw: string(&qemu.gw, "10.0.2.2"),
+        args: string(&qemu.args, ""),
+        envs: string(&qemu.envs, ""),
+    })
 }
 
 /// Parses the configuration field of the string type
-fn parse_cfg_string(config: &Table, field: &str, default: &str) -> String {
-    let default_string = Value::String(default.to_string());
-    config
-        .get(field)
-        .unwrap_or(&default_string)
-        .as_str()
-        .unwrap_or_else(|| {
-            log(LogLevel::Error, &format!("{} is not a string", field));
-            std::process::exit(1);
-        })
-        .to_string()
-}
-
-/// Parses the configuration field of the vector type
-fn parse_cfg_vector(config: &Table, field: &str) -> Vec<String> {
-    let empty_vector = Value::Array(Vec::new());
-    config
-        .get(field)
-        .unwrap_or(&empty_vector)
-        .as_array()
-        .unwrap_or_else(|| {
-            log(LogLevel::Error, &format!("{} is not an array", field));
-            std::process::exit(1);
-        })
-        .iter()
-        .map(|value| {
-            value
-                .as_str()
-                .unwrap_or_else(|| {
-                    log(LogLevel::Error, &format!("{} elements are strings", field));
-                    std::process::exit(1);
-                })
-                .to_string()
-        })
-        .collect()
+fn parse_cfg_string(config: &Table, field: &str, default: &str) -> Result<String> {
+    match config.get(field) {
+        Some(value) => value
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| Error::Config(format!("{} is not a string", field))),
+        None => Ok(default.to_string()),
+    }
+}
+
+/// Inserts a string field into a config table, unless it is empty or has its default value
+fn insert_string(config: &mut Table, field: &str, value: &str, default: &str) {
+    if !value.is_empty() && value != default {
+        config.insert(field.to_string(), Value::String(value.to_string()));
+    }
+}
+
+/// Inserts a vector field into a config table, unless it is empty
+fn insert_vector(config: &mut Table, field: &str, values: &[String]) {
+    if !values.is_empty() {
+        config.insert(field.to_string(), Value::from(values.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates the interpolator of a config rooted at `/project`
+    fn interpolator(config: &str, build_dir: Option<&str>) -> Interpolator {
+        let mut config: Table = toml::from_str(config).unwrap();
+        Interpolator::new(&mut config, Path::new("/project"), build_dir).unwrap()
+    }
+
+    /// Creates the interpolator of a local config with an overridden build directory
+    fn interpolator_without_os() -> Interpolator {
+        interpolator("[build]\nout_dir = \"out\"\n", Some("/tmp/out"))
+    }
+
+    fn interpolate(interpolator: &Interpolator, s: &str) -> Result<String> {
+        interpolator.interpolate(s, "key", Some("app"), &mut Vec::new())
+    }
+
+    #[test]
+    fn builtin_variables() {
+        let interpolator = interpolator(
+            "[os]\nname = \"ruxos\"\n[os.platform]\nname = \"aarch64-qemu-virt\"\nmode = \"release\"\n",
+            None,
+        );
+        assert_eq!(
+            interpolate(&interpolator, "${arch}/${platform}/${mode}").unwrap(),
+            "aarch64/aarch64-qemu-virt/release"
+        );
+        assert_eq!(
+            interpolate(&interpolator, "${build_dir}/${target.name}").unwrap(),
+            "ruxgo_bld/app"
+        );
+    }
+
+    #[test]
+    fn unset_mode_is_debug() {
+        let interpolator = interpolator("[os]\nname = \"ruxos\"\n", None);
+        assert_eq!(interpolate(&interpolator, "${mode}").unwrap(), "debug");
+        let interpolator = interpolator_without_os();
+        assert_eq!(interpolate(&interpolator, "${mode}").unwrap(), "debug");
+        assert_eq!(interpolate(&interpolator, "${platform}").unwrap(), "host");
+    }
+
+    #[test]
+    fn build_dir_overrides_out_dir() {
+        let interpolator = interpolator_without_os();
+        assert_eq!(
+            interpolate(&interpolator, "${build_dir}").unwrap(),
+            "/tmp/out"
+        );
+    }
+
+    #[test]
+    fn user_variables_refer_to_each_other() {
+        let mut config: Table =
+            toml::from_str("[vars]\nversion = \"7.0\"\ndir = \"redis-${version}\"\njobs = 4\n")
+                .unwrap();
+        let interpolator = Interpolator::new(&mut config, Path::new("/project"), None).unwrap();
+        assert!(!config.contains_key("vars"));
+        assert_eq!(
+            interpolate(&interpolator, "./${dir}/src -j${jobs}").unwrap(),
+            "./redis-7.0/src -j4"
+        );
+    }
+
+    #[test]
+    fn escaped_and_plain_dollars() {
+        let interpolator = interpolator_without_os();
+        assert_eq!(
+            interpolate(&interpolator, "$${mode} costs $5 ${mode}").unwrap(),
+            "${mode} costs $5 debug"
+        );
+        assert_eq!(interpolate(&interpolator, "$$${mode}").unwrap(), "$${mode}");
+    }
+
+    #[test]
+    fn environment_variables() {
+        let interpolator = interpolator_without_os();
+        std::env::set_var("RUXGO_TEST_INTERP", "value");
+        assert_eq!(
+            interpolate(&interpolator, "${env:RUXGO_TEST_INTERP}").unwrap(),
+            "value"
+        );
+        assert_eq!(
+            interpolate(&interpolator, "${env:RUXGO_TEST_UNSET:-${mode}-x}").unwrap(),
+            "debug-x"
+        );
+        assert!(interpolate(&interpolator, "${env:RUXGO_TEST_UNSET}").is_err());
+    }
+
+    #[test]
+    fn invalid_variables() {
+        let interpolator = interpolator("[vars]\na = \"${b}\"\nb = \"${a}\"\n", None);
+        let error = interpolate(&interpolator, "${a}").unwrap_err().to_string();
+        assert!(error.contains("refers to itself"), "{}", error);
+        let error = interpolate(&interpolator, "${missing}")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Undefined variable 'missing' in 'key'"),
+            "{}",
+            error
+        );
+        assert!(interpolate(&interpolator, "${mode").is_err());
+        assert!(interpolator
+            .interpolate("${target.name}", "key", None, &mut Vec::new())
+            .is_err());
+
+        let mut config: Table = toml::from_str("[vars]\nmode = \"x\"\n").unwrap();
+        assert!(Interpolator::new(&mut config, Path::new("/project"), None).is_err());
+    }
+
+    #[test]
+    fn step_outputs_are_known_sources() {
+        let mut target = TargetConfig::new("app", "exe");
+        target.files = vec!["./ruxgo-missing-gen/main.c".to_string()];
+        assert!(target.get_src_names().is_err());
+        target.steps.push(StepConfig {
+            name: "gen".to_string(),
+            command: "true".to_string(),
+            inputs: Vec::new(),
+            outputs: vec!["ruxgo-missing-gen".to_string()],
+            working_dir: ".".to_string(),
+        });
+        assert_eq!(
+            target.get_src_names().unwrap(),
+            vec!["./ruxgo-missing-gen/main.c"]
+        );
+    }
 }
@@ -0,0 +1,374 @@
+//! This module contains functions for applying and reverting patch queues.
+
+use crate::error::{Error, Result};
+use crate::hasher::Hasher;
+use crate::report::{PatchReport, PatchStatus};
+use crate::utils::log::{log, LogLevel};
+use crate::utils::paths::BuildPaths;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path};
+use std::process::{Command, Stdio};
+
+/// File in the patch directory recording the applied patches
+const PATCH_STATE: &str = "state.json";
+
+/// Directory in the patch directory holding the files as they were before patching
+const ORIGINALS: &str = "originals";
+
+/// Struct describing a patch applied to a directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AppliedPatch {
+    pub path: String,
+    pub hash: String,
+}
+
+/// Struct describing the patches applied to a directory, in order
+/// # Notes
+/// The state is kept under the build directory, together with a copy of every file
+/// the patches touch as it was before the first patch, so that the directory can be
+/// restored even after a patch file changed or was removed from the config.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PatchState {
+    pub applied: Vec<AppliedPatch>,
+    /// The files touched by the applied patches, and whether they existed before
+    pub files: BTreeMap<String, bool>,
+}
+
+impl PatchState {
+    /// Loads the patch state of a directory
+    /// # Arguments
+    /// * `paths` - The paths of the build directory
+    /// * `dir` - The patched directory
+    pub fn load(paths: &BuildPaths, dir: &str) -> Result<PatchState> {
+        let path = Path::new(&paths.patch_dir(dir)).join(PATCH_STATE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Ok(PatchState::default()),
+        };
+        serde_json::from_str(&contents).map_err(|why| {
+            Error::Patch(format!(
+                "Patch state '{}' is corrupted: {}",
+                path.display(),
+                why
+            ))
+        })
+    }
+
+    /// Saves the patch state of a directory
+    /// # Arguments
+    /// * `paths` - The paths of the build directory
+    /// * `dir` - The patched directory
+    pub fn save(&self, paths: &BuildPaths, dir: &str) -> Result<()> {
+        let path = Path::new(&paths.patch_dir(dir)).join(PATCH_STATE);
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|why| Error::Patch(format!("Failed to serialize patch state: {}", why)))?;
+        Hasher::write_atomic(&path, contents.as_bytes())
+    }
+
+    /// Returns the number of leading applied patches that match the configured ones
+    /// # Arguments
+    /// * `patches` - The configured patches
+    pub fn num_unchanged(&self, patches: &[String]) -> usize {
+        self.applied
+            .iter()
+            .zip(patches)
+            .take_while(|(applied, patch)| {
+                applied.path == **patch && applied.hash == hash_patch(patch)
+            })
+            .count()
+    }
+
+    /// Returns whether an applied patch changed or is no longer configured
+    /// # Arguments
+    /// * `patches` - The configured patches
+    pub fn is_stale(&self, patches: &[String]) -> bool {
+        self.num_unchanged(patches) < self.applied.len()
+    }
+}
+
+/// Applies the configured patches to a directory in order
+/// # Notes
+/// Patches that are already applied are skipped. If an applied patch changed or is no
+/// longer configured, the directory is restored to its original files first and all
+/// patches are applied again.
+/// # Arguments
+/// * `paths` - The paths of the build directory
+/// * `dir` - The directory to patch
+/// * `patches` - The patch files, relative to the project root, applied with `-p1`
+pub fn sync_patches(paths: &BuildPaths, dir: &str, patches: &[String]) -> Result<()> {
+    let mut state = PatchState::load(paths, dir)?;
+    let mut num_unchanged = state.num_unchanged(patches);
+    if num_unchanged == state.applied.len() && num_unchanged == patches.len() {
+        if !patches.is_empty() {
+            log(
+                LogLevel::Info,
+                &format!("Patches of '{}' are up to date", dir),
+            );
+        }
+        return Ok(());
+    }
+    if num_unchanged < state.applied.len() {
+        revert_patches(paths, dir)?;
+        state = PatchState::default();
+        num_unchanged = 0;
+    }
+    let originals = Path::new(&paths.patch_dir(dir)).join(ORIGINALS);
+    for patch in &patches[num_unchanged..] {
+        let contents = fs::read_to_string(patch)
+            .map_err(|why| Error::io(format!("Could not read patch '{}'", patch), why))?;
+        // The originals are saved before patching, so that an interrupted patch is restored
+        for file in patched_files(&contents) {
+            if state.files.contains_key(&file) {
+                continue;
+            }
+            let path = Path::new(dir).join(&file);
+            let exists = path.is_file();
+            if exists {
+                let original = originals.join(&file);
+                original
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::copy(&path, &original))
+                    .map_err(|why| {
+                        Error::io(format!("Could not save '{}'", path.display()), why)
+                    })?;
+            }
+            state.files.insert(file, exists);
+        }
+        state.save(paths, dir)?;
+        log(
+            LogLevel::Log,
+            &format!("Applying patch '{}' to {}", patch, dir),
+        );
+        if let Err(output) = run_patch(dir, &contents) {
+            return Err(Error::Patch(format!(
+                "Failed to apply patch '{}' to '{}':\n{}",
+                patch, dir, output
+            )));
+        }
+        state.applied.push(AppliedPatch {
+            path: patch.clone(),
+            hash: Hasher::hash_string(&contents),
+        });
+        state.save(paths, dir)?;
+    }
+    Ok(())
+}
+
+/// Reverts all patches applied to a directory by restoring its original files
+/// # Arguments
+/// * `paths` - The paths of the build directory
+/// * `dir` - The patched directory
+pub fn revert_patches(paths: &BuildPaths, dir: &str) -> Result<()> {
+    let state = PatchState::load(paths, dir)?;
+    if !state.files.is_empty() {
+        log(
+            LogLevel::Log,
+            &format!("Restoring the original files of {}", dir),
+        );
+    }
+    let originals = Path::new(&paths.patch_dir(dir)).join(ORIGINALS);
+    for (file, existed) in &state.files {
+        let path = Path::new(dir).join(file);
+        let restored = if *existed {
+            // The patch may have removed the file together with its directory
+            path.parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::copy(originals.join(file), &path))
+                .map(|_| ())
+        } else if path.exists() {
+            fs::remove_file(&path)
+        } else {
+            Ok(())
+        };
+        restored
+            .map_err(|why| Error::io(format!("Could not restore '{}'", path.display()), why))?;
+    }
+    forget_patches(paths, dir)
+}
+
+/// Removes the patch state of a directory without touching it
+/// # Notes
+/// Used when the directory is replaced by a fresh copy.
+/// # Arguments
+/// * `paths` - The paths of the build directory
+/// * `dir` - The patched directory
+pub fn forget_patches(paths: &BuildPaths, dir: &str) -> Result<()> {
+    let patch_dir = paths.patch_dir(dir);
+    if Path::new(&patch_dir).exists() {
+        fs::remove_dir_all(&patch_dir)
+            .map_err(|why| Error::io(format!("Could not remove '{}'", patch_dir), why))?;
+    }
+    Ok(())
+}
+
+/// Returns the status of the configured and applied patches of a directory
+/// # Arguments
+/// * `paths` - The paths of the build directory
+/// * `dir` - The patched directory
+/// * `patches` - The configured patches
+pub fn status(paths: &BuildPaths, dir: &str, patches: &[String]) -> Result<PatchReport> {
+    let state = PatchState::load(paths, dir)?;
+    let mut report = PatchReport {
+        dir: dir.to_string(),
+        patches: Vec::new(),
+    };
+    for patch in patches {
+        let status = match state.applied.iter().find(|applied| applied.path == *patch) {
+            Some(applied) if applied.hash == hash_patch(patch) => PatchStatus::Applied,
+            Some(_) => PatchStatus::Changed,
+            None => PatchStatus::Pending,
+        };
+        report.patches.push((patch.clone(), status));
+    }
+    for applied in &state.applied {
+        if !patches.contains(&applied.path) {
+            report
+                .patches
+                .push((applied.path.clone(), PatchStatus::Stale));
+        }
+    }
+    Ok(report)
+}
+
+/// Returns the files a patch touches, relative to the patched directory
+/// # Notes
+/// Both the old and the new name of a file are returned, with the first component
+/// stripped like `-p1` does.
+fn patched_files(contents: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    for line in contents.lines() {
+        let Some(name) = line
+            .strip_prefix("--- ")
+            .or_else(|| line.strip_prefix("+++ "))
+        else {
+            continue;
+        };
+        // The name ends at a tab, followed by an optional timestamp
+        let name = name.split('\t').next().unwrap_or_default().trim_end();
+        if name == "/dev/null" {
+            continue;
+        }
+        let Some((_, file)) = name.split_once('/') else {
+            continue;
+        };
+        // `patch` refuses names leading outside of the directory as well
+        let inside = Path::new(file)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if inside && !file.is_empty() && !files.iter().any(|f| f == file) {
+            files.push(file.to_string());
+        }
+    }
+    files
+}
+
+/// Returns the hash of a patch file, empty if it cannot be read
+fn hash_patch(patch: &str) -> String {
+    fs::read_to_string(patch)
+        .map(|contents| Hasher::hash_string(&contents))
+        .unwrap_or_default()
+}
+
+/// Runs `patch` on a directory, checking with a dry run first so that a failing
+/// patch leaves the directory untouched
+fn run_patch(dir: &str, contents: &str) -> std::result::Result<(), String> {
+    for dry_run in [true, false] {
+        let mut args = vec!["-p1", "--batch", "--forward", "-d", dir];
+        if dry_run {
+            args.push("--dry-run");
+        }
+        let mut child = Command::new("patch")
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|why| format!("Failed to execute patch: {}", why))?;
+        child
+            .stdin
+            .take()
+            .ok_or("Failed to open the input of patch")?
+            .write_all(contents.as_bytes())
+            .map_err(|why| format!("Failed to write patch: {}", why))?;
+        let output = child
+            .wait_with_output()
+            .map_err(|why| format!("Failed to wait for patch: {}", why))?;
+        if !output.status.success() {
+            return Err(format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::OSConfig;
+    use crate::utils::test_dir;
+
+    fn patch(value: &str) -> String {
+        format!(
+            "--- a/a.c\n+++ b/a.c\n@@ -1 +1 @@\n-int a;\n+int a = {};\n\
+             --- /dev/null\n+++ b/new.c\n@@ -0,0 +1 @@\n+int b;\n",
+            value
+        )
+    }
+
+    #[test]
+    fn patched_files_strips_the_first_component() {
+        let files = patched_files(&format!(
+            "{}--- a/../x.c\t2024-01-01\n+++ b/sub/y.c\t2024-01-01\n",
+            patch("1")
+        ));
+        assert_eq!(files, vec!["a.c", "new.c", "sub/y.c"]);
+    }
+
+    #[test]
+    fn applies_restores_and_reapplies_patches() {
+        let tmp = test_dir("patches-queue");
+        let dir = tmp.path();
+        let src = dir.join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a.c"), "int a;\n").unwrap();
+        let patch_file = dir.join("a.patch").to_string_lossy().to_string();
+        fs::write(&patch_file, patch("1")).unwrap();
+        let paths = BuildPaths::new(&dir.join("build").to_string_lossy(), &OSConfig::default());
+        let src_dir = src.to_string_lossy().to_string();
+        let patches = vec![patch_file.clone()];
+        let statuses = |patches: &[String]| -> Vec<PatchStatus> {
+            let report = status(&paths, &src_dir, patches).unwrap();
+            report.patches.into_iter().map(|(_, s)| s).collect()
+        };
+
+        assert_eq!(statuses(&patches), vec![PatchStatus::Pending]);
+        sync_patches(&paths, &src_dir, &patches).unwrap();
+        sync_patches(&paths, &src_dir, &patches).unwrap();
+        assert_eq!(fs::read_to_string(src.join("a.c")).unwrap(), "int a = 1;\n");
+        assert!(src.join("new.c").is_file());
+        assert_eq!(statuses(&patches), vec![PatchStatus::Applied]);
+        // The state lives in the build directory, not in the patched sources
+        assert_eq!(fs::read_dir(&src).unwrap().count(), 2);
+        assert!(Path::new(&paths.patch_dir(&src_dir)).is_dir());
+
+        // A changed patch is applied to the restored original files
+        fs::write(&patch_file, patch("2")).unwrap();
+        assert_eq!(statuses(&patches), vec![PatchStatus::Changed]);
+        sync_patches(&paths, &src_dir, &patches).unwrap();
+        assert_eq!(fs::read_to_string(src.join("a.c")).unwrap(), "int a = 2;\n");
+
+        assert_eq!(statuses(&[]), vec![PatchStatus::Stale]);
+        revert_patches(&paths, &src_dir).unwrap();
+        assert_eq!(fs::read_to_string(src.join("a.c")).unwrap(), "int a;\n");
+        assert!(!src.join("new.c").exists());
+        assert!(!Path::new(&paths.patch_dir(&src_dir)).exists());
+        assert!(statuses(&[]).is_empty());
+    }
+}
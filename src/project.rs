@@ -0,0 +1,289 @@
+//! Project Module
+
+use crate::commands;
+use crate::error::{Error, Result};
+use crate::parser::{self, BuildConfig, OSConfig, SourceConfig, TargetConfig, ToolchainConfig};
+use crate::report::{BuildReport, CleanReport, RunReport};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use toml::{Table, Value};
+
+/// The path reported in the errors of configs that are not read from a file
+static IN_MEMORY_CONFIG: &str = "<project>";
+
+/// Struct describing a parsed project, the programmatic counterpart of a config file
+/// # Notes
+/// Relative paths are resolved against the current directory, like the paths of a
+/// config file in the current directory. A project is (de)serialized as the contents of
+/// a config file, see `Project::to_table`.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub build_config: BuildConfig,
+    pub os_config: OSConfig,
+    /// The targets in build order, without the loader of `app`
+    pub targets: Vec<TargetConfig>,
+}
+
+impl Project {
+    /// Returns a builder describing a project in Rust
+    pub fn builder() -> ProjectBuilder {
+        ProjectBuilder::default()
+    }
+
+    /// Loads the project of a config file
+    /// # Arguments
+    /// * `path` - The path to the config file
+    /// * `build_dir` - Overrides the build directory given by `[build] out_dir`
+    pub fn load(path: &str, build_dir: Option<&str>) -> Result<Self> {
+        let (build_config, os_config, targets) = parser::parse_config(path, build_dir)?;
+        Ok(Project {
+            build_config,
+            os_config,
+            targets,
+        })
+    }
+
+    /// Parses a project from the contents of a config file
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let (build_config, os_config, targets) =
+            parser::parse_config_str(contents, IN_MEMORY_CONFIG, None)?;
+        Ok(Project {
+            build_config,
+            os_config,
+            targets,
+        })
+    }
+
+    /// Parses a project from the table of a config file
+    pub fn from_table(config: Table) -> Result<Self> {
+        let contents = toml::to_string(&config)
+            .map_err(|e| Error::Config(format!("Could not write the config: {}", e)))?;
+        Project::from_toml(&contents)
+    }
+
+    /// Returns the contents of the config file describing the project
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(&self.to_table()?)
+            .map_err(|e| Error::Config(format!("Could not write the config: {}", e)))
+    }
+
+    /// Returns the table of the config file describing the project
+    /// # Notes
+    /// Values holding their default are left out and `${` is escaped, so that parsing
+    /// the table again gives the same project. A workspace cannot be written, because
+    /// its members have config files of their own.
+    pub fn to_table(&self) -> Result<Table> {
+        if !self.build_config.workspace.is_empty() {
+            return Err(Error::Config(
+                "A workspace cannot be written as a single config".to_string(),
+            ));
+        }
+        let mut config = self.build_config.to_table(&self.os_config);
+        let os = self.os_config.to_table();
+        if !os.is_empty() {
+            config.insert("os".to_string(), Value::Table(os));
+        }
+        if !self.targets.is_empty() {
+            let targets = self.targets.iter().map(|t| Value::Table(t.to_table()));
+            config.insert("targets".to_string(), Value::Array(targets.collect()));
+        }
+        config.iter_mut().for_each(|(_, value)| escape_vars(value));
+        Ok(config)
+    }
+
+    /// Builds all targets of the project
+    /// Returns the artifacts of the targets and the compiler warnings.
+    pub fn build(&self) -> Result<BuildReport> {
+        let targets = self.prepared_targets()?;
+        commands::build(&self.build_config, &targets, &self.os_config, false)
+    }
+
+    /// Runs the executable of the project, on the host or in qemu
+    /// # Arguments
+    /// * `bin_args` - Arguments passed to the executable
+    pub fn run(&self, bin_args: &[&str]) -> Result<RunReport> {
+        let targets = self.prepared_targets()?;
+        let exe_target = commands::exe_target(&targets)?;
+        commands::run(
+            Some(bin_args.to_vec()),
+            &self.build_config,
+            &self.os_config,
+            exe_target,
+            &targets,
+        )
+    }
+
+    /// Cleans the outputs of the project
+    /// # Arguments
+    /// * `choices` - The parts to remove, any of `All`, `App_bins`, `Obj`, `OS` and `Ulib`
+    pub fn clean(&self, choices: &[&str]) -> Result<CleanReport> {
+        let targets = self.prepared_targets()?;
+        let choices = choices.iter().map(|choice| choice.to_string()).collect();
+        Ok(commands::clean(
+            &self.build_config,
+            &targets,
+            &self.os_config,
+            choices,
+        ))
+    }
+
+    /// Returns the targets including the loader of `app`
+    fn prepared_targets(&self) -> Result<Vec<TargetConfig>> {
+        commands::prepare_targets(&self.build_config, &self.os_config, self.targets.clone())
+    }
+}
+
+impl Serialize for Project {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.to_table()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Project {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Project::from_table(Table::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Struct building a project in Rust instead of a config file
+/// # Notes
+/// The builder writes the config it describes and parses it like a config file, so
+/// that the project gets the same validation and defaults. Values are taken literally,
+/// `${...}` is not interpolated.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectBuilder {
+    build: Table,
+    toolchain: ToolchainConfig,
+    sources: Vec<SourceConfig>,
+    targets: Vec<TargetConfig>,
+    os_config: OSConfig,
+    offline: bool,
+}
+
+impl ProjectBuilder {
+    /// Sets the compiler, e.g. `gcc` or `clang`
+    pub fn compiler(mut self, compiler: &str) -> Self {
+        self.build
+            .insert("compiler".to_string(), Value::from(compiler));
+        self
+    }
+
+    /// Sets the application started by the loader
+    pub fn app(mut self, app: &str) -> Self {
+        self.build.insert("app".to_string(), Value::from(app));
+        self
+    }
+
+    /// Sets the directory of all build outputs
+    pub fn out_dir(mut self, out_dir: &str) -> Self {
+        self.build
+            .insert("out_dir".to_string(), Value::from(out_dir));
+        self
+    }
+
+    /// Sets the toolchain, empty tools are derived from the compiler and the platform
+    pub fn toolchain(mut self, toolchain: ToolchainConfig) -> Self {
+        self.toolchain = toolchain;
+        self
+    }
+
+    /// Adds a source archive fetched before building
+    pub fn source(mut self, source: SourceConfig) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Adds a target, see `TargetConfig::new`
+    pub fn target(mut self, target: TargetConfig) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    /// Sets the OS the targets are built for, see `OSConfig::new`
+    /// # Notes
+    /// Empty fields of the OS config and its platform get their defaults.
+    pub fn os(mut self, os_config: OSConfig) -> Self {
+        self.os_config = os_config;
+        self
+    }
+
+    /// Only uses cached sources, like `--offline`
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Parses the described config into a project
+    pub fn finish(self) -> Result<Project> {
+        let mut config = Table::new();
+        config.insert("build".to_string(), Value::Table(self.build));
+        let toolchain = [
+            ("cc", self.toolchain.cc),
+            ("cxx", self.toolchain.cxx),
+            ("ar", self.toolchain.ar),
+            ("ld", self.toolchain.ld),
+            ("objcopy", self.toolchain.objcopy),
+            ("sysroot", self.toolchain.sysroot),
+            ("target", self.toolchain.target),
+        ];
+        let toolchain: Table = toolchain
+            .into_iter()
+            .filter(|(_, tool)| !tool.is_empty())
+            .map(|(field, tool)| (field.to_string(), Value::String(tool)))
+            .collect();
+        if !toolchain.is_empty() {
+            config.insert("toolchain".to_string(), Value::Table(toolchain));
+        }
+        if !self.sources.is_empty() {
+            let sources = self.sources.iter().map(|s| Value::Table(s.to_table()));
+            config.insert("sources".to_string(), Value::Array(sources.collect()));
+        }
+        if !self.targets.is_empty() {
+            let targets = self.targets.iter().map(|t| Value::Table(t.to_table()));
+            config.insert("targets".to_string(), Value::Array(targets.collect()));
+        }
+        let os = self.os_config.to_table();
+        if !os.is_empty() {
+            config.insert("os".to_string(), Value::Table(os));
+        }
+        config.iter_mut().for_each(|(_, value)| escape_vars(value));
+
+        let mut project = Project::from_table(config)?;
+        project.build_config.offline = self.offline;
+        Ok(project)
+    }
+}
+
+/// Escapes the `${` of all strings of a config value, so that they are not interpolated
+fn escape_vars(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = s.replace("${", "$${"),
+        Value::Array(arr) => arr.iter_mut().for_each(escape_vars),
+        Value::Table(table) => table.iter_mut().for_each(|(_, value)| escape_vars(value)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use walkdir::WalkDir;
+
+    #[test]
+    fn app_configs_round_trip() {
+        let mut configs: Vec<String> = WalkDir::new("apps")
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name() == "config_linux.toml")
+            .map(|entry| entry.path().to_string_lossy().to_string())
+            .collect();
+        configs.sort();
+        assert!(!configs.is_empty());
+        for config in configs {
+            let contents = Project::load(&config, None).unwrap().to_toml().unwrap();
+            let reparsed = Project::from_toml(&contents).unwrap().to_toml().unwrap();
+            assert_eq!(contents, reparsed, "{}", config);
+        }
+    }
+}
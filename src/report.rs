@@ -0,0 +1,96 @@
+//! Report Module
+
+use serde::Serialize;
+
+/// Enum describing the severity of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Struct describing a diagnostic emitted while building or cleaning
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The target the diagnostic belongs to, empty if it belongs to none
+    pub target: String,
+    /// The file the diagnostic is about
+    pub file: String,
+    pub message: String,
+}
+
+/// Struct describing the output of a target
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Artifact {
+    pub target: String,
+    /// The type of the target, `exe`, `dll`, `static` or `object`
+    pub typ: String,
+    pub path: String,
+    /// The ELF image of an executable, empty for the other types
+    pub elf_path: String,
+    /// Whether the artifact was linked by this build, false if it was up to date
+    pub linked: bool,
+}
+
+/// Struct describing the outcome of a build
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BuildReport {
+    /// The artifacts of all targets, in build order
+    pub artifacts: Vec<Artifact>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl BuildReport {
+    /// Appends the artifacts and diagnostics of another report
+    pub fn merge(&mut self, other: BuildReport) {
+        self.artifacts.extend(other.artifacts);
+        self.diagnostics.extend(other.diagnostics);
+    }
+
+    /// Returns the artifact of a target
+    pub fn artifact(&self, target: &str) -> Option<&Artifact> {
+        self.artifacts
+            .iter()
+            .find(|artifact| artifact.target == target)
+    }
+}
+
+/// Struct describing the outcome of running the executable
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunReport {
+    /// The command line that was run, on the host or in qemu
+    pub command: String,
+    /// The exit code, `None` if the process was killed by a signal
+    pub exit_code: Option<i32>,
+}
+
+/// Struct describing the outcome of a clean
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CleanReport {
+    /// The files and directories that were removed
+    pub removed: Vec<String>,
+    /// The files and directories that could not be removed
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Enum describing the state of a configured or applied patch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchStatus {
+    Applied,
+    /// Applied, but the patch file changed since
+    Changed,
+    Pending,
+    /// Applied, but no longer configured
+    Stale,
+}
+
+/// Struct describing the patches of a patched directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PatchReport {
+    pub dir: String,
+    /// The patches in order, the stale ones last
+    pub patches: Vec<(String, PatchStatus)>,
+}
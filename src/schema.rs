@@ -0,0 +1,1163 @@
+//! Config Schema Module
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::de::{
+    self, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use toml::Spanned;
+
+/// A ruxgo.toml config file
+// The parser deserializes the merged and interpolated config into this type, and the
+// JSON Schema is generated from it.
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(title = "ruxgo config")]
+pub struct ConfigFile {
+    /// Config files merged before this one
+    pub include: Option<Strings>,
+    /// The workspace members
+    pub workspace: Option<WorkspaceTable>,
+    /// Variables used as ${name}
+    pub vars: Option<BTreeMap<String, Scalar>>,
+    /// Target templates used with extends
+    pub templates: Option<BTreeMap<String, TargetTable>>,
+    /// The build options
+    pub build: Option<BuildTable>,
+    /// The toolchain
+    pub toolchain: Option<ToolchainTable>,
+    /// Archives fetched before building
+    pub sources: Option<Vec<SourceTable>>,
+    /// The build targets
+    pub targets: Option<Vec<TargetEntry>>,
+    /// The OS the targets are built for
+    pub os: Option<OsTable>,
+}
+
+/// A string or an array of strings
+#[derive(Clone, JsonSchema)]
+#[schemars(untagged)]
+pub enum Strings {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Strings {
+    /// Returns the strings, a single string being a list of one
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Strings::One(s) => vec![s.clone()],
+            Strings::Many(strings) => strings.clone(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Strings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StringsVisitor;
+
+        impl<'de> Visitor<'de> for StringsVisitor {
+            type Value = Strings;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string or an array of strings")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Strings, E> {
+                Ok(Strings::One(value.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Strings, A::Error> {
+                Vec::deserialize(de::value::SeqAccessDeserializer::new(seq)).map(Strings::Many)
+            }
+        }
+
+        deserializer.deserialize_any(StringsVisitor)
+    }
+}
+
+/// A string, number or boolean
+#[derive(JsonSchema)]
+#[schemars(untagged)]
+pub enum Scalar {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl<'de> Deserialize<'de> for Scalar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ScalarVisitor;
+
+        impl<'de> Visitor<'de> for ScalarVisitor {
+            type Value = Scalar;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string, an integer, a float or a boolean")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Scalar, E> {
+                Ok(Scalar::String(value.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Scalar, E> {
+                Ok(Scalar::Integer(value))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Scalar, E> {
+                Ok(Scalar::Integer(value as i64))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Scalar, E> {
+                Ok(Scalar::Float(value))
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<Scalar, E> {
+                Ok(Scalar::Boolean(value))
+            }
+        }
+
+        deserializer.deserialize_any(ScalarVisitor)
+    }
+}
+
+/// The `[workspace]` table
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceTable {
+    /// Member directories, each with its own ruxgo.toml
+    pub members: Vec<String>,
+}
+
+/// The `[build]` table
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BuildTable {
+    /// The compiler, e.g. gcc or clang
+    pub compiler: Option<String>,
+    /// A prebuilt application run by the loader
+    pub app: Option<String>,
+    /// The build directory, ruxgo_bld by default
+    pub out_dir: Option<String>,
+}
+
+/// The `[toolchain]` table
+#[derive(Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ToolchainTable {
+    /// The C compiler
+    pub cc: Option<String>,
+    /// The C++ compiler
+    pub cxx: Option<String>,
+    /// The archiver of static libraries
+    pub ar: Option<String>,
+    /// The linker
+    pub ld: Option<String>,
+    /// The objcopy tool
+    pub objcopy: Option<String>,
+    /// The sysroot passed to the compilers
+    pub sysroot: Option<String>,
+    /// The target triple passed to clang
+    pub target: Option<String>,
+}
+
+/// A `[[sources]]` table
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SourceTable {
+    /// The unique name of the source
+    pub name: String,
+    /// A remote url, a file:// url or a local path
+    pub url: String,
+    /// The sha256 of the archive
+    pub sha256: Option<String>,
+    /// The archive format, derived from the url by default
+    pub archive: Option<Archive>,
+    /// The number of leading path components stripped when extracting
+    pub strip_components: Option<u32>,
+    /// The extraction directory, the name by default
+    pub dest: Option<String>,
+    /// Patches applied after extracting
+    pub patches: Option<Vec<String>>,
+}
+
+/// A `[[targets.steps]]` table
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StepTable {
+    /// The unique name of the step in the target
+    pub name: Option<String>,
+    /// The shell command to run
+    pub command: String,
+    /// Files or directories the step reads
+    pub inputs: Option<Vec<String>>,
+    /// Files or directories the step writes
+    pub outputs: Option<Vec<String>>,
+    /// The directory the command runs in
+    pub working_dir: Option<String>,
+}
+
+/// A `[[targets]]` table or a template
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TargetTable {
+    /// The unique name of the target
+    pub name: Option<String>,
+    /// The type of the target
+    #[serde(rename = "type")]
+    pub typ: Option<TargetType>,
+    /// Directories searched for sources, recursively
+    pub src: Option<Strings>,
+    /// Source files compiled in addition to src
+    pub files: Option<Vec<String>>,
+    /// Patterns selecting the files of src
+    pub src_only: Option<Vec<String>>,
+    /// Patterns excluding files of src
+    pub src_exclude: Option<Vec<String>>,
+    /// How src_only and src_exclude are matched
+    pub src_match: Option<SrcMatch>,
+    /// Include directories
+    pub include_dir: Option<Strings>,
+    /// Compiler flags
+    pub cflags: Option<String>,
+    /// The archiver of a static target
+    pub archive: Option<String>,
+    /// The linker of the target
+    pub linker: Option<String>,
+    /// Linker or archiver flags
+    pub ldflags: Option<String>,
+    /// Targets the target depends on
+    pub deps: Option<Vec<String>>,
+    /// Patches applied to the single src root
+    pub patches: Option<Vec<String>>,
+    /// Commands run before compiling
+    pub steps: Option<Vec<StepTable>>,
+    /// Templates the target extends
+    pub extends: Option<Strings>,
+}
+
+/// A `[[targets]]` table, which needs a name and a type unless it extends a template
+/// that may provide them
+pub struct TargetEntry(pub TargetTable);
+
+impl<'de> Deserialize<'de> for TargetEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let target = TargetTable::deserialize(deserializer)?;
+        if target.extends.is_none() {
+            if target.name.is_none() {
+                return Err(de::Error::missing_field("name"));
+            }
+            if target.typ.is_none() {
+                return Err(de::Error::missing_field("type"));
+            }
+        }
+        Ok(TargetEntry(target))
+    }
+}
+
+impl JsonSchema for TargetEntry {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        "TargetEntry".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let required = |keys: &[&str]| {
+            let mut schema = SchemaObject::default();
+            schema.object().required = keys.iter().map(|key| key.to_string()).collect();
+            Schema::from(schema)
+        };
+        let mut schema = SchemaObject::default();
+        schema.subschemas().all_of = Some(vec![gen.subschema_for::<TargetTable>()]);
+        schema.subschemas().any_of =
+            Some(vec![required(&["name", "type"]), required(&["extends"])]);
+        schema.into()
+    }
+}
+
+/// The `[os]` table
+#[derive(Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OsTable {
+    /// The OS, e.g. ruxos
+    pub name: Option<String>,
+    /// The user library
+    pub ulib: Option<Ulib>,
+    /// Rebuild the OS on every build
+    pub develop: Option<YesNo>,
+    /// The OS features, e.g. fs or net
+    pub services: Option<Vec<String>>,
+    /// The platform options
+    pub platform: Option<PlatformTable>,
+}
+
+/// The `[os.platform]` table
+#[derive(Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformTable {
+    /// The platform, e.g. x86_64-qemu-q35
+    pub name: Option<String>,
+    /// The number of CPUs
+    pub smp: Option<String>,
+    /// The build mode
+    pub mode: Option<Mode>,
+    /// The log level of the OS
+    pub log: Option<LogLevel>,
+    /// The verbosity of the OS build
+    pub v: Option<String>,
+    /// The QEMU options
+    pub qemu: Option<QemuTable>,
+}
+
+/// The `[os.platform.qemu]` table
+#[derive(Default, PartialEq, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct QemuTable {
+    /// Wait for a debugger
+    pub debug: Option<YesNo>,
+    /// Attach a block device
+    pub blk: Option<YesNo>,
+    /// Attach a network device
+    pub net: Option<YesNo>,
+    /// Enable the graphic output
+    pub graphic: Option<YesNo>,
+    /// The memory size, e.g. 128M
+    pub memory: Option<String>,
+    /// The disk image of the block device
+    pub disk_img: Option<String>,
+    /// Share a directory through 9p
+    pub v9p: Option<YesNo>,
+    /// The directory shared through 9p
+    pub v9p_path: Option<String>,
+    /// Write a qemu.log
+    pub qemu_log: Option<YesNo>,
+    /// Dump the network traffic
+    pub net_dump: Option<YesNo>,
+    /// The network backend
+    pub net_dev: Option<NetDev>,
+    /// The guest IP address
+    pub ip: Option<String>,
+    /// The gateway IP address
+    pub gw: Option<String>,
+    /// Arguments passed to the application
+    pub args: Option<String>,
+    /// Environment variables passed to the application
+    pub envs: Option<String>,
+}
+
+/// Returns the schema of a string that is one of the given values
+/// # Notes
+/// Values with variables are only checked once interpolated, so any string containing
+/// `${` is accepted as well.
+fn choice_schema(values: &[&str]) -> Schema {
+    let mut value = string_schema();
+    value.enum_values = Some(values.iter().map(|value| json!(value)).collect());
+    let mut variable = string_schema();
+    variable.string().pattern = Some("\\$\\{".to_string());
+    let mut schema = SchemaObject::default();
+    schema.subschemas().any_of = Some(vec![value.into(), variable.into()]);
+    schema.into()
+}
+
+/// Returns the schema of any string
+fn string_schema() -> SchemaObject {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        ..Default::default()
+    }
+}
+
+/// Declares an enum of the values a string field may take
+macro_rules! choice {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+        pub enum $name {
+            $(
+                #[serde(rename = $value)]
+                $variant,
+            )+
+        }
+
+        impl $name {
+            /// Returns the value as written in the config file
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $($name::$variant => $value,)+
+                }
+            }
+        }
+
+        impl JsonSchema for $name {
+            fn schema_name() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                choice_schema(&[$($value),+])
+            }
+        }
+    };
+}
+
+choice!(
+    /// A "y" or "n" switch
+    YesNo { Yes = "y", No = "n" }
+);
+choice!(
+    /// The format of a source archive
+    Archive { TarGz = "tar.gz", TarXz = "tar.xz", Tar = "tar", Zip = "zip", File = "file" }
+);
+choice!(
+    /// The type of a target
+    TargetType { Exe = "exe", Dll = "dll", Static = "static", Object = "object" }
+);
+choice!(
+    /// How the patterns of a target match its files
+    SrcMatch { Glob = "glob", Substring = "substring" }
+);
+choice!(
+    /// The user library of the OS
+    Ulib { Ruxlibc = "ruxlibc", Ruxmusl = "ruxmusl" }
+);
+choice!(
+    /// The build mode of the OS
+    Mode { Release = "release", Debug = "debug" }
+);
+choice!(
+    /// The log level of the OS
+    LogLevel { Off = "off", Error = "error", Warn = "warn", Info = "info", Debug = "debug", Trace = "trace" }
+);
+choice!(
+    /// The network backend of QEMU
+    NetDev { User = "user", Tap = "tap" }
+);
+
+/// Returns the JSON Schema of the config file, for editors such as Taplo
+/// # Notes
+/// The schema is generated from `ConfigFile`, the type the parser reads config files into.
+pub fn json_schema() -> RootSchema {
+    SchemaSettings::draft07()
+        .with(|settings| settings.option_add_null_type = false)
+        .into_generator()
+        .into_root_schema_for::<ConfigFile>()
+}
+
+/// Byte range of a key or value in a config file
+type Span = std::ops::Range<usize>;
+
+/// Enum describing a config value
+/// # Notes
+/// The keys of tables and the items of arrays keep their span. The values of tables
+/// are located by their key, because the implicit tables of dotted keys and table
+/// headers such as `[os.platform.qemu]` have no span of their own.
+enum Node {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<(Span, Node)>),
+    Table(Vec<Entry>),
+    /// A value dropped after an error, which deserializes into any type
+    Missing,
+}
+
+/// A key of a table and its value
+struct Entry {
+    key: String,
+    /// The span of the key, `None` for keys added after an error
+    span: Option<Span>,
+    value: Node,
+}
+
+impl Node {
+    /// Returns the description of the value, used in errors
+    fn describe(&self) -> String {
+        match self {
+            Node::String(s) => format!("the string {:?}", s),
+            Node::Integer(n) => format!("the integer {}", n),
+            Node::Float(f) => format!("the float {}", f),
+            Node::Boolean(b) => format!("the boolean {}", b),
+            Node::Array(_) => "an array".to_string(),
+            Node::Table(_) => "a table".to_string(),
+            Node::Missing => "nothing".to_string(),
+        }
+    }
+
+    /// Returns the value at the given table entry and array item indices
+    fn get_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        let Some((&index, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        let node = match self {
+            Node::Table(entries) => &mut entries.get_mut(index)?.value,
+            Node::Array(items) => &mut items.get_mut(index)?.1,
+            _ => return None,
+        };
+        node.get_mut(rest)
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Node, E> {
+        Ok(Node::Boolean(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Node, E> {
+        Ok(Node::Integer(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Node, E> {
+        Ok(Node::Integer(value as i64))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Node, E> {
+        Ok(Node::Float(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Node, E> {
+        Ok(Node::String(value.to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut arr = Vec::new();
+        while let Some(item) = seq.next_element::<Spanned<Node>>()? {
+            arr.push((item.span(), item.into_inner()));
+        }
+        Ok(Node::Array(arr))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut table = Vec::new();
+        while let Some(key) = map.next_key::<Spanned<String>>()? {
+            table.push(Entry {
+                span: Some(key.span()),
+                key: key.into_inner(),
+                value: map.next_value()?,
+            });
+        }
+        Ok(Node::Table(table))
+    }
+}
+
+/// Validates a config file against the types the parser reads it into
+/// # Notes
+/// Unknown keys, type mismatches, invalid values and missing required keys are all
+/// reported, each with its file, line and column. Values containing a `${` variable
+/// are only checked once interpolated.
+/// # Arguments
+/// * `path` - The path to the config file, used in errors
+/// * `contents` - The contents of the config file
+pub fn validate(path: &Path, contents: &str) -> Vec<String> {
+    let mut root = match toml::from_str::<Node>(contents) {
+        Ok(root) => root,
+        Err(e) => return vec![format!("{}: {}", path.display(), e)],
+    };
+    let mut errors = Vec::new();
+    // Deserializing stops at the first error, so the value in error is dropped from the
+    // tree and the tree is deserialized again until no error is left
+    loop {
+        let place = Place::default();
+        let deserializer = NodeDeserializer {
+            node: &root,
+            place: &place,
+        };
+        let Err(error) = ConfigFile::deserialize(deserializer) else {
+            break;
+        };
+        let error = error.locate(&root, &place);
+        let (span, message) = error.message();
+        errors.push(match span {
+            Some(span) => {
+                let before = &contents[..span.start.min(contents.len())];
+                let line = before.matches('\n').count() + 1;
+                let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+                format!("{}:{}:{}: {}", path.display(), line, column, message)
+            }
+            None => format!("{}: {}", path.display(), message),
+        });
+        if !error.recover(&mut root) {
+            break;
+        }
+    }
+    errors
+}
+
+/// Where a value is in a config file
+#[derive(Debug, Clone, Default)]
+struct Place {
+    /// The table entry and array item indices leading to the value
+    path: Vec<usize>,
+    /// The span of the value, or of its key
+    span: Option<Span>,
+    /// The name of the value, e.g. `targets[0].src`, empty for the root
+    name: String,
+}
+
+impl Place {
+    /// Returns the place of a value in this table or array
+    fn child(&self, index: usize, span: Option<Span>, name: String) -> Place {
+        let mut path = self.path.clone();
+        path.push(index);
+        Place { path, span, name }
+    }
+
+    /// Returns how the value is referred to in errors
+    fn location(&self) -> String {
+        if self.name.is_empty() {
+            "the config".to_string()
+        } else {
+            format!("'{}'", self.name)
+        }
+    }
+}
+
+/// Enum describing why a value could not be deserialized
+#[derive(Debug)]
+enum ErrorKind {
+    /// The value has the wrong type, with the description of the expected one
+    Invalid(String),
+    /// The value is not one of the given choices
+    Choice(String, &'static [&'static str]),
+    /// The table has a key the type does not know
+    UnknownKey {
+        index: usize,
+        key: String,
+        span: Option<Span>,
+        suggestion: Option<&'static str>,
+    },
+    /// The table lacks a required key
+    MissingKey(&'static str),
+    Other(String),
+}
+
+/// Struct describing an error of a config value
+#[derive(Debug)]
+struct ValueError {
+    kind: ErrorKind,
+    /// Where the value is, and its description
+    at: Option<Box<(Place, String)>>,
+}
+
+impl ValueError {
+    fn new(kind: ErrorKind) -> Self {
+        ValueError { kind, at: None }
+    }
+
+    /// Sets where the error is, unless a nested value already did
+    fn locate(mut self, node: &Node, place: &Place) -> Self {
+        if self.at.is_none() {
+            self.at = Some(Box::new((place.clone(), node.describe())));
+        }
+        self
+    }
+
+    /// Returns the span and the message of the error
+    fn message(&self) -> (Option<Span>, String) {
+        let Some((place, found)) = self.at.as_deref() else {
+            return (None, self.to_string());
+        };
+        let message = match &self.kind {
+            ErrorKind::Invalid(expected) => {
+                format!("'{}' must be {}, found {}", place.name, expected, found)
+            }
+            ErrorKind::Choice(value, choices) => format!(
+                "invalid value '{}' for '{}', expected one of: {}",
+                value,
+                place.name,
+                choices.join(", ")
+            ),
+            ErrorKind::UnknownKey {
+                key,
+                span,
+                suggestion,
+                ..
+            } => {
+                let message = match suggestion {
+                    Some(suggestion) => format!(
+                        "unknown key '{}' in {}, did you mean '{}'?",
+                        key,
+                        place.location(),
+                        suggestion
+                    ),
+                    None => format!("unknown key '{}' in {}", key, place.location()),
+                };
+                return (span.clone(), message);
+            }
+            ErrorKind::MissingKey(key) => {
+                format!("missing key '{}' in {}", key, place.location())
+            }
+            ErrorKind::Other(message) => format!("{}: {}", place.location(), message),
+        };
+        (place.span.clone(), message)
+    }
+
+    /// Drops the value in error from the tree, so that deserializing it again finds the
+    /// next error
+    /// # Notes
+    /// Unknown keys are removed and missing keys are added. Other values are replaced
+    /// by `Node::Missing`. Returns false if the tree cannot be changed, which stops the
+    /// validation.
+    fn recover(self, root: &mut Node) -> bool {
+        let Some(node) = self.at.and_then(|at| root.get_mut(&at.0.path)) else {
+            return false;
+        };
+        match (self.kind, node) {
+            (ErrorKind::UnknownKey { index, .. }, Node::Table(entries)) => {
+                entries.remove(index);
+                true
+            }
+            (ErrorKind::MissingKey(key), Node::Table(entries)) => {
+                if entries.iter().any(|entry| entry.key == key) {
+                    return false;
+                }
+                entries.push(Entry {
+                    key: key.to_string(),
+                    span: None,
+                    value: Node::Missing,
+                });
+                true
+            }
+            (_, Node::Missing) => false,
+            (_, node) => {
+                *node = Node::Missing;
+                true
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Invalid(expected) => write!(f, "expected {}", expected),
+            ErrorKind::Choice(value, _) => write!(f, "invalid value '{}'", value),
+            ErrorKind::UnknownKey { key, .. } => write!(f, "unknown key '{}'", key),
+            ErrorKind::MissingKey(key) => write!(f, "missing key '{}'", key),
+            ErrorKind::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+impl de::Error for ValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ValueError::new(ErrorKind::Other(msg.to_string()))
+    }
+
+    fn invalid_type(_: de::Unexpected, expected: &dyn de::Expected) -> Self {
+        ValueError::new(ErrorKind::Invalid(describe_expected(&expected.to_string())))
+    }
+
+    fn invalid_value(_: de::Unexpected, expected: &dyn de::Expected) -> Self {
+        ValueError::new(ErrorKind::Invalid(describe_expected(&expected.to_string())))
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        ValueError::new(ErrorKind::Choice(variant.to_string(), expected))
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        ValueError::new(ErrorKind::MissingKey(field))
+    }
+}
+
+/// Returns the TOML name of a type expected by serde, e.g. "a table" for a struct
+fn describe_expected(expected: &str) -> String {
+    match expected {
+        "u8" | "u16" | "u32" | "u64" | "usize" => "a non-negative integer",
+        "i8" | "i16" | "i32" | "i64" | "isize" => "an integer",
+        "f32" | "f64" => "a number",
+        "a sequence" => "an array",
+        "a map" => "a table",
+        "a borrowed string" => "a string",
+        expected if expected.starts_with("struct ") => "a table",
+        expected => expected,
+    }
+    .to_string()
+}
+
+/// Returns the known key closest to an unknown one, if any is close enough
+fn suggest(keys: &[&'static str], key: &str) -> Option<&'static str> {
+    keys.iter()
+        .map(|name| (strsim::jaro_winkler(name, key), *name))
+        .filter(|(similarity, _)| *similarity >= 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, name)| name)
+}
+
+/// The value of the keys added after an error
+static MISSING: Node = Node::Missing;
+
+/// Deserializer of a config value, whose errors know where the value is
+#[derive(Clone, Copy)]
+struct NodeDeserializer<'a> {
+    node: &'a Node,
+    place: &'a Place,
+}
+
+impl<'a> NodeDeserializer<'a> {
+    /// Deserializes a nested value, locating its errors
+    fn nested<'de, T: DeserializeSeed<'de>>(
+        seed: T,
+        node: &Node,
+        place: &Place,
+    ) -> Result<T::Value, ValueError> {
+        seed.deserialize(NodeDeserializer { node, place })
+            .map_err(|e| e.locate(node, place))
+    }
+}
+
+/// Declares the deserialization of numbers, which are 0 when missing
+macro_rules! deserialize_numbers {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+                match self.node {
+                    Node::Missing => visitor.visit_u64(0),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for NodeDeserializer<'a> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.node {
+            Node::String(s) => visitor.visit_str(s),
+            Node::Integer(n) => visitor.visit_i64(*n),
+            Node::Float(f) => visitor.visit_f64(*f),
+            Node::Boolean(b) => visitor.visit_bool(*b),
+            Node::Array(items) => visitor.visit_seq(Items {
+                items: items.iter().enumerate(),
+                place: self.place,
+            }),
+            Node::Table(entries) => visitor.visit_map(Entries {
+                entries: entries.iter().enumerate(),
+                value: None,
+                place: self.place,
+            }),
+            Node::Missing => visitor.visit_str(""),
+        }
+    }
+
+    deserialize_numbers!(
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64
+    );
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.node {
+            Node::Missing => visitor.visit_bool(false),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.node {
+            Node::Missing => visitor.visit_seq(Items {
+                items: [].iter().enumerate(),
+                place: self.place,
+            }),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.node {
+            Node::Missing => visitor.visit_map(Entries {
+                entries: [].iter().enumerate(),
+                value: None,
+                place: self.place,
+            }),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        match self.node {
+            Node::Table(entries) => {
+                let unknown = entries
+                    .iter()
+                    .enumerate()
+                    .find(|(_, entry)| !fields.contains(&entry.key.as_str()));
+                if let Some((index, entry)) = unknown {
+                    return Err(ValueError::new(ErrorKind::UnknownKey {
+                        index,
+                        key: entry.key.clone(),
+                        span: entry.span.clone(),
+                        suggestion: suggest(fields, &entry.key),
+                    }));
+                }
+                self.deserialize_any(visitor)
+            }
+            Node::Missing => visitor.visit_map(MissingFields {
+                fields: fields.iter(),
+                place: self.place,
+            }),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        let value = match self.node {
+            // Variables are only checked once interpolated
+            Node::String(s) if s.contains("${") => variants[0],
+            Node::String(s) => s.as_str(),
+            Node::Missing => variants[0],
+            _ => {
+                return Err(ValueError::new(ErrorKind::Invalid(format!(
+                    "one of: {}",
+                    variants.join(", ")
+                ))));
+            }
+        };
+        visitor.visit_enum(value.into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct newtype_struct tuple
+        tuple_struct identifier
+    }
+}
+
+/// The items of an array being deserialized
+struct Items<'a> {
+    items: std::iter::Enumerate<std::slice::Iter<'a, (Span, Node)>>,
+    place: &'a Place,
+}
+
+impl<'de, 'a> SeqAccess<'de> for Items<'a> {
+    type Error = ValueError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ValueError> {
+        let Some((index, (span, node))) = self.items.next() else {
+            return Ok(None);
+        };
+        let name = format!("{}[{}]", self.place.name, index);
+        let place = self.place.child(index, Some(span.clone()), name);
+        NodeDeserializer::nested(seed, node, &place).map(Some)
+    }
+}
+
+/// The entries of a table being deserialized
+struct Entries<'a> {
+    entries: std::iter::Enumerate<std::slice::Iter<'a, Entry>>,
+    /// The entry whose key was deserialized last
+    value: Option<(usize, &'a Entry)>,
+    place: &'a Place,
+}
+
+impl<'de, 'a> MapAccess<'de> for Entries<'a> {
+    type Error = ValueError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ValueError> {
+        let Some((index, entry)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some((index, entry));
+        seed.deserialize(entry.key.as_str().into_deserializer())
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ValueError> {
+        let (index, entry) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value without a key"))?;
+        let name = if self.place.name.is_empty() {
+            entry.key.clone()
+        } else {
+            format!("{}.{}", self.place.name, entry.key)
+        };
+        let place = self.place.child(index, entry.span.clone(), name);
+        NodeDeserializer::nested(seed, &entry.value, &place)
+    }
+}
+
+/// The fields of a missing table, which are all missing as well
+struct MissingFields<'a> {
+    fields: std::slice::Iter<'static, &'static str>,
+    place: &'a Place,
+}
+
+impl<'de, 'a> MapAccess<'de> for MissingFields<'a> {
+    type Error = ValueError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ValueError> {
+        match self.fields.next() {
+            Some(field) => seed.deserialize(field.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ValueError> {
+        NodeDeserializer::nested(seed, &MISSING, self.place)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(contents: &str) -> Vec<String> {
+        validate(Path::new("ruxgo.toml"), contents)
+    }
+
+    #[test]
+    fn app_configs_are_valid() {
+        let apps = Path::new(env!("CARGO_MANIFEST_DIR")).join("apps");
+        let mut count = 0;
+        for entry in walkdir::WalkDir::new(apps) {
+            let entry = entry.unwrap();
+            if entry.file_name() != "config_linux.toml" {
+                continue;
+            }
+            let contents = std::fs::read_to_string(entry.path()).unwrap();
+            if let Err(e) = toml::from_str::<ConfigFile>(&contents) {
+                panic!("{}: {}", entry.path().display(), e);
+            }
+            assert_eq!(
+                validate(entry.path(), &contents),
+                Vec::<String>::new(),
+                "{}",
+                entry.path().display()
+            );
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn unknown_keys_are_located_and_suggested() {
+        let contents = "[build]\ncompiler = \"gcc\"\n\n[[targets]]\nname = \"app\"\ntype = \"exe\"\nsrcs = \"./src\"\nfoo = 1\n";
+        assert_eq!(
+            errors(contents),
+            [
+                "ruxgo.toml:7:1: unknown key 'srcs' in 'targets[0]', did you mean 'src'?",
+                "ruxgo.toml:8:1: unknown key 'foo' in 'targets[0]'",
+            ]
+        );
+        assert_eq!(
+            errors("[os.platform.qemu]\nmemroy = \"1G\"\n"),
+            ["ruxgo.toml:2:1: unknown key 'memroy' in 'os.platform.qemu', did you mean 'memory'?"]
+        );
+        assert_eq!(
+            errors("bulid.compiler = \"gcc\"\n"),
+            ["ruxgo.toml:1:1: unknown key 'bulid' in the config, did you mean 'build'?"]
+        );
+    }
+
+    #[test]
+    fn types_and_values_are_checked() {
+        let contents = "[os]\nulib = \"glibc\"\nservices = \"fs\"\n[os.platform]\nmode = \"${env:MODE}\"\nsmp = 4\n\n[[sources]]\nname = \"a\"\nurl = \"a.tar\"\nstrip_components = -1\n\n[[targets]]\nname = \"app\"\ntype = \"exe\"\ninclude_dir = [\"inc\", 1]\n";
+        assert_eq!(
+            errors(contents),
+            [
+                "ruxgo.toml:2:1: invalid value 'glibc' for 'os.ulib', expected one of: ruxlibc, ruxmusl",
+                "ruxgo.toml:3:1: 'os.services' must be an array, found the string \"fs\"",
+                "ruxgo.toml:6:1: 'os.platform.smp' must be a string, found the integer 4",
+                "ruxgo.toml:11:1: 'sources[0].strip_components' must be a non-negative integer, found the integer -1",
+                "ruxgo.toml:16:23: 'targets[0].include_dir[1]' must be a string, found the integer 1",
+            ]
+        );
+        assert_eq!(
+            errors("targets = \"app\"\n[vars]\nlist = []\n[[sources]]\nname = \"a\"\nurl = \"a\"\narchive = 1\n"),
+            [
+                "ruxgo.toml:1:1: 'targets' must be an array, found the string \"app\"",
+                "ruxgo.toml:3:1: 'vars.list' must be a string, an integer, a float or a boolean, found an array",
+                "ruxgo.toml:7:1: 'sources[0].archive' must be one of: tar.gz, tar.xz, tar, zip, file, found the integer 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn required_keys_are_checked() {
+        let contents =
+            "[workspace]\n\n[[targets]]\nname = \"app\"\n\n[[targets]]\nextends = \"lib\"\n\n[[targets]]\n";
+        assert_eq!(
+            errors(contents),
+            [
+                "ruxgo.toml:1:2: missing key 'members' in 'workspace'",
+                "ruxgo.toml:3:1: missing key 'type' in 'targets[0]'",
+                "ruxgo.toml:9:1: missing key 'name' in 'targets[2]'",
+                "ruxgo.toml:9:1: missing key 'type' in 'targets[2]'",
+            ]
+        );
+        // Templates may leave out any key
+        assert!(errors("[templates.lib]\ncflags = \"-O2\"\n").is_empty());
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let errors = errors("[build\n");
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("ruxgo.toml: TOML parse error"),
+            "{}",
+            errors[0]
+        );
+    }
+}
@@ -0,0 +1,667 @@
+//! This module contains functions for fetching, verifying and extracting sources.
+
+use crate::error::{Error, Result};
+use crate::hasher::Hasher;
+use crate::packages;
+use crate::parser::{BuildConfig, SourceConfig};
+use crate::patches::{self, PatchState};
+use crate::utils::log::{log, LogLevel};
+use bytes::Bytes;
+use directories::ProjectDirs;
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as OtherWrite;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use xz2::read::XzDecoder;
+
+/// File in the destination recording which source it was extracted from
+const SOURCE_MARKER: &str = ".ruxgo-source";
+
+/// Fetches all sources of the project and applies their patches
+/// # Arguments
+/// * `build_config` - The local build configuration
+pub fn fetch_sources(build_config: &BuildConfig) -> Result<()> {
+    let paths = &build_config.paths;
+    for source in &build_config.sources {
+        // A changed patch is reapplied to a fresh copy of the source
+        let dest = Path::new(&source.dest);
+        if dest.join(SOURCE_MARKER).exists()
+            && PatchState::load(paths, &source.dest)?.is_stale(&source.patches)
+        {
+            log(
+                LogLevel::Info,
+                &format!(
+                    "Patches of source '{}' changed, extracting a fresh copy",
+                    source.name
+                ),
+            );
+            fs::remove_dir_all(dest)
+                .map_err(|why| Error::io(format!("Could not remove '{}'", source.dest), why))?;
+        }
+        if fetch_source(source, build_config.offline)? {
+            patches::forget_patches(paths, &source.dest)?;
+        }
+        patches::sync_patches(paths, &source.dest, &source.patches)?;
+    }
+    Ok(())
+}
+
+/// Fetches, verifies and extracts a source into its destination
+/// Returns whether a fresh copy was extracted.
+/// # Notes
+/// Downloads are cached per user, keyed by their sha256, so that `--offline` builds and
+/// other projects can reuse them. A destination that exists but was not extracted by
+/// ruxgo is left untouched.
+/// # Arguments
+/// * `source` - The source to fetch
+/// * `offline` - Whether to only use the cache
+pub fn fetch_source(source: &SourceConfig, offline: bool) -> Result<bool> {
+    let dest = Path::new(&source.dest);
+    let marker = dest.join(SOURCE_MARKER);
+    let fingerprint = fingerprint(source);
+    if fs::read_to_string(&marker).is_ok_and(|contents| contents == fingerprint) {
+        log(
+            LogLevel::Info,
+            &format!("Source '{}' is up to date", source.name),
+        );
+        return Ok(false);
+    }
+    if dest.exists() && !marker.exists() {
+        log(
+            LogLevel::Warn,
+            &format!(
+                "Destination '{}' of source '{}' was not fetched by ruxgo, leaving it untouched",
+                source.dest, source.name
+            ),
+        );
+        return Ok(false);
+    }
+
+    let archive_path = fetch_archive(source, offline)?;
+    if dest.exists() {
+        fs::remove_dir_all(dest)
+            .map_err(|why| Error::io(format!("Could not remove '{}'", source.dest), why))?;
+    }
+    log(
+        LogLevel::Log,
+        &format!("Extracting source '{}' to {}", source.name, source.dest),
+    );
+    if let Err(why) = extract(source, &archive_path, dest) {
+        let _ = fs::remove_dir_all(dest);
+        return Err(Error::io(
+            format!("Failed to extract source '{}'", source.name),
+            why,
+        ));
+    }
+    Hasher::write_atomic(&marker, fingerprint.as_bytes())?;
+    Ok(true)
+}
+
+/// Returns the file marking the destination of a source as extracted
+pub fn marker_path(source: &SourceConfig) -> String {
+    format!("{}/{}", source.dest, SOURCE_MARKER)
+}
+
+/// Returns a shell command fetching, verifying, extracting and patching a source
+/// # Notes
+/// The command is used by `ruxgo export`, so it only relies on `curl`, `cp`, `sha256sum`,
+/// `tar`, `unzip` and `patch`. Like ruxgo, it copies local sources instead of
+/// downloading them, verifies only a given sha256 and copies files that are not
+/// archives into the destination. It writes the same marker as ruxgo.
+/// # Arguments
+/// * `source` - The source to fetch
+pub fn fetch_command(source: &SourceConfig) -> String {
+    let dest = &source.dest;
+    let download = format!("{}.download", dest);
+    let mut cmd = format!("rm -rf {dest} && mkdir -p {dest}");
+    let file_name = match local_path(&source.url) {
+        Some(local_path) => {
+            cmd += &format!(" && cp {} {download}", local_path.display());
+            local_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        }
+        None => {
+            cmd += &format!(" && curl -fsSL -o {download} {}", source.url);
+            download_name(source).to_string()
+        }
+    };
+    if !source.sha256.is_empty() {
+        cmd += &format!(" && echo '{}  {download}' | sha256sum -c -", source.sha256);
+    }
+    let strip = source.strip_components;
+    match source.archive.as_str() {
+        "zip" => {
+            let unzipped = format!("{}.unzip", dest);
+            cmd += &format!(
+                " && rm -rf {unzipped} && unzip -q {download} -d {unzipped} && cp -R {unzipped}/{}. {dest}/ && rm -rf {unzipped}",
+                "*/".repeat(strip)
+            );
+        }
+        "tar" | "tar.gz" | "tar.xz" => {
+            let flag = match source.archive.as_str() {
+                "tar.gz" => "z",
+                "tar.xz" => "J",
+                _ => "",
+            };
+            cmd += &format!(" && tar -x{}f {download} -C {dest}", flag);
+            if strip > 0 {
+                cmd += &format!(" --strip-components={}", strip);
+            }
+        }
+        _ => cmd += &format!(" && cp {download} {dest}/{file_name}"),
+    }
+    cmd += &format!(" && rm -f {download}");
+    for patch in &source.patches {
+        cmd += &format!(" && patch -p1 --batch --forward -d {dest} < {}", patch);
+    }
+    cmd += &format!(
+        " && printf '%s' {} > {}",
+        fingerprint(source),
+        marker_path(source)
+    );
+    cmd
+}
+
+/// Returns the fingerprint written to the marker of an extracted source
+fn fingerprint(source: &SourceConfig) -> String {
+    Hasher::hash_string(&format!(
+        "{}\n{}\n{}\n{}",
+        source.url, source.sha256, source.archive, source.strip_components
+    ))
+}
+
+/// Returns the path of the verified archive of a source, downloading it if needed
+fn fetch_archive(source: &SourceConfig, offline: bool) -> Result<PathBuf> {
+    if source.sha256.is_empty() {
+        log(
+            LogLevel::Warn,
+            &format!(
+                "Source '{}' has no sha256, skipping verification",
+                source.name
+            ),
+        );
+    }
+
+    // Local archives are used in place
+    if let Some(local_path) = local_path(&source.url) {
+        let contents = fs::read(&local_path)
+            .map_err(|why| Error::io(format!("Could not read source '{}'", source.url), why))?;
+        verify(source, &contents)?;
+        return Ok(local_path);
+    }
+
+    let key = if source.sha256.is_empty() {
+        Hasher::hash_string(&source.url)
+    } else {
+        source.sha256.clone()
+    };
+    let cache_dir = cache_dir().join(key);
+    let cache_path = cache_dir.join(download_name(source));
+    if let Ok(contents) = fs::read(&cache_path) {
+        if source.sha256.is_empty() || sha256(&contents) == source.sha256 {
+            log(
+                LogLevel::Info,
+                &format!("Using cached source: {}", cache_path.display()),
+            );
+            return Ok(cache_path);
+        }
+        log(
+            LogLevel::Warn,
+            &format!("Cached source '{}' is corrupted, refetching", source.name),
+        );
+    }
+    if offline {
+        return Err(Error::Package(format!(
+            "Source '{}' is not cached and cannot be downloaded with --offline",
+            source.name
+        )));
+    }
+
+    log(
+        LogLevel::Log,
+        &format!("Downloading source '{}' from {}", source.name, source.url),
+    );
+    let contents = download(&source.url).map_err(|why| {
+        Error::Package(format!(
+            "Failed to download source '{}': {}",
+            source.name, why
+        ))
+    })?;
+    verify(source, &contents)?;
+    fs::create_dir_all(&cache_dir).map_err(|why| Error::io("Couldn't create cache dir", why))?;
+    Hasher::write_atomic(&cache_path, &contents)?;
+    Ok(cache_path)
+}
+
+/// Returns the file name of a downloaded source, the last component of its url
+fn download_name(source: &SourceConfig) -> &str {
+    source
+        .url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&source.name)
+}
+
+/// Returns the directory where downloaded sources are cached
+fn cache_dir() -> PathBuf {
+    ProjectDirs::from("com", "RuxosApps", "ruxos-c")
+        .map(|dirs| dirs.cache_dir().to_path_buf())
+        .unwrap_or_else(std::env::temp_dir)
+        .join("sources")
+}
+
+/// Returns the path of a `file://` url or a plain local path
+fn local_path(url: &str) -> Option<PathBuf> {
+    if let Some(path) = url.strip_prefix("file://") {
+        Some(PathBuf::from(path))
+    } else if url.contains("://") {
+        None
+    } else {
+        Some(PathBuf::from(url))
+    }
+}
+
+/// Downloads a url, blocking the current thread
+fn download(url: &str) -> std::result::Result<Bytes, Box<dyn std::error::Error>> {
+    let fetch = packages::fetch_binary(url);
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(fetch)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(fetch),
+    }
+}
+
+/// Returns the sha256 of the contents as a hex string
+fn sha256(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .fold(String::new(), |mut acc, &byte| {
+            write!(acc, "{:02x}", byte).expect("Unable to write");
+            acc
+        })
+}
+
+/// Checks the contents of a source against its sha256
+fn verify(source: &SourceConfig, contents: &[u8]) -> Result<()> {
+    if source.sha256.is_empty() {
+        return Ok(());
+    }
+    let actual = sha256(contents);
+    if actual != source.sha256 {
+        return Err(Error::Package(format!(
+            "Checksum mismatch for source '{}': expected {}, got {}",
+            source.name, source.sha256, actual
+        )));
+    }
+    Ok(())
+}
+
+/// Extracts an archive into the destination
+fn extract(source: &SourceConfig, archive_path: &Path, dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+    let file = File::open(archive_path)?;
+    let strip = source.strip_components;
+    match source.archive.as_str() {
+        "tar.gz" => unpack_tar(GzDecoder::new(file), dest, strip),
+        "tar.xz" => unpack_tar(XzDecoder::new(file), dest, strip),
+        "tar" => unpack_tar(file, dest, strip),
+        "zip" => unpack_zip(file, dest, strip),
+        _ => {
+            let file_name = archive_path.file_name().unwrap_or_default();
+            fs::copy(archive_path, dest.join(file_name)).map(|_| ())
+        }
+    }
+}
+
+/// Strips the leading components of an archive path
+/// Returns `None` for paths that are stripped entirely or would escape the destination.
+fn strip_path(path: &Path, strip: usize) -> Option<PathBuf> {
+    let components: Vec<Component> = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    if components
+        .iter()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let stripped: PathBuf = components.into_iter().skip(strip).collect();
+    (!stripped.as_os_str().is_empty()).then_some(stripped)
+}
+
+/// Returns an error if a path resolves outside of the destination
+/// # Arguments
+/// * `root` - The canonical destination
+/// * `path` - The path to check, with its existing parents canonicalized
+fn check_inside(root: &Path, path: &Path) -> io::Result<()> {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    if !resolved.starts_with(root) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Archive entry escapes the destination: {}", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Unpacks a tar stream into the destination
+/// # Notes
+/// Links pointing outside of the destination are rejected, so that no entry can be
+/// written through them.
+fn unpack_tar<R: Read>(reader: R, dest: &Path, strip: usize) -> io::Result<()> {
+    let root = dest.canonicalize()?;
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_pax_global_extensions() || entry_type.is_pax_local_extensions() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        let target = match strip_path(&path, strip) {
+            Some(relative) => dest.join(relative),
+            None => continue,
+        };
+        let Some(parent) = target.parent() else {
+            continue;
+        };
+        fs::create_dir_all(parent)?;
+        // The parent is resolved, so that it cannot lead outside through an earlier link
+        let parent = parent.canonicalize()?;
+        check_inside(&root, &parent)?;
+        // Hard links point to other entries of the archive, which are stripped as well
+        if entry_type.is_hard_link() {
+            let link = entry.link_name()?.map(|link| link.into_owned());
+            if let Some(relative) = link.and_then(|link| strip_path(&link, strip)) {
+                let original = dest.join(relative);
+                check_inside(&root, &original.canonicalize()?)?;
+                fs::hard_link(original, &target)?;
+            }
+            continue;
+        }
+        if entry_type.is_symlink() {
+            let link = entry.link_name()?.unwrap_or_default().into_owned();
+            check_inside(&root, &parent.join(link))?;
+        }
+        entry.unpack(&target)?;
+    }
+    Ok(())
+}
+
+/// Unpacks a zip archive into the destination
+fn unpack_zip(file: File, dest: &Path, strip: usize) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+        let target = match entry
+            .enclosed_name()
+            .and_then(|path| strip_path(path, strip))
+        {
+            Some(relative) => dest.join(relative),
+            None => continue,
+        };
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out = File::create(&target)?;
+        io::copy(&mut entry, &mut out)?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&target, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// Returns a tar with a top-level directory, like most release tarballs
+    fn tar_bytes() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut add = |path: &str, contents: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, path, contents).unwrap();
+        };
+        add("pkg-1.0/configure", b"#!/bin/sh\n");
+        add("pkg-1.0/src/main.c", b"int main(void) { return 0; }\n");
+        builder.into_inner().unwrap()
+    }
+
+    fn zip_bytes() -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        writer.add_directory("pkg-1.0/src/", options).unwrap();
+        writer.start_file("pkg-1.0/src/main.c", options).unwrap();
+        writer.write_all(b"int main(void) { return 0; }\n").unwrap();
+        writer.start_file("../escape.c", options).unwrap();
+        writer.write_all(b"escaped").unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn source(dir: &Path, archive_name: &str, contents: &[u8], strip: usize) -> SourceConfig {
+        let archive_path = dir.join(archive_name);
+        fs::write(&archive_path, contents).unwrap();
+        SourceConfig {
+            name: "pkg".to_string(),
+            url: format!("file://{}", archive_path.display()),
+            sha256: sha256(contents),
+            archive: SourceConfig::archive_from_url(archive_name).to_string(),
+            strip_components: strip,
+            dest: dir.join("dest").to_string_lossy().to_string(),
+            patches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn strip_path_strips_and_rejects_escapes() {
+        assert_eq!(
+            strip_path(Path::new("pkg/src/main.c"), 1),
+            Some(PathBuf::from("src/main.c"))
+        );
+        assert_eq!(
+            strip_path(Path::new("./pkg/main.c"), 1),
+            Some(PathBuf::from("main.c"))
+        );
+        assert_eq!(strip_path(Path::new("pkg/"), 1), None);
+        assert_eq!(strip_path(Path::new("pkg/../../etc/passwd"), 1), None);
+        assert_eq!(strip_path(Path::new("/etc/passwd"), 0), None);
+    }
+
+    #[test]
+    fn unpacks_tar_gz_with_strip_components() {
+        let tmp = test_dir("sources-tar-gz");
+        let dir = tmp.path();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar_bytes()).unwrap();
+        let source = source(dir, "pkg-1.0.tar.gz", &encoder.finish().unwrap(), 1);
+
+        fetch_source(&source, true).unwrap();
+        let dest = Path::new(&source.dest);
+        assert_eq!(
+            fs::read_to_string(dest.join("src/main.c")).unwrap(),
+            "int main(void) { return 0; }\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dest.join("configure"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+        assert!(!dest.join("pkg-1.0").exists());
+        assert_eq!(
+            fs::read_to_string(marker_path(&source)).unwrap(),
+            fingerprint(&source)
+        );
+    }
+
+    #[test]
+    fn unpacks_tar_without_strip_components() {
+        let tmp = test_dir("sources-tar");
+        let dir = tmp.path();
+        let source = source(dir, "pkg-1.0.tar", &tar_bytes(), 0);
+        fetch_source(&source, true).unwrap();
+        assert!(Path::new(&source.dest).join("pkg-1.0/src/main.c").is_file());
+    }
+
+    #[test]
+    fn unpacks_zip_with_strip_components() {
+        let tmp = test_dir("sources-zip");
+        let dir = tmp.path();
+        let source = source(dir, "pkg-1.0.zip", &zip_bytes(), 1);
+        fetch_source(&source, true).unwrap();
+        let dest = Path::new(&source.dest);
+        assert!(dest.join("src/main.c").is_file());
+        assert!(!dir.join("escape.c").exists() && !dest.join("escape.c").exists());
+    }
+
+    /// Returns a tar of the given symlinks and files
+    fn tar_with_links(links: &[(&str, &str)], files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, target) in links {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            header.set_mode(0o777);
+            builder.append_link(&mut header, path, target).unwrap();
+        }
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn rejects_links_escaping_the_destination() {
+        let tmp = test_dir("sources-escape");
+        let dir = tmp.path();
+        let outside = dir.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        let outside_str = outside.to_string_lossy().to_string();
+        for (index, link) in [outside_str.as_str(), "../.."].iter().enumerate() {
+            let tar = tar_with_links(&[("pkg-1.0/dir", link)], &[("pkg-1.0/dir/evil.c", b"evil")]);
+            let source = source(dir, &format!("escape-{}.tar", index), &tar, 1);
+            assert!(fetch_source(&source, true).is_err());
+            assert!(!outside.join("evil.c").exists());
+            assert!(!dir.join("evil.c").exists());
+            assert!(!Path::new(&source.dest).exists());
+        }
+
+        // Links inside the destination are kept
+        let tar = tar_with_links(
+            &[("pkg-1.0/include/main.c", "../src/main.c")],
+            &[("pkg-1.0/src/main.c", b"int main(void) { return 0; }\n")],
+        );
+        let source = source(dir, "inside.tar", &tar, 1);
+        fetch_source(&source, true).unwrap();
+        assert_eq!(
+            fs::read_to_string(Path::new(&source.dest).join("include/main.c")).unwrap(),
+            "int main(void) { return 0; }\n"
+        );
+    }
+
+    /// Returns the files below a directory with their contents
+    fn files(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files: Vec<(PathBuf, Vec<u8>)> = walkdir::WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let path = entry.path().strip_prefix(dir).unwrap().to_path_buf();
+                (path, fs::read(entry.path()).unwrap())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn fetch_command_matches_the_native_fetch() {
+        let tmp = test_dir("sources-command");
+        let dir = tmp.path();
+        let mut plain = source(dir, "data.bin", b"data", 0);
+        plain.sha256.clear();
+        for source in [source(dir, "pkg-1.0.tar", &tar_bytes(), 1), plain] {
+            let command = fetch_command(&source);
+            assert!(!command.contains("curl"), "{}", command);
+            assert_eq!(
+                command.contains("sha256sum"),
+                !source.sha256.is_empty(),
+                "{}",
+                command
+            );
+            fetch_source(&source, true).unwrap();
+            let native = files(Path::new(&source.dest));
+            let status = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .status()
+                .unwrap();
+            assert!(status.success(), "{}", command);
+            assert_eq!(files(Path::new(&source.dest)), native, "{}", command);
+        }
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        let tmp = test_dir("sources-checksum");
+        let dir = tmp.path();
+        let mut source = source(dir, "pkg-1.0.tar", &tar_bytes(), 1);
+        source.sha256 = sha256(b"something else");
+        assert!(matches!(
+            fetch_source(&source, true),
+            Err(Error::Package(_))
+        ));
+        assert!(!Path::new(&source.dest).exists());
+    }
+
+    #[test]
+    fn leaves_foreign_destination_untouched() {
+        let tmp = test_dir("sources-foreign");
+        let dir = tmp.path();
+        let source = source(dir, "pkg-1.0.tar", &tar_bytes(), 1);
+        fs::create_dir_all(&source.dest).unwrap();
+        fs::write(Path::new(&source.dest).join("mine.c"), "").unwrap();
+        fetch_source(&source, true).unwrap();
+        assert!(Path::new(&source.dest).join("mine.c").exists());
+        assert!(!Path::new(&source.dest).join("src/main.c").exists());
+    }
+}
@@ -0,0 +1,145 @@
+//! This module contains functions for running the custom build steps of targets.
+
+use crate::error::{Error, Result};
+use crate::hasher::{Hasher, Manifest};
+use crate::parser::{BuildConfig, StepConfig, TargetConfig};
+use crate::utils::log::{log, LogLevel};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use walkdir::WalkDir;
+
+/// Runs the custom build steps of a target that are out of date
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `target_config` - The target whose steps to run
+pub fn run_steps(build_config: &BuildConfig, target_config: &TargetConfig) -> Result<()> {
+    if target_config.steps.is_empty() {
+        return Ok(());
+    }
+    let profile_dir = build_config.paths.profile_dir();
+    if !Path::new(&profile_dir).exists() {
+        fs::create_dir_all(&profile_dir)
+            .map_err(|why| Error::io("Couldn't create build dir", why))?;
+    }
+    for step in &target_config.steps {
+        let manifest_path = build_config
+            .paths
+            .step_manifest_file(&target_config.name, &step.name);
+        let mut manifest = Manifest::load(&manifest_path);
+        let fingerprint = format!("{}\n{}", step.working_dir, step.command);
+        check_inputs(step)?;
+        if let Some(reason) = needs_run(step, &fingerprint, &manifest) {
+            log(LogLevel::Debug, &reason);
+        } else {
+            log(
+                LogLevel::Info,
+                &format!(
+                    "Step '{}' of target '{}' is up to date",
+                    step.name, target_config.name
+                ),
+            );
+            continue;
+        }
+
+        log(
+            LogLevel::Log,
+            &format!(
+                "Running step '{}' of target '{}'",
+                step.name, target_config.name
+            ),
+        );
+        log(LogLevel::Info, &format!("Command: {}", step.command));
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&step.command)
+            .current_dir(&step.working_dir)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .map_err(|why| Error::io(format!("Failed to execute step '{}'", step.name), why))?;
+        if !status.success() {
+            return Err(Error::Step(format!(
+                "Step '{}' of target '{}' failed: {}",
+                step.name, target_config.name, status
+            )));
+        }
+        for output in &step.outputs {
+            if !Path::new(output).exists() {
+                return Err(Error::Step(format!(
+                    "Step '{}' of target '{}' did not produce output: {}",
+                    step.name, target_config.name, output
+                )));
+            }
+        }
+
+        manifest.entries.clear();
+        for path in &step_files(step) {
+            Hasher::save_hash(path, &mut manifest, &target_config.name, Some(&fingerprint));
+        }
+        manifest.save(&manifest_path)?;
+    }
+    Ok(())
+}
+
+/// Returns the reason why a step needs to run, if any
+fn needs_run(step: &StepConfig, fingerprint: &str, manifest: &Manifest) -> Option<String> {
+    if step.inputs.is_empty() && step.outputs.is_empty() {
+        return Some(format!("Step '{}' has no inputs and outputs", step.name));
+    }
+    if let Some(output) = step.outputs.iter().find(|o| !Path::new(o).exists()) {
+        return Some(format!(
+            "Output of step '{}' is missing: {}",
+            step.name, output
+        ));
+    }
+    let files = step_files(step);
+    for path in &files {
+        if Hasher::is_file_changed(path, manifest) {
+            return Some(format!("File of step '{}' changed: {}", step.name, path));
+        }
+        if Hasher::is_command_changed(path, fingerprint, manifest) {
+            return Some(format!("Command of step '{}' changed", step.name));
+        }
+    }
+    // Files that were recorded but are no longer part of the step
+    if manifest.entries.len() != files.len() {
+        return Some(format!("Files of step '{}' changed", step.name));
+    }
+    None
+}
+
+/// Checks that all inputs of a step exist, since it could never be up to date otherwise
+fn check_inputs(step: &StepConfig) -> Result<()> {
+    for input in &step.inputs {
+        if !Path::new(input).exists() {
+            return Err(Error::Step(format!(
+                "Input of step '{}' does not exist: {}",
+                step.name, input
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the input and output files of a step, with directories expanded recursively
+fn step_files(step: &StepConfig) -> Vec<String> {
+    let mut files = Vec::new();
+    for path in step.inputs.iter().chain(step.outputs.iter()) {
+        if Path::new(path).is_dir() {
+            for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+                if entry.path().is_file() {
+                    if let Some(file) = entry.path().to_str() {
+                        files.push(file.to_string());
+                    }
+                }
+            }
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files.sort();
+    files.dedup();
+    files
+}
@@ -1,6 +1,17 @@
-//! This module contains various logging, environment config and features config.
+//! This module contains various logging, environment config, features config, source filters and build paths.
 //! used by the ruxgo library
 
 pub mod env;
 pub mod features;
 pub mod log;
+pub mod matcher;
+pub mod paths;
+
+/// Returns an empty directory for a test, removed with the returned guard
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix(&format!("ruxgo-{}-", name))
+        .tempdir()
+        .unwrap()
+}
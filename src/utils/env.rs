@@ -4,7 +4,7 @@ use crate::parser::{OSConfig, PlatformConfig, QemuConfig};
 
 // This function is used to configure environment variables
 pub fn config_env(os_config: &OSConfig) {
-    if os_config != &OSConfig::default() && os_config.platform != PlatformConfig::default() {
+    if !os_config.name.is_empty() && os_config.platform != PlatformConfig::default() {
         std::env::set_var("RUX_ARCH", &os_config.platform.arch);
         std::env::set_var("RUX_PLATFORM", &os_config.platform.name);
         std::env::set_var("RUX_SMP", &os_config.platform.smp);
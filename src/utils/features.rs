@@ -1,9 +1,10 @@
 //! Features Module
 
+use crate::error::{Error, Result};
 use crate::parser::OSConfig;
 use crate::utils::log::{log, LogLevel};
 
-pub fn cfg_feat(os_config: &OSConfig) -> (Vec<String>, Vec<String>) {
+pub fn cfg_feat(os_config: &OSConfig) -> Result<(Vec<String>, Vec<String>)> {
     let mut lib_features = vec![
         "fp_simd",
         "alloc",
@@ -33,11 +34,9 @@ pub fn cfg_feat(os_config: &OSConfig) -> (Vec<String>, Vec<String>) {
             rux_feats.push(format!("log-level-{}", os_config.platform.log));
         }
         _ => {
-            log(
-                LogLevel::Error,
-                "LOG must be one of 'off', 'error', 'warn', 'info', 'debug', 'trace'",
-            );
-            std::process::exit(1);
+            return Err(Error::Config(
+                "LOG must be one of 'off', 'error', 'warn', 'info', 'debug', 'trace'".to_string(),
+            ))
         }
     }
     if os_config.platform.qemu.bus == "pci" {
@@ -55,26 +54,24 @@ pub fn cfg_feat(os_config: &OSConfig) -> (Vec<String>, Vec<String>) {
             lib_feats.push(feat);
         }
     }
-    (rux_feats, lib_feats)
+    Ok((rux_feats, lib_feats))
 }
 
-pub fn cfg_feat_addprefix(os_config: &OSConfig) -> (Vec<String>, Vec<String>) {
+pub fn cfg_feat_addprefix(os_config: &OSConfig) -> Result<(Vec<String>, Vec<String>)> {
     // Set prefix
     let rux_feat_prefix = "ruxfeat/";
     let lib_feat_prefix = match os_config.ulib.as_str() {
         "ruxlibc" => "ruxlibc/",
         "ruxmusl" => "ruxmusl/",
         _ => {
-            log(
-                LogLevel::Error,
-                "Ulib must be one of \"ruxlibc\" or \"ruxmusl\"",
-            );
-            std::process::exit(1);
+            return Err(Error::Config(
+                "Ulib must be one of \"ruxlibc\" or \"ruxmusl\"".to_string(),
+            ))
         }
     };
 
     // Add prefix
-    let (rux_feats_pre, lib_feats_pre) = cfg_feat(os_config);
+    let (rux_feats_pre, lib_feats_pre) = cfg_feat(os_config)?;
     let rux_feats_final = rux_feats_pre
         .into_iter()
         .map(|feat| format!("{}{}", rux_feat_prefix, feat))
@@ -92,5 +89,5 @@ pub fn cfg_feat_addprefix(os_config: &OSConfig) -> (Vec<String>, Vec<String>) {
         &format!("lib_feats_final : {:?}", lib_feats_final),
     );
 
-    (rux_feats_final, lib_feats_final)
+    Ok((rux_feats_final, lib_feats_final))
 }
@@ -1,10 +1,12 @@
 //! Log Module
 
 use colored::Colorize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Once, RwLock};
 
 static INIT: Once = Once::new();
 static LOG_LEVEL: RwLock<LogLevel> = RwLock::new(LogLevel::Info);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
 
 /// This enum is used to represent the different log levels
 #[derive(PartialEq, PartialOrd, Debug)]
@@ -39,6 +41,8 @@ fn init_log_level() {
 /// * `message` - The message to log
 /// # Example
 /// ```
+/// # use ruxgo::utils::log::{log, LogLevel};
+/// # let error = "unknown";
 /// log(LogLevel::Info, "Hello World!");
 /// log(LogLevel::Error, &format!("Something went wrong! {}", error));
 /// ```
@@ -51,6 +55,7 @@ fn init_log_level() {
 /// * `Log`
 /// * `Warn`
 /// * `Error`
+///
 /// If the environment variable is not set, the default log level is `Log`
 pub fn log(level: LogLevel, message: &str) {
     INIT.call_once(|| {
@@ -65,6 +70,17 @@ pub fn log(level: LogLevel, message: &str) {
     };
     // Use read lock to check log level
     if level >= *LOG_LEVEL.read().unwrap() {
-        println!("{} {}", level_str, message);
+        if TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!("{} {}", level_str, message);
+        } else {
+            println!("{} {}", level_str, message);
+        }
     }
 }
+
+/// Sends all further messages to stderr
+/// # Notes
+/// Used by commands whose stdout is read by other tools, e.g. `ruxgo metadata`.
+pub fn log_to_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
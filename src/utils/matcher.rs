@@ -0,0 +1,317 @@
+//! Source Filter Module
+
+use crate::error::{Error, Result};
+use crate::parser::TargetConfig;
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::Path;
+
+/// Struct describing a single gitignore-style pattern
+struct Rule {
+    negate: bool,
+    dir_only: bool,
+    /// Matches the path itself
+    glob: GlobMatcher,
+    /// Matches everything below a matching directory
+    glob_under: GlobMatcher,
+}
+
+/// Patterns of `src_only` or `src_exclude`
+enum Patterns {
+    Glob(Vec<Rule>),
+    Substring(Vec<String>),
+}
+
+/// Struct deciding which files of a target's `src` are compiled
+/// # Notes
+/// Patterns follow gitignore rules and are matched against the path relative to the
+/// `src` root: a pattern without a slash matches at any depth (`test.c`), a pattern
+/// with a slash is anchored to the root (`/test.c`, `lib/*.c`), `**` matches any
+/// number of directories, a trailing slash only matches directories and `!` negates.
+/// The last matching pattern wins. With `src_match = "substring"` the old behavior of
+/// matching any path that contains the pattern is kept.
+pub struct SrcFilter {
+    only: Patterns,
+    exclude: Patterns,
+}
+
+impl SrcFilter {
+    /// Creates the source filter of a target
+    pub fn new(target_config: &TargetConfig) -> Result<Self> {
+        let compile = |patterns: &Vec<String>| -> Result<Patterns> {
+            if target_config.src_match == "substring" {
+                Ok(Patterns::Substring(patterns.clone()))
+            } else {
+                Ok(Patterns::Glob(
+                    patterns
+                        .iter()
+                        .map(|pattern| Rule::new(pattern, &target_config.name))
+                        .collect::<Result<_>>()?,
+                ))
+            }
+        };
+        Ok(SrcFilter {
+            only: compile(&target_config.src_only)?,
+            exclude: compile(&target_config.src_exclude)?,
+        })
+    }
+
+    /// Checks if a file or directory is excluded by `src_exclude`
+    /// # Arguments
+    /// * `root` - The `src` root the path was found in
+    /// * `path` - The path of the file or directory
+    pub fn is_excluded(&self, root: &Path, path: &Path) -> bool {
+        self.exclude.last_match(root, path) == Some(true)
+    }
+
+    /// Checks if a file is selected by `src_only`, which selects every file when empty
+    /// # Arguments
+    /// * `root` - The `src` root the path was found in
+    /// * `path` - The path of the file
+    pub fn is_included(&self, root: &Path, path: &Path) -> bool {
+        match &self.only {
+            Patterns::Glob(rules) if rules.is_empty() => true,
+            Patterns::Substring(patterns) if patterns.is_empty() => true,
+            only => only.last_match(root, path) == Some(true),
+        }
+    }
+}
+
+impl Patterns {
+    /// Returns whether the last matching pattern is positive, `None` if none matches
+    fn last_match(&self, root: &Path, path: &Path) -> Option<bool> {
+        match self {
+            Patterns::Substring(patterns) => {
+                let path = path.to_str().unwrap_or_default();
+                #[cfg(target_os = "windows")]
+                let path = path.replace('\\', "/");
+                patterns
+                    .iter()
+                    .any(|pattern| path.contains(pattern.as_str()))
+                    .then_some(true)
+            }
+            Patterns::Glob(rules) => {
+                let relative = path.strip_prefix(root).unwrap_or(path);
+                if relative.as_os_str().is_empty() {
+                    return None;
+                }
+                let is_dir = path.is_dir();
+                rules
+                    .iter()
+                    .rev()
+                    .find(|rule| rule.matches(relative, is_dir))
+                    .map(|rule| !rule.negate)
+            }
+        }
+    }
+}
+
+impl Rule {
+    /// Compiles a gitignore-style pattern
+    fn new(pattern: &str, target_name: &str) -> Result<Self> {
+        let (negate, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+        // Patterns with a slash are anchored to the src root, others match at any depth
+        let glob = if pattern.contains('/') {
+            pattern.trim_start_matches('/').to_string()
+        } else {
+            format!("**/{}", pattern)
+        };
+        let build = |glob: &str| {
+            GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .map(|glob| glob.compile_matcher())
+                .map_err(|why| {
+                    Error::Config(format!(
+                        "Invalid source pattern '{}' in target '{}': {}",
+                        pattern, target_name, why
+                    ))
+                })
+        };
+        Ok(Rule {
+            negate,
+            dir_only,
+            glob: build(&glob)?,
+            glob_under: build(&format!("{}/**", glob))?,
+        })
+    }
+
+    /// Checks if the rule matches a path relative to the src root
+    fn matches(&self, relative: &Path, is_dir: bool) -> bool {
+        (self.glob.is_match(relative) && (is_dir || !self.dir_only))
+            || self.glob_under.is_match(relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Creates a source tree for a test in a directory removed with the returned guard
+    fn src_tree(name: &str) -> TempDir {
+        let tmp = test_dir(&format!("matcher-{}", name));
+        let root = tmp.path();
+        for file in [
+            "main.c",
+            "domain.c",
+            "test.c",
+            "ssl.c",
+            "modules/mod.c",
+            "examples/example.c",
+            "lib/a.c",
+            "lib/deep/b.c",
+            "sub/test.c",
+            "sub/modules/nested.c",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        tmp
+    }
+
+    /// Returns the sources selected in the tree, relative to its root
+    fn selected(root: &Path, src_match: &str, only: &[&str], exclude: &[&str]) -> Vec<String> {
+        let mut target = TargetConfig::new("test", "exe");
+        target.src = vec![root.to_string_lossy().to_string()];
+        target.src_match = src_match.to_string();
+        target.src_only = only.iter().map(|s| s.to_string()).collect();
+        target.src_exclude = exclude.iter().map(|s| s.to_string()).collect();
+        let mut names: Vec<String> = target
+            .get_src_names()
+            .unwrap()
+            .iter()
+            .map(|name| {
+                Path::new(name)
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn old_root_dir_and_file_forms_match_as_before() {
+        let tmp = src_tree("old-root-forms");
+        let root = tmp.path();
+        // The `/dir` and `/file.c` forms of the app configs, naming entries of the src root
+        // that have no namesake deeper in the tree
+        for exclude in [
+            &["/examples", "/lib"][..],
+            &["/ssl.c"][..],
+            &["/examples", "/ssl.c", "/lib"][..],
+        ] {
+            assert_eq!(
+                selected(root, "glob", &[], exclude),
+                selected(root, "substring", &[], exclude),
+                "src_exclude = {:?}",
+                exclude
+            );
+        }
+    }
+
+    #[test]
+    fn old_plain_file_forms_match_as_before() {
+        let tmp = src_tree("old-plain-forms");
+        let root = tmp.path();
+        // Plain file names match at any depth, like the substring they used to be
+        for patterns in [&["test.c"][..], &["ssl.c", "b.c", "mod.c"][..]] {
+            assert_eq!(
+                selected(root, "glob", &[], patterns),
+                selected(root, "substring", &[], patterns),
+                "src_exclude = {:?}",
+                patterns
+            );
+            assert_eq!(
+                selected(root, "glob", patterns, &[]),
+                selected(root, "substring", patterns, &[]),
+                "src_only = {:?}",
+                patterns
+            );
+        }
+        assert!(!selected(root, "glob", &[], &["test.c"]).contains(&"sub/test.c".to_string()));
+    }
+
+    #[test]
+    fn glob_differs_from_substring_only_on_partial_names_and_nested_anchors() {
+        let tmp = src_tree("differences");
+        let root = tmp.path();
+        // A file name no longer matches files that merely end with it
+        assert!(selected(root, "glob", &[], &["main.c"]).contains(&"domain.c".to_string()));
+        assert!(!selected(root, "substring", &[], &["main.c"]).contains(&"domain.c".to_string()));
+        // A leading slash anchors to the src root, as in gitignore
+        assert!(selected(root, "glob", &[], &["/modules"])
+            .contains(&"sub/modules/nested.c".to_string()));
+        assert!(!selected(root, "substring", &[], &["/modules"])
+            .contains(&"sub/modules/nested.c".to_string()));
+        // Without the slash, the directory is excluded at any depth
+        assert!(!selected(root, "glob", &[], &["modules"])
+            .iter()
+            .any(|name| name.contains("modules")));
+    }
+
+    #[test]
+    fn glob_forms() {
+        let tmp = src_tree("glob-forms");
+        let root = tmp.path();
+        assert_eq!(
+            selected(root, "glob", &["lib/*.c"], &[]),
+            vec!["lib/a.c".to_string()]
+        );
+        assert_eq!(
+            selected(root, "glob", &["lib/**/*.c"], &[]),
+            vec!["lib/a.c".to_string(), "lib/deep/b.c".to_string()]
+        );
+        assert_eq!(
+            selected(
+                root,
+                "glob",
+                &["*.c", "!sub/**"],
+                &["lib/", "modules/", "examples"]
+            ),
+            vec![
+                "domain.c".to_string(),
+                "main.c".to_string(),
+                "ssl.c".to_string(),
+                "test.c".to_string()
+            ]
+        );
+        // The last matching pattern wins
+        assert_eq!(
+            selected(
+                root,
+                "glob",
+                &[],
+                &[
+                    "sub/*.c",
+                    "!sub/test.c",
+                    "lib",
+                    "modules",
+                    "examples",
+                    "/*.c"
+                ]
+            ),
+            vec!["sub/test.c".to_string()]
+        );
+        // A trailing slash only matches directories
+        assert!(selected(root, "glob", &[], &["test.c/"]).contains(&"test.c".to_string()));
+    }
+
+    #[test]
+    fn invalid_pattern_is_an_error() {
+        let mut target = TargetConfig::new("test", "exe");
+        target.src_exclude = vec!["[".to_string()];
+        assert!(matches!(SrcFilter::new(&target), Err(Error::Config(_))));
+    }
+}
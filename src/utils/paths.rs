@@ -0,0 +1,258 @@
+//! Build Paths Module
+
+use crate::hasher::Hasher;
+use crate::parser::OSConfig;
+use std::path::{Path, PathBuf};
+
+/// The default build directory, relative to the current directory
+pub static DEFAULT_BUILD_DIR: &str = "ruxgo_bld";
+
+/// The platform-neutral config file
+pub static CONFIG_FILE: &str = "ruxgo.toml";
+
+/// The environment variable pointing to the config file
+pub static CONFIG_ENV: &str = "RUXGO_CONFIG";
+
+/// The platform suffix of the hash files and object directory used by older versions
+#[cfg(target_os = "windows")]
+static LEGACY_PLATFORM: &str = "win32";
+#[cfg(not(target_os = "windows"))]
+static LEGACY_PLATFORM: &str = "linux";
+
+/// The platform-specific config file used by older projects
+#[cfg(target_os = "windows")]
+pub static LEGACY_CONFIG_FILE: &str = "config_win32.toml";
+#[cfg(not(target_os = "windows"))]
+pub static LEGACY_CONFIG_FILE: &str = "config_linux.toml";
+
+/// Returns the name of the config file of a variant, e.g. `ruxgo.ruxos.toml`
+pub fn config_file_name(variant: Option<&str>) -> String {
+    match variant {
+        Some(variant) => format!("ruxgo.{}.toml", variant),
+        None => CONFIG_FILE.to_string(),
+    }
+}
+
+/// Locates the config file of the project
+/// # Notes
+/// The config is taken from `config` (`--config`), then from `RUXGO_CONFIG`; both may
+/// name a file or a directory holding the config. Otherwise the current directory and
+/// its parents are searched for `ruxgo.toml` (or `ruxgo.<variant>.toml`), while a
+/// `config_linux.toml` in the current directory is still honored without a variant.
+/// # Arguments
+/// * `config` - The config file or directory given on the command line
+/// * `variant` - The config variant to select
+pub fn find_config(config: Option<&Path>, variant: Option<&str>) -> Result<PathBuf, String> {
+    let file_name = config_file_name(variant);
+    let explicit = config.map(Path::to_path_buf).or_else(|| {
+        std::env::var_os(CONFIG_ENV)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    });
+    if let Some(path) = explicit {
+        let path = if path.is_dir() {
+            path.join(&file_name)
+        } else {
+            path
+        };
+        return if path.is_file() {
+            Ok(path)
+        } else {
+            Err(format!("Config file not found: {}", path.display()))
+        };
+    }
+
+    let current_dir = std::env::current_dir().map_err(|why| why.to_string())?;
+    if variant.is_none()
+        && !current_dir.join(CONFIG_FILE).is_file()
+        && current_dir.join(LEGACY_CONFIG_FILE).is_file()
+    {
+        return Ok(current_dir.join(LEGACY_CONFIG_FILE));
+    }
+    current_dir
+        .ancestors()
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            format!(
+                "Could not find {} in {} or any parent directory",
+                file_name,
+                current_dir.display()
+            )
+        })
+}
+
+/// Struct describing the layout of the build directory
+/// # Notes
+/// Every path produced by a build hangs off `root`, so that the build directory can be
+/// moved out of the source tree with `--build-dir` or `[build] out_dir`.
+/// Outputs that depend on the OS config live in a per-profile directory named after
+/// the platform, mode and ulib (e.g. `x86_64-qemu-q35-release-ruxmusl`), local builds
+/// use the `host` profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildPaths {
+    pub root: String,
+    pub profile: String,
+}
+
+impl Default for BuildPaths {
+    fn default() -> Self {
+        BuildPaths::new(DEFAULT_BUILD_DIR, &OSConfig::default())
+    }
+}
+
+impl BuildPaths {
+    /// Creates the build paths rooted at `root` for the given os config
+    pub fn new(root: &str, os_config: &OSConfig) -> Self {
+        let root = root.trim_end_matches('/');
+        let root = if root.is_empty() {
+            DEFAULT_BUILD_DIR.to_string()
+        } else {
+            root.to_string()
+        };
+        let profile = if os_config.name.is_empty() {
+            "host".to_string()
+        } else {
+            let mode = if os_config.platform.mode.is_empty() {
+                "debug"
+            } else {
+                &os_config.platform.mode
+            };
+            format!("{}-{}-{}", os_config.platform.name, mode, os_config.ulib)
+        };
+        BuildPaths { root, profile }
+    }
+
+    /// Directory of all outputs of the current profile
+    pub fn profile_dir(&self) -> String {
+        format!("{}/{}", self.root, self.profile)
+    }
+
+    /// Directory of the linked targets
+    pub fn bin_dir(&self) -> String {
+        format!("{}/bin", self.profile_dir())
+    }
+
+    /// Directory of the object files
+    pub fn obj_dir(&self) -> String {
+        format!("{}/obj", self.profile_dir())
+    }
+
+    /// Cargo target directory of the OS build
+    pub fn target_dir(&self) -> String {
+        format!("{}/target", self.profile_dir())
+    }
+
+    /// The linked output of a target and, for executables, its ELF image
+    /// # Arguments
+    /// * `target_name` - The name of the target
+    /// * `typ` - The type of the target, `exe`, `dll`, `static` or `object`
+    pub fn target_bin(&self, target_name: &str, typ: &str) -> (String, String) {
+        let mut bin_path = format!("{}/{}", self.bin_dir(), target_name);
+        let mut elf_path = String::new();
+        #[cfg(target_os = "windows")]
+        match typ {
+            "exe" => bin_path.push_str(".exe"),
+            "dll" => bin_path.push_str(".dll"),
+            "static" => bin_path.push_str(".lib"),
+            _ => (),
+        }
+        #[cfg(target_os = "linux")]
+        match typ {
+            "exe" => {
+                elf_path = format!("{}.elf", bin_path);
+                bin_path.push_str(".bin");
+            }
+            "dll" => bin_path.push_str(".so"),
+            "static" => bin_path.push_str(".a"),
+            "object" => bin_path.push_str(".o"),
+            _ => (),
+        }
+        (bin_path, elf_path)
+    }
+
+    /// Manifest of a target
+    pub fn manifest_file(&self, target_name: &str) -> String {
+        format!("{}/{}.manifest.json", self.profile_dir(), target_name)
+    }
+
+    /// Directory holding the patch state and the original files of a patched directory
+    /// # Arguments
+    /// * `dir` - The patched directory, relative to the project root
+    pub fn patch_dir(&self, dir: &str) -> String {
+        let name: String = dir
+            .trim_start_matches("./")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        // The hash keeps directories apart whose names only differ in other characters
+        let hash = Hasher::hash_string(dir);
+        format!("{}/patches/{}-{}", self.root, name, &hash[..8])
+    }
+
+    /// Manifest of a custom build step of a target
+    pub fn step_manifest_file(&self, target_name: &str, step_name: &str) -> String {
+        format!(
+            "{}/{}.step-{}.manifest.json",
+            self.profile_dir(),
+            target_name,
+            step_name
+        )
+    }
+
+    /// Hash file of a target written by older versions, removed before building
+    pub fn legacy_hash_file(&self, target_name: &str) -> String {
+        format!("{}/{}.{}.hash", self.root, target_name, LEGACY_PLATFORM)
+    }
+
+    /// Directory of the object files written by older versions, before profiles
+    pub fn legacy_obj_dir(&self) -> String {
+        format!("{}/obj_{}", self.root, LEGACY_PLATFORM)
+    }
+
+    /// Directory of the linked targets written by older versions, before profiles
+    pub fn legacy_bin_dir(&self) -> String {
+        format!("{}/bin", self.root)
+    }
+
+    /// Hash file of the OS config
+    pub fn os_config_hash(&self) -> String {
+        format!("{}/os_config.hash", self.profile_dir())
+    }
+
+    /// The ruxlibc static library
+    pub fn ruxlibc_bin(&self) -> String {
+        format!("{}/libc.a", self.bin_dir())
+    }
+
+    /// Build directory of ruxmusl
+    pub fn ruxmusl_dir(&self) -> String {
+        format!("{}/ruxmusl", self.profile_dir())
+    }
+
+    /// Installed ruxmusl headers
+    pub fn ruxmusl_inc(&self) -> String {
+        format!("{}/install/include", self.ruxmusl_dir())
+    }
+
+    /// Installed ruxmusl static library
+    pub fn ruxmusl_bin(&self) -> String {
+        format!("{}/install/lib/libc.a", self.ruxmusl_dir())
+    }
+
+    /// Generated source of the app loader
+    pub fn loader_src(&self) -> String {
+        format!("{}/loader.c", self.root)
+    }
+
+    /// The generated compile_commands.json
+    pub fn compile_commands(&self) -> String {
+        format!("{}/compile_commands.json", self.root)
+    }
+}
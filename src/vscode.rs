@@ -0,0 +1,284 @@
+//! VS Code Module
+
+use crate::builder::Target;
+use crate::commands;
+use crate::compdb;
+use crate::error::{Error, Result};
+use crate::hasher::Hasher;
+use crate::parser::{self, BuildConfig, OSConfig, QemuConfig, TargetConfig};
+use crate::utils::log::{log, LogLevel};
+use serde::Serialize;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+/// The directory of the VS Code workspace settings
+static VSCODE_DIR: &str = ".vscode";
+
+/// The label of the task starting qemu for gdb, run before the debugger attaches
+static QEMU_TASK: &str = "ruxgo: qemu (gdb)";
+
+/// The line printed by the qemu task once qemu waits for gdb
+static QEMU_READY: &str = "QEMU is listening for GDB connection on port 1234";
+
+/// Struct describing a configuration of c_cpp_properties.json
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CppConfiguration {
+    /// The name of the target the configuration is taken from
+    pub name: String,
+    pub include_path: Vec<String>,
+    pub defines: Vec<String>,
+    pub compiler_path: String,
+    /// The compiler arguments that are not includes or defines, e.g. `-nostdinc`
+    pub compiler_args: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub c_standard: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub cpp_standard: String,
+    /// Empty if VS Code has no mode for the architecture, it is then taken from the compiler
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub intelli_sense_mode: String,
+}
+
+/// Writes the VS Code workspace of the project into `.vscode`
+/// Returns the paths of the written files.
+/// # Notes
+/// `c_cpp_properties.json` has one configuration per target, taken from the command
+/// compiling its first source. `tasks.json` builds, runs and cleans the project through
+/// ruxgo. `launch.json` debugs the executable, on the host with gdb or, on a qemu
+/// platform, by starting qemu with `-s -S` and attaching gdb-multiarch to the ELF.
+/// # Arguments
+/// * `build_config` - The local build configuration
+/// * `os_config` - The local os configuration
+/// * `targets` - The targets, including the loader, see `commands::prepare_targets`
+/// * `config_file` - The config file, passed to ruxgo by the tasks
+pub fn write(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    targets: &Vec<TargetConfig>,
+    config_file: &str,
+) -> Result<Vec<String>> {
+    let mut configurations = Vec::new();
+    for target in targets {
+        let tgt = Target::new(build_config, os_config, target, targets)?;
+        configurations.push(cpp_configuration(build_config, os_config, target, &tgt)?);
+    }
+    let cpp_properties = json!({
+        "configurations": configurations,
+        "version": 4,
+    });
+
+    // The executable may be missing, e.g. in a workspace of libraries
+    let mut qemu_command = None;
+    let mut program = None;
+    if let Ok(exe_target) = commands::exe_target(targets) {
+        let tgt = Target::new(build_config, os_config, exe_target, targets)?;
+        let qemu = &os_config.platform.qemu;
+        if !os_config.name.is_empty() && qemu != &QemuConfig::default() {
+            let (_, debug_command) = qemu.config_qemu(&os_config.platform, &tgt)?;
+            qemu_command = Some(debug_command.join(" "));
+            program = Some(tgt.elf_path.clone());
+        } else if os_config.name.is_empty() {
+            program = Some(tgt.bin_path.clone());
+        }
+    }
+
+    let ruxgo = |arg: &str| {
+        vec![
+            "--config".to_string(),
+            config_file.to_string(),
+            arg.to_string(),
+        ]
+    };
+    let mut tasks = vec![
+        json!({
+            "label": "ruxgo: build",
+            "type": "shell",
+            "command": "ruxgo",
+            "args": ruxgo("-b"),
+            "group": { "kind": "build", "isDefault": true },
+            "problemMatcher": ["$gcc"],
+        }),
+        json!({
+            "label": "ruxgo: run",
+            "type": "shell",
+            "command": "ruxgo",
+            "args": ruxgo("-r"),
+            "problemMatcher": [],
+        }),
+        json!({
+            "label": "ruxgo: clean",
+            "type": "shell",
+            "command": "ruxgo",
+            "args": ruxgo("-c"),
+            "problemMatcher": [],
+        }),
+    ];
+    if let Some(qemu_command) = &qemu_command {
+        tasks.push(json!({
+            "label": QEMU_TASK,
+            "type": "shell",
+            "command": format!("echo '{}' && {}", QEMU_READY, qemu_command),
+            "dependsOn": "ruxgo: build",
+            "isBackground": true,
+            "problemMatcher": {
+                "owner": "ruxgo",
+                "pattern": { "regexp": "^$" },
+                "background": {
+                    "activeBegins": true,
+                    "beginsPattern": QEMU_READY,
+                    "endsPattern": QEMU_READY,
+                },
+            },
+        }));
+    }
+    let tasks = json!({ "version": "2.0.0", "tasks": tasks });
+
+    let mut files = vec![
+        ("c_cpp_properties.json", cpp_properties),
+        ("tasks.json", tasks),
+    ];
+    if let Some(program) = program {
+        let launch = match qemu_command {
+            Some(_) => json!({
+                "name": "ruxgo: debug in qemu",
+                "type": "cppdbg",
+                "request": "launch",
+                "program": workspace_path(&program),
+                "cwd": "${workspaceFolder}",
+                "MIMode": "gdb",
+                "miDebuggerPath": "gdb-multiarch",
+                "miDebuggerServerAddress": "localhost:1234",
+                "preLaunchTask": QEMU_TASK,
+            }),
+            None => json!({
+                "name": "ruxgo: debug",
+                "type": "cppdbg",
+                "request": "launch",
+                "program": workspace_path(&program),
+                "args": [],
+                "cwd": "${workspaceFolder}",
+                "MIMode": "gdb",
+                "miDebuggerPath": "gdb",
+                "preLaunchTask": "ruxgo: build",
+            }),
+        };
+        let launch = json!({ "version": "0.2.0", "configurations": [launch] });
+        files.push(("launch.json", launch));
+    }
+
+    fs::create_dir_all(VSCODE_DIR)
+        .map_err(|why| Error::io(format!("Could not create {} directory", VSCODE_DIR), why))?;
+    let mut written = Vec::new();
+    for (name, contents) in files {
+        let path = format!("{}/{}", VSCODE_DIR, name);
+        let contents = serde_json::to_string_pretty(&contents)
+            .map_err(|why| Error::io(format!("Could not serialize {}", path), why.into()))?;
+        Hasher::write_atomic(&path, contents.as_bytes())?;
+        log(LogLevel::Log, &format!("Generated {}", path));
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Returns the VS Code configuration of a target
+/// # Notes
+/// Includes and defines are taken from the compile command, so that they match the
+/// build, including the ones added for the OS.
+fn cpp_configuration(
+    build_config: &BuildConfig,
+    os_config: &OSConfig,
+    target: &TargetConfig,
+    tgt: &Target,
+) -> Result<CppConfiguration> {
+    let mut config = CppConfiguration {
+        name: target.name.clone(),
+        ..Default::default()
+    };
+    let units = tgt.compile_units();
+    let (compiler, args) = match units.first() {
+        Some(unit) => {
+            let argv = compdb::split_command(&unit.command)?;
+            let args = argv[1..]
+                .iter()
+                .filter(|arg| **arg != unit.file && **arg != unit.object)
+                .cloned()
+                .collect();
+            (argv[0].clone(), args)
+        }
+        None => (build_config.toolchain.cc.clone(), Vec::new()),
+    };
+    let is_cpp = units.first().is_some_and(|unit| {
+        build_config.toolchain.compiler_for(&unit.file) == build_config.toolchain.cxx
+    });
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("-D") {
+            config.defines.push(flag_value(&arg, "-D", &mut args));
+        } else if arg.starts_with("-isystem") {
+            let include = flag_value(&arg, "-isystem", &mut args);
+            config.include_path.push(workspace_path(&include));
+        } else if arg.starts_with("-I") {
+            let include = flag_value(&arg, "-I", &mut args);
+            config.include_path.push(workspace_path(&include));
+        } else if let Some(std) = arg.strip_prefix("-std=") {
+            match is_cpp {
+                true => config.cpp_standard = std.to_string(),
+                false => config.c_standard = std.to_string(),
+            }
+        } else if arg != "-o" && arg != "-c" {
+            config.compiler_args.push(arg);
+        }
+    }
+    // Headers of the target that are not in an include directory
+    for include in &target.include_dir {
+        let include = workspace_path(include);
+        if !config.include_path.contains(&include) {
+            config.include_path.push(include);
+        }
+    }
+
+    config.compiler_path = parser::resolve_program(&compiler)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or(compiler.clone());
+    let arch = if !os_config.name.is_empty() {
+        os_config.platform.arch.as_str()
+    } else {
+        std::env::consts::ARCH
+    };
+    let family = if compiler.contains("clang") {
+        "clang"
+    } else {
+        "gcc"
+    };
+    let arch = match arch {
+        "x86_64" => "x64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        _ => "",
+    };
+    if !arch.is_empty() {
+        config.intelli_sense_mode = format!("{}-{}-{}", std::env::consts::OS, family, arch);
+    }
+    Ok(config)
+}
+
+/// Returns the value of a flag, either attached like `-DFOO` or the next argument
+fn flag_value(arg: &str, flag: &str, args: &mut impl Iterator<Item = String>) -> String {
+    match &arg[flag.len()..] {
+        "" => args.next().unwrap_or_default(),
+        value => value.to_string(),
+    }
+}
+
+/// Returns a path relative to the project root as a path in the VS Code workspace
+fn workspace_path(path: &str) -> String {
+    if Path::new(path).is_absolute() {
+        path.to_string()
+    } else {
+        format!("${{workspaceFolder}}/{}", path.trim_start_matches("./"))
+    }
+}